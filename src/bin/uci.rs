@@ -0,0 +1,9 @@
+//! A UCI chess engine, reading commands from standard input and writing answers to standard
+//! output.
+
+use std::io;
+
+fn main() -> io::Result<()> {
+    let stdin = io::stdin();
+    chess::prelude::run_uci(stdin.lock(), io::stdout())
+}
@@ -0,0 +1,9 @@
+//! A chess engine speaking the xboard protocol, reading commands from standard input and
+//! writing answers to standard output.
+
+use std::io;
+
+fn main() -> io::Result<()> {
+    let stdin = io::stdin();
+    chess::prelude::run_xboard(stdin.lock(), io::stdout())
+}
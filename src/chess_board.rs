@@ -357,6 +357,134 @@ impl ChessBoard {
             }
     }
 
+    /// Get every piece of `color` that attacks `square`.
+    pub fn attackers_of(&self, square: Position, color: Color) -> Bitboard {
+        self.attackers_with_occupancy(square, color, self.all_pieces)
+    }
+
+    /// Get all pieces of `attacker_color` attacking `target`, treating only the squares in
+    /// `occupancy` as occupied.
+    ///
+    /// Removing a piece from `occupancy` both removes it as an attacker and reveals any sliding
+    /// piece behind it.
+    pub(crate) fn attackers_with_occupancy(
+        &self,
+        target: Position,
+        attacker_color: Color,
+        occupancy: Bitboard,
+    ) -> Bitboard {
+        use Color::*;
+        use PieceType::*;
+
+        let mut attacker_bb = Bitboard::empty();
+        for piece_type in PieceType::all_iter() {
+            attacker_bb |= match (attacker_color, piece_type) {
+                (Black, Pawn) => {
+                    (Position::up_left(&target)
+                        .map(Bitboard::with_one)
+                        .unwrap_or(Bitboard::empty())
+                        | Position::up_right(&target)
+                            .map(Bitboard::with_one)
+                            .unwrap_or(Bitboard::empty()))
+                        & self.get_bitboard(Black, Pawn)
+                }
+                (Black, Knight) => {
+                    Bitboard::knight_targets(target) & self.get_bitboard(Black, Knight)
+                }
+                (Black, Bishop) => {
+                    Bitboard::bishop_targets(target, occupancy) & self.get_bitboard(Black, Bishop)
+                }
+                (Black, Rook) => {
+                    Bitboard::rook_targets(target, occupancy) & self.get_bitboard(Black, Rook)
+                }
+                (Black, Queen) => {
+                    Bitboard::queen_targets(target, occupancy) & self.get_bitboard(Black, Queen)
+                }
+                (Black, King) => Bitboard::king_targets(target) & self.get_bitboard(Black, King),
+                (White, Pawn) => {
+                    (Position::down_left(&target)
+                        .map(Bitboard::with_one)
+                        .unwrap_or(Bitboard::empty())
+                        | Position::down_right(&target)
+                            .map(Bitboard::with_one)
+                            .unwrap_or(Bitboard::empty()))
+                        & self.get_bitboard(White, Pawn)
+                }
+                (White, Knight) => {
+                    Bitboard::knight_targets(target) & self.get_bitboard(White, Knight)
+                }
+                (White, Bishop) => {
+                    Bitboard::bishop_targets(target, occupancy) & self.get_bitboard(White, Bishop)
+                }
+                (White, Rook) => {
+                    Bitboard::rook_targets(target, occupancy) & self.get_bitboard(White, Rook)
+                }
+                (White, Queen) => {
+                    Bitboard::queen_targets(target, occupancy) & self.get_bitboard(White, Queen)
+                }
+                (White, King) => Bitboard::king_targets(target) & self.get_bitboard(White, King),
+            }
+        }
+        attacker_bb & occupancy
+    }
+
+    /// Get the squares attacked by the piece on `pos`, whether they are empty or occupied by
+    /// either side.
+    ///
+    /// # Returns
+    /// The attacked squares, or an empty bitboard if there is no piece on `pos`.
+    pub fn attacks_from(&self, pos: Position) -> Bitboard {
+        let piece = match self.get_piece(pos) {
+            Some(piece) => piece,
+            None => return Bitboard::empty(),
+        };
+        match piece.kind() {
+            PieceType::Pawn => {
+                let (left, right) = match piece.color() {
+                    Color::Black => (pos.down_left(), pos.down_right()),
+                    Color::White => (pos.up_left(), pos.up_right()),
+                };
+                [left, right]
+                    .iter()
+                    .flatten()
+                    .fold(Bitboard::empty(), |attacks, &target| {
+                        attacks | Bitboard::with_one(target)
+                    })
+            }
+            PieceType::Knight => Bitboard::knight_targets(pos),
+            PieceType::Bishop => Bitboard::bishop_targets(pos, self.all_pieces),
+            PieceType::Rook => Bitboard::rook_targets(pos, self.all_pieces),
+            PieceType::Queen => Bitboard::queen_targets(pos, self.all_pieces),
+            PieceType::King => Bitboard::king_targets(pos),
+        }
+    }
+
+    /// Get every square attacked by at least one piece of `color`.
+    pub fn attacked_squares(&self, color: Color) -> Bitboard {
+        self.get_occupancy_for_color(color)
+            .positions()
+            .into_iter()
+            .fold(Bitboard::empty(), |attacked, pos| {
+                attacked | self.attacks_from(pos)
+            })
+    }
+
+    /// Get the number of pieces of `color` that attack `square`.
+    pub fn control_count(&self, square: Position, color: Color) -> u32 {
+        self.attackers_of(square, color).positions().len() as u32
+    }
+
+    /// Returns `true` if `color` has enough material to possibly checkmate, which is any pawn,
+    /// rook or queen, or at least two minor pieces.
+    pub fn has_mating_material(&self, color: Color) -> bool {
+        let major_or_pawn = self.get_bitboard(color, PieceType::Pawn)
+            | self.get_bitboard(color, PieceType::Rook)
+            | self.get_bitboard(color, PieceType::Queen);
+        let minor = self.get_bitboard(color, PieceType::Knight)
+            | self.get_bitboard(color, PieceType::Bishop);
+        major_or_pawn != Bitboard::empty() || minor.positions().len() >= 2
+    }
+
     pub fn to_pretty_string(&self) -> String {
         use Color::*;
         use PieceType::*;
@@ -521,6 +649,38 @@ mod tests {
         );
     }
 
+    #[test]
+    fn attackers_of_test() {
+        let b = ChessBoard::default();
+        assert_eq!(
+            b.attackers_of(F3, Color::White),
+            Bitboard::with_ones([E2, G2, G1])
+        );
+        assert_eq!(b.attackers_of(F3, Color::Black), Bitboard::empty());
+        // the pawns shield the queen
+        assert_eq!(
+            b.attackers_of(D3, Color::White),
+            Bitboard::with_ones([C2, E2])
+        );
+        assert_eq!(b.control_count(F3, Color::White), 3);
+        assert_eq!(b.control_count(E4, Color::White), 0);
+    }
+
+    #[test]
+    fn attacked_squares_test() {
+        let b = ChessBoard::default();
+        assert_eq!(
+            b.attacked_squares(Color::White) & Bitboard::with_ones([A3, H3, E4, E2]),
+            Bitboard::with_ones([A3, H3, E2])
+        );
+        assert_eq!(b.attacks_from(E2), Bitboard::with_ones([D3, F3]));
+        assert_eq!(
+            b.attacks_from(D8),
+            Bitboard::with_ones([C8, E8, C7, D7, E7])
+        );
+        assert_eq!(b.attacks_from(E4), Bitboard::empty());
+    }
+
     #[test]
     fn take_piece_test() {
         let mut b = ChessBoard::default();
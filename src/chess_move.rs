@@ -102,6 +102,18 @@ impl ChessMove {
             .collect()
     }
 
+    /// Get the move in the long algebraic notation used by UCI, such as `e2e4` or `e7e8q`.
+    ///
+    /// Castling is written as the move of the king, such as `e1g1`.
+    pub fn to_uci_string(&self) -> String {
+        match self {
+            ChessMove::Promotion { from, to, piece } => {
+                format!("{}{}{}", from, to, piece.uci_char())
+            }
+            _ => format!("{}{}", self.from(), self.to()),
+        }
+    }
+
     /// Returns `true` if the chess move is [`Regular`].
     ///
     /// [`Regular`]: ChessMove::Regular
@@ -136,6 +148,11 @@ impl ChessMove {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
 pub enum PromotionPiece {
     Knight,
     Bishop,
@@ -144,6 +161,15 @@ pub enum PromotionPiece {
 }
 
 impl PromotionPiece {
+    pub(crate) fn uci_char(&self) -> char {
+        match self {
+            PromotionPiece::Knight => 'n',
+            PromotionPiece::Bishop => 'b',
+            PromotionPiece::Rook => 'r',
+            PromotionPiece::Queen => 'q',
+        }
+    }
+
     pub(crate) fn create_piece(&self, color: Color) -> Piece {
         match self {
             PromotionPiece::Knight => Piece::knight(color),
@@ -165,6 +191,18 @@ pub(crate) struct MoveManager {
     castling_rights: CastlingRights,
     half_moves: u32,
     full_moves: u32,
+    previous_states: Vec<PreviousState>,
+}
+
+/// The state of a game before a move was made, used to undo the move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PreviousState {
+    board: ChessBoard,
+    white_en_passant_target: Option<Position>,
+    black_en_passant_target: Option<Position>,
+    castling_rights: CastlingRights,
+    half_moves: u32,
+    full_moves: u32,
 }
 
 impl MoveManager {
@@ -187,6 +225,7 @@ impl MoveManager {
             castling_rights,
             half_moves,
             full_moves,
+            previous_states: vec![],
         }
     }
 
@@ -214,6 +253,10 @@ impl MoveManager {
         self.full_moves
     }
 
+    pub(crate) fn move_history(&self) -> &[ChessMove] {
+        &self.move_history
+    }
+
     pub(crate) fn dry_run_move(
         &self,
         board: &mut ChessBoard,
@@ -269,6 +312,15 @@ impl MoveManager {
         player: Color,
         chess_move: ChessMove,
     ) -> Option<Piece> {
+        self.previous_states.push(PreviousState {
+            board: *board,
+            white_en_passant_target: self.white_en_passant_target,
+            black_en_passant_target: self.black_en_passant_target,
+            castling_rights: self.castling_rights,
+            half_moves: self.half_moves,
+            full_moves: self.full_moves,
+        });
+
         let mut moved_pawn = false;
         if let ChessMove::Regular { from, to } = chess_move {
             if let Some(Piece {
@@ -291,8 +343,10 @@ impl MoveManager {
                     }
                 }
             }
-            self.update_castling_rights(from);
         }
+        // moving the king or a rook, or capturing a rook, loses the right to castle with it
+        self.update_castling_rights(chess_move.from());
+        self.update_castling_rights(chess_move.to());
 
         let taken_piece = self.dry_run_move(board, player, chess_move);
 
@@ -319,33 +373,55 @@ impl MoveManager {
         }
 
         self.board_history.push(*board);
+        self.move_history.push(chess_move);
 
         taken_piece
     }
 
+    /// Undo the last move made, restoring `board` to the position before it.
+    ///
+    /// The legal moves are not evaluated for the restored position.
+    ///
+    /// # Returns
+    /// The move that was undone, or `None` if no moves have been made.
+    pub(crate) fn undo_move(&mut self, board: &mut ChessBoard) -> Option<ChessMove> {
+        let previous = self.previous_states.pop()?;
+        self.board_history.pop();
+        let chess_move = self.move_history.pop();
+
+        *board = previous.board;
+        self.white_en_passant_target = previous.white_en_passant_target;
+        self.black_en_passant_target = previous.black_en_passant_target;
+        self.castling_rights = previous.castling_rights;
+        self.half_moves = previous.half_moves;
+        self.full_moves = previous.full_moves;
+
+        chess_move
+    }
+
     pub fn get_legal_moves(&self) -> &HashSet<ChessMove> {
         &self.legal_moves
     }
 
-    fn update_castling_rights(&mut self, from: Position) {
-        if from == E1 {
+    fn update_castling_rights(&mut self, square: Position) {
+        if square == E1 {
             *self.castling_rights.white_kingside_mut() = false;
             *self.castling_rights.white_queenside_mut() = false;
         }
-        if from == A1 {
+        if square == A1 {
             *self.castling_rights.white_queenside_mut() = false;
         }
-        if from == A8 {
+        if square == H1 {
             *self.castling_rights.white_kingside_mut() = false;
         }
-        if from == E8 {
+        if square == E8 {
             *self.castling_rights.black_kingside_mut() = false;
             *self.castling_rights.black_queenside_mut() = false;
         }
-        if from == A8 {
+        if square == A8 {
             *self.castling_rights.black_queenside_mut() = false;
         }
-        if from == H8 {
+        if square == H8 {
             *self.castling_rights.black_kingside_mut() = false;
         }
     }
@@ -369,6 +445,45 @@ impl MoveManager {
         self.legal_moves = actual_legal_moves;
     }
 
+    /// Evaluate legal captures and promotions for `player`, without evaluating the full set of
+    /// legal moves.
+    ///
+    /// If `include_checks` is `true`, quiet moves that put the opponent in check are included
+    /// as well.
+    pub(crate) fn evaluate_tactical_moves(
+        &self,
+        board: &ChessBoard,
+        player: Color,
+        include_checks: bool,
+    ) -> Vec<ChessMove> {
+        let mut tactical_moves = Vec::with_capacity(16);
+        for pos in board.get_occupancy_for_color(player).positions() {
+            for chess_move in self.evaluate_legal_moves_from(board, pos, player) {
+                let is_capture_or_promotion = match chess_move {
+                    ChessMove::Regular { to, .. } => {
+                        board.has_piece_of_color_at(player.opponent(), to)
+                    }
+                    ChessMove::EnPassant { .. } | ChessMove::Promotion { .. } => true,
+                    ChessMove::Castle { .. } => false,
+                };
+                if !is_capture_or_promotion && !include_checks {
+                    continue;
+                }
+
+                let mut board_clone = *board;
+                self.dry_run_move(&mut board_clone, player, chess_move);
+                if self.is_in_check(&board_clone, player) {
+                    continue;
+                }
+                if is_capture_or_promotion || self.is_in_check(&board_clone, player.opponent()) {
+                    tactical_moves.push(chess_move);
+                }
+            }
+        }
+
+        tactical_moves
+    }
+
     pub fn is_in_check(&self, board: &ChessBoard, player: Color) -> bool {
         match player {
             Color::Black => {
@@ -396,7 +511,12 @@ impl MoveManager {
         }
     }
 
-    fn is_under_attack(&self, board: &ChessBoard, target: Position, attacker_color: Color) -> bool {
+    pub(crate) fn is_under_attack(
+        &self,
+        board: &ChessBoard,
+        target: Position,
+        attacker_color: Color,
+    ) -> bool {
         use Color::*;
         use PieceType::*;
 
@@ -409,68 +529,81 @@ impl MoveManager {
         target: Position,
         attacker_color: Color,
     ) -> Bitboard {
-        use Color::*;
-        use PieceType::*;
+        board.attackers_of(target, attacker_color)
+    }
 
-        let mut attacker_bb = Bitboard::empty();
-        for piece_type in PieceType::all_iter() {
-            attacker_bb |= match (attacker_color, piece_type) {
-                (Black, Pawn) => {
-                    (Position::up_left(&target)
-                        .map(|p| Bitboard::with_one(p))
-                        .unwrap_or(Bitboard::empty())
-                        | Position::up_right(&target)
-                            .map(|p| Bitboard::with_one(p))
-                            .unwrap_or(Bitboard::empty()))
-                        & board.get_bitboard(Black, Pawn)
-                }
-                (Black, Knight) => {
-                    Bitboard::knight_targets(target) & board.get_bitboard(Black, Knight)
-                }
-                (Black, Bishop) => {
-                    Bitboard::bishop_targets(target, board.full_occupancy())
-                        & board.get_bitboard(Black, Bishop)
-                }
-                (Black, Rook) => {
-                    Bitboard::rook_targets(target, board.full_occupancy())
-                        & board.get_bitboard(Black, Rook)
-                }
-                (Black, Queen) => {
-                    Bitboard::queen_targets(target, board.full_occupancy())
-                        & board.get_bitboard(Black, Queen)
-                }
-                (Black, King) => Bitboard::king_targets(target) & board.get_bitboard(Black, King),
-                (White, Pawn) => {
-                    (Position::down_left(&target)
-                        .map(|p| Bitboard::with_one(p))
-                        .unwrap_or(Bitboard::empty())
-                        | Position::down_right(&target)
-                            .map(|p| Bitboard::with_one(p))
-                            .unwrap_or(Bitboard::empty()))
-                        & board.get_bitboard(White, Pawn)
-                }
-                (White, Knight) => {
-                    Bitboard::knight_targets(target) & board.get_bitboard(White, Knight)
-                }
-                (White, Bishop) => {
-                    Bitboard::bishop_targets(target, board.full_occupancy())
-                        & board.get_bitboard(White, Bishop)
-                }
-                (White, Rook) => {
-                    Bitboard::rook_targets(target, board.full_occupancy())
-                        & board.get_bitboard(White, Rook)
-                }
-                (White, Queen) => {
-                    Bitboard::queen_targets(target, board.full_occupancy())
-                        & board.get_bitboard(White, Queen)
-                }
-                (White, King) => Bitboard::king_targets(target) & board.get_bitboard(White, King),
+    /// Statically evaluate the exchange sequence started by `chess_move` on its target square.
+    ///
+    /// Both sides keep recapturing with their least valuable attacker, including sliding pieces
+    /// revealed behind pieces that have left the square, and either side may stop capturing
+    /// whenever continuing would lose material.
+    ///
+    /// # Returns
+    /// The expected material gain for `player`, measured with [`PieceType::value`].
+    pub(crate) fn static_exchange_evaluation(
+        &self,
+        board: &ChessBoard,
+        player: Color,
+        chess_move: ChessMove,
+    ) -> i32 {
+        let target = chess_move.to();
+        let mut occupancy = board.full_occupancy() & !Bitboard::with_one(chess_move.from());
+
+        let mut gain = Vec::with_capacity(32);
+        let mut piece_on_target_value = match chess_move {
+            ChessMove::Regular { from, to } => {
+                gain.push(board.get_piece(to).map(|p| p.kind().value()).unwrap_or(0));
+                board.get_piece(from).unwrap().kind().value()
+            }
+            ChessMove::EnPassant { taken_index, .. } => {
+                occupancy &= !Bitboard::with_one(taken_index);
+                gain.push(PieceType::Pawn.value());
+                PieceType::Pawn.value()
+            }
+            ChessMove::Promotion { to, piece, .. } => {
+                let promoted_value = piece.create_piece(player).kind().value();
+                let taken_value = board.get_piece(to).map(|p| p.kind().value()).unwrap_or(0);
+                gain.push(taken_value + promoted_value - PieceType::Pawn.value());
+                promoted_value
             }
+            ChessMove::Castle { .. } => return 0,
+        };
+
+        let mut side = player.opponent();
+        loop {
+            let attackers = board.attackers_with_occupancy(target, side, occupancy);
+            let least_valuable = PieceType::all_iter().find_map(|kind| {
+                (attackers & board.get_bitboard(side, kind))
+                    .first_position()
+                    .map(|pos| (pos, kind))
+            });
+            let (attacker_pos, attacker_kind) = match least_valuable {
+                Some(attacker) => attacker,
+                None => break,
+            };
+
+            occupancy &= !Bitboard::with_one(attacker_pos);
+            if attacker_kind == PieceType::King
+                && board.attackers_with_occupancy(target, side.opponent(), occupancy) != 0
+            {
+                // the king can't recapture into a defended square
+                break;
+            }
+
+            gain.push(piece_on_target_value - gain[gain.len() - 1]);
+            piece_on_target_value = attacker_kind.value();
+            side = side.opponent();
         }
-        attacker_bb
+
+        while gain.len() > 1 {
+            let last = gain.pop().unwrap();
+            let previous = gain.last_mut().unwrap();
+            *previous = -std::cmp::max(-*previous, last);
+        }
+        gain[0]
     }
 
-    fn evaluate_legal_moves_from(
+    pub(crate) fn evaluate_legal_moves_from(
         &self,
         board: &ChessBoard,
         from: Position,
@@ -642,6 +775,11 @@ impl MoveManager {
         use bitboard::*;
         let mut moves = Vec::with_capacity(2);
 
+        // the king can't castle out of check
+        if self.is_under_attack(board, E1, Color::Black) {
+            return Some(moves);
+        }
+
         // check short castle
         if self.castling_rights.white_kingside()
             && !board.has_piece_at(F1)
@@ -664,7 +802,6 @@ impl MoveManager {
             && !board.has_piece_at(B1)
             && !self.is_under_attack(board, D1, Color::Black)
             && !self.is_under_attack(board, C1, Color::Black)
-            && !self.is_under_attack(board, B1, Color::Black)
         {
             moves.push(ChessMove::Castle {
                 rook_from: A1,
@@ -682,6 +819,11 @@ impl MoveManager {
 
         let mut moves = Vec::with_capacity(2);
 
+        // the king can't castle out of check
+        if self.is_under_attack(board, E8, Color::White) {
+            return Some(moves);
+        }
+
         // check short castle
         if self.castling_rights.black_kingside()
             && !board.has_piece_at(F8)
@@ -704,7 +846,6 @@ impl MoveManager {
             && !board.has_piece_at(B8)
             && !self.is_under_attack(board, D8, Color::White)
             && !self.is_under_attack(board, C8, Color::White)
-            && !self.is_under_attack(board, B8, Color::White)
         {
             moves.push(ChessMove::Castle {
                 rook_from: A8,
@@ -790,11 +931,14 @@ impl Default for MoveManager {
             castling_rights: CastlingRights::default(),
             half_moves: 0,
             full_moves: 1,
+            previous_states: vec![],
         }
     }
 }
+/// Which castling moves each player still has the right to make.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub(crate) struct CastlingRights {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CastlingRights {
     white_kingside: bool,
     white_queenside: bool,
     black_kingside: bool,
@@ -816,7 +960,7 @@ impl CastlingRights {
         }
     }
 
-    pub(crate) fn white_kingside(&self) -> bool {
+    pub fn white_kingside(&self) -> bool {
         self.white_kingside
     }
 
@@ -824,7 +968,7 @@ impl CastlingRights {
         &mut self.white_kingside
     }
 
-    pub(crate) fn white_queenside(&self) -> bool {
+    pub fn white_queenside(&self) -> bool {
         self.white_queenside
     }
 
@@ -832,7 +976,7 @@ impl CastlingRights {
         &mut self.white_queenside
     }
 
-    pub(crate) fn black_kingside(&self) -> bool {
+    pub fn black_kingside(&self) -> bool {
         self.black_kingside
     }
 
@@ -840,7 +984,7 @@ impl CastlingRights {
         &mut self.black_kingside
     }
 
-    pub(crate) fn black_queenside(&self) -> bool {
+    pub fn black_queenside(&self) -> bool {
         self.black_queenside
     }
 
@@ -930,8 +1074,6 @@ impl FromStr for CastlingRights {
                     _ => return Err("invalid castling rights".to_string()),
                 }
             }
-        } else {
-            (wk, wq, bk, bq) = (true, true, true, true)
         }
 
         Ok(CastlingRights::new(wk, wq, bk, bq))
@@ -1145,4 +1287,18 @@ mod tests {
         assert_eq!(moves.len(), expected.len());
         assert_eq!(moves, &expected);
     }
+
+    #[test]
+    fn castling_rights_from_str() {
+        assert_eq!(
+            CastlingRights::from_str("-"),
+            Ok(CastlingRights::new(false, false, false, false))
+        );
+        assert_eq!(
+            CastlingRights::from_str("Kq"),
+            Ok(CastlingRights::new(true, false, false, true))
+        );
+        assert!(CastlingRights::from_str("").is_err());
+        assert!(CastlingRights::from_str("KK").is_err());
+    }
 }
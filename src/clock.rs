@@ -0,0 +1,523 @@
+//! Chess clocks and time controls.
+//!
+//! A [`TimeControl`] is made of one or more [`Stage`]s, each giving a player a number of moves
+//! to make in some amount of time, with an [`Increment`] or delay for every move. When a player
+//! completes the moves of a stage, the time of the next stage is added to their clock. The last
+//! stage lasts for the rest of the game, unless it has a number of moves, in which case it
+//! repeats.
+//!
+//! A [`Clock`] keeps the time of both players under a time control. It reads the time from a
+//! [`TimeSource`], which is the system clock by default and can be a [`ManualTimeSource`] in
+//! tests. A clock attached to a [`Game`] with [`Game::attach_clock`] is switched to the other
+//! player by [`Game::make_move`], and ends the game when a flag falls.
+//!
+//! [`Game`]: crate::game::Game
+//! [`Game::attach_clock`]: crate::game::Game::attach_clock
+//! [`Game::make_move`]: crate::game::Game::make_move
+
+use crate::Color;
+use std::{
+    fmt::{Debug, Display},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// A source of the current time for a [`Clock`].
+pub trait TimeSource: Debug + Send + Sync {
+    /// The time elapsed since some fixed point, which must never decrease.
+    fn now(&self) -> Duration;
+}
+
+/// Reads the time from the system's monotonic clock.
+#[derive(Debug, Clone, Copy)]
+pub struct SystemTimeSource {
+    start: Instant,
+}
+
+impl Default for SystemTimeSource {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl TimeSource for SystemTimeSource {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// A time source that only moves when told to, for tests and replays.
+///
+/// Clones share the same time, so a clone can be given to a [`Clock`] and the original kept to
+/// advance it.
+#[derive(Debug, Clone, Default)]
+pub struct ManualTimeSource {
+    now: Arc<Mutex<Duration>>,
+}
+
+impl ManualTimeSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl TimeSource for ManualTimeSource {
+    fn now(&self) -> Duration {
+        *self.now.lock().unwrap()
+    }
+}
+
+/// The time given to a player for every move they make.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Increment {
+    None,
+    /// The time is added to the clock after every move.
+    Fischer(Duration),
+    /// The time spent on a move is added back to the clock after the move, but never more than
+    /// the given time.
+    Bronstein(Duration),
+    /// The clock doesn't start counting down until the given time has passed on every move, also
+    /// known as US delay.
+    SimpleDelay(Duration),
+}
+
+/// A part of a time control.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stage {
+    moves: Option<u32>,
+    time: Duration,
+    increment: Increment,
+}
+
+impl Stage {
+    pub fn new(moves: Option<u32>, time: Duration, increment: Increment) -> Self {
+        Self {
+            moves,
+            time,
+            increment,
+        }
+    }
+
+    /// The number of moves to make in this stage, or `None` if it lasts for the rest of the game.
+    pub fn moves(&self) -> Option<u32> {
+        self.moves
+    }
+
+    /// The time added to a player's clock at the start of the stage.
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    pub fn increment(&self) -> Increment {
+        self.increment
+    }
+}
+
+/// The time each player has for their moves.
+///
+/// Time controls can be parsed from and written as the `TimeControl` tag of PGN, where stages
+/// are separated by `:`, and times are given in seconds. For example, `300+2` is five minutes
+/// with a two second increment, and `40/7200:1800` is 40 moves in two hours followed by half an
+/// hour for the rest of the game. Delays can't be written in this format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeControl {
+    stages: Vec<Stage>,
+}
+
+impl TimeControl {
+    /// Create a time control from its stages.
+    ///
+    /// # Returns
+    /// * `Ok` with the time control.
+    /// * `Err` if there are no stages, or if a stage other than the last has no number of moves.
+    pub fn new(stages: Vec<Stage>) -> Result<Self, String> {
+        match stages.split_last() {
+            None => Err("a time control needs at least one stage".to_string()),
+            Some((_, first)) if first.iter().any(|stage| stage.moves.is_none()) => {
+                Err("only the last stage can last for the rest of the game".to_string())
+            }
+            Some(_) => Ok(Self { stages }),
+        }
+    }
+
+    /// A fixed amount of time for the whole game.
+    pub fn sudden_death(time: Duration) -> Self {
+        Self::single(time, Increment::None)
+    }
+
+    pub fn fischer(time: Duration, increment: Duration) -> Self {
+        Self::single(time, Increment::Fischer(increment))
+    }
+
+    pub fn bronstein(time: Duration, delay: Duration) -> Self {
+        Self::single(time, Increment::Bronstein(delay))
+    }
+
+    pub fn simple_delay(time: Duration, delay: Duration) -> Self {
+        Self::single(time, Increment::SimpleDelay(delay))
+    }
+
+    fn single(time: Duration, increment: Increment) -> Self {
+        Self {
+            stages: vec![Stage::new(None, time, increment)],
+        }
+    }
+
+    pub fn stages(&self) -> &[Stage] {
+        &self.stages
+    }
+}
+
+impl FromStr for TimeControl {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let seconds = |field: &str| {
+            field
+                .parse::<u64>()
+                .map(Duration::from_secs)
+                .map_err(|_| format!("invalid time '{}'", field))
+        };
+        let mut stages = Vec::new();
+        for field in s.split(':') {
+            let (moves, field) = match field.split_once('/') {
+                Some((moves, field)) => {
+                    let moves = moves
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|&moves| moves > 0)
+                        .ok_or_else(|| format!("invalid number of moves '{}'", moves))?;
+                    (Some(moves), field)
+                }
+                None => (None, field),
+            };
+            let (time, increment) = match field.split_once('+') {
+                Some((time, increment)) => (time, Increment::Fischer(seconds(increment)?)),
+                None => (field, Increment::None),
+            };
+            stages.push(Stage::new(moves, seconds(time)?, increment));
+        }
+        Self::new(stages)
+    }
+}
+
+impl Display for TimeControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, stage) in self.stages.iter().enumerate() {
+            if i > 0 {
+                write!(f, ":")?;
+            }
+            if let Some(moves) = stage.moves {
+                write!(f, "{}/", moves)?;
+            }
+            write!(f, "{}", stage.time.as_secs())?;
+            if let Increment::Fischer(increment) = stage.increment {
+                write!(f, "+{}", increment.as_secs())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The time of one player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PlayerTime {
+    remaining: Duration,
+    stage: usize,
+    moves_in_stage: u32,
+    moves: u32,
+}
+
+/// A chess clock for two players.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    time_control: TimeControl,
+    time_source: Arc<dyn TimeSource>,
+    white: PlayerTime,
+    black: PlayerTime,
+    /// The player whose clock is running, and the time it started.
+    running: Option<(Color, Duration)>,
+    flagged: Option<Color>,
+}
+
+impl Clock {
+    /// Create a stopped clock that reads the time from `time_source`.
+    pub fn new<T: TimeSource + 'static>(time_control: TimeControl, time_source: T) -> Self {
+        let start = PlayerTime {
+            remaining: time_control.stages[0].time,
+            stage: 0,
+            moves_in_stage: 0,
+            moves: 0,
+        };
+        Self {
+            time_control,
+            time_source: Arc::new(time_source),
+            white: start,
+            black: start,
+            running: None,
+            flagged: None,
+        }
+    }
+
+    /// Create a stopped clock that reads the time from the system clock.
+    pub fn with_system_time(time_control: TimeControl) -> Self {
+        Self::new(time_control, SystemTimeSource::default())
+    }
+
+    pub fn time_control(&self) -> &TimeControl {
+        &self.time_control
+    }
+
+    /// Get the player whose clock is running, if any.
+    pub fn running(&self) -> Option<Color> {
+        self.running.map(|(color, _)| color)
+    }
+
+    /// Get the time `color` has left, counting the time spent on the current move.
+    pub fn remaining(&self, color: Color) -> Duration {
+        let time = self.time(color);
+        match self.running {
+            Some((running, started)) if running == color => {
+                let spent = self.time_source.now().saturating_sub(started);
+                time.remaining.saturating_sub(self.charged(*time, spent))
+            }
+            _ => time.remaining,
+        }
+    }
+
+    /// Get the number of moves `color` has completed on the clock.
+    pub fn moves(&self, color: Color) -> u32 {
+        self.time(color).moves
+    }
+
+    /// Get the player whose time has run out, if any.
+    pub fn flag_fallen(&self) -> Option<Color> {
+        self.flagged.or_else(|| {
+            let (running, _) = self.running?;
+            if self.remaining(running) == Duration::ZERO {
+                Some(running)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Start the clock of `color`, stopping the other clock without counting its move.
+    pub fn start(&mut self, color: Color) {
+        if self.flag_fallen().is_none() {
+            self.stop();
+            self.running = Some((color, self.time_source.now()));
+        }
+    }
+
+    /// Stop the clock without counting a move, such as when the game ends or is adjourned.
+    pub fn stop(&mut self) {
+        if let Some((running, started)) = self.running.take() {
+            let spent = self.time_source.now().saturating_sub(started);
+            let charged = self.charged(*self.time(running), spent);
+            let time = self.time_mut(running);
+            if charged >= time.remaining {
+                time.remaining = Duration::ZERO;
+                self.flagged = Some(running);
+            } else {
+                time.remaining -= charged;
+            }
+        }
+    }
+
+    /// Complete the move of the player whose clock is running and start the clock of their
+    /// opponent. If no clock is running, nothing happens.
+    ///
+    /// # Returns
+    /// * `Ok` if the move was made in time.
+    /// * `Err` if the flag of the player fell before the move.
+    pub fn press(&mut self) -> Result<(), &'static str> {
+        let (color, started) = match self.running {
+            Some(running) => running,
+            None => return Ok(()),
+        };
+        let now = self.time_source.now();
+        self.stop();
+        if self.flagged.is_some() {
+            return Err("flag has fallen");
+        }
+
+        let spent = now.saturating_sub(started);
+        let stages = &self.time_control.stages;
+        let time = match color {
+            Color::White => &mut self.white,
+            Color::Black => &mut self.black,
+        };
+        let stage = stages[time.stage];
+        time.remaining += match stage.increment {
+            Increment::Fischer(increment) => increment,
+            Increment::Bronstein(delay) => spent.min(delay),
+            Increment::None | Increment::SimpleDelay(_) => Duration::ZERO,
+        };
+        time.moves += 1;
+        time.moves_in_stage += 1;
+        if stage.moves == Some(time.moves_in_stage) {
+            // the last stage repeats if it has a number of moves
+            time.stage = (time.stage + 1).min(stages.len() - 1);
+            time.moves_in_stage = 0;
+            time.remaining += stages[time.stage].time;
+        }
+
+        self.running = Some((color.opponent(), now));
+        Ok(())
+    }
+
+    /// The time taken off the clock of a player for `spent` time on a move.
+    fn charged(&self, time: PlayerTime, spent: Duration) -> Duration {
+        match self.time_control.stages[time.stage].increment {
+            Increment::SimpleDelay(delay) => spent.saturating_sub(delay),
+            _ => spent,
+        }
+    }
+
+    fn time(&self, color: Color) -> &PlayerTime {
+        match color {
+            Color::White => &self.white,
+            Color::Black => &self.black,
+        }
+    }
+
+    fn time_mut(&mut self, color: Color) -> &mut PlayerTime {
+        match color {
+            Color::White => &mut self.white,
+            Color::Black => &mut self.black,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        game::{Game, GameOver},
+        prelude::*,
+    };
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    fn running_clock(time_control: TimeControl) -> (Clock, ManualTimeSource) {
+        let time = ManualTimeSource::new();
+        let mut clock = Clock::new(time_control, time.clone());
+        clock.start(Color::White);
+        (clock, time)
+    }
+
+    #[test]
+    fn parse_time_control() {
+        let time_control: TimeControl = "40/7200:1800".parse().unwrap();
+        assert_eq!(
+            time_control.stages(),
+            [
+                Stage::new(Some(40), secs(7200), Increment::None),
+                Stage::new(None, secs(1800), Increment::None),
+            ]
+        );
+        assert_eq!(time_control.to_string(), "40/7200:1800");
+        assert_eq!(
+            "300+2".parse(),
+            Ok(TimeControl::fischer(secs(300), secs(2)))
+        );
+        assert!("".parse::<TimeControl>().is_err());
+        assert!("300:60".parse::<TimeControl>().is_err());
+        assert!("0/300".parse::<TimeControl>().is_err());
+    }
+
+    #[test]
+    fn fischer_and_bronstein() {
+        let (mut clock, time) = running_clock(TimeControl::fischer(secs(60), secs(2)));
+        time.advance(secs(10));
+        assert_eq!(clock.remaining(Color::White), secs(50));
+        clock.press().unwrap();
+        assert_eq!(clock.remaining(Color::White), secs(52));
+        assert_eq!(clock.running(), Some(Color::Black));
+
+        let (mut clock, time) = running_clock(TimeControl::bronstein(secs(60), secs(5)));
+        time.advance(secs(3));
+        clock.press().unwrap();
+        assert_eq!(clock.remaining(Color::White), secs(60));
+        time.advance(secs(8));
+        clock.press().unwrap();
+        assert_eq!(clock.remaining(Color::Black), secs(57));
+    }
+
+    #[test]
+    fn simple_delay() {
+        let (mut clock, time) = running_clock(TimeControl::simple_delay(secs(60), secs(5)));
+        time.advance(secs(4));
+        assert_eq!(clock.remaining(Color::White), secs(60));
+        time.advance(secs(3));
+        assert_eq!(clock.remaining(Color::White), secs(58));
+        clock.press().unwrap();
+        assert_eq!(clock.remaining(Color::White), secs(58));
+    }
+
+    #[test]
+    fn stages() {
+        let (mut clock, time) = running_clock("2/60:30+1".parse().unwrap());
+        for _ in 0..4 {
+            time.advance(secs(10));
+            clock.press().unwrap();
+        }
+        assert_eq!(clock.moves(Color::White), 2);
+        assert_eq!(clock.remaining(Color::White), secs(70));
+        time.advance(secs(10));
+        clock.press().unwrap();
+        assert_eq!(clock.remaining(Color::White), secs(61));
+    }
+
+    #[test]
+    fn flag_fall() {
+        let (mut clock, time) = running_clock(TimeControl::sudden_death(secs(60)));
+        time.advance(secs(60));
+        assert_eq!(clock.flag_fallen(), Some(Color::White));
+        assert!(clock.press().is_err());
+        assert_eq!(clock.running(), None);
+        assert_eq!(clock.remaining(Color::White), Duration::ZERO);
+    }
+
+    #[test]
+    fn game_with_clock() {
+        let time = ManualTimeSource::new();
+        let mut game = Game::default();
+        game.attach_clock(Clock::new(
+            TimeControl::fischer(secs(60), secs(1)),
+            time.clone(),
+        ));
+        time.advance(secs(5));
+        game.make_move(game.parse_uci_move("e2e4").unwrap())
+            .unwrap();
+        let clock = game.clock().unwrap();
+        assert_eq!(clock.remaining(Color::White), secs(56));
+        assert_eq!(clock.running(), Some(Color::Black));
+
+        time.advance(secs(60));
+        assert!(game.is_over());
+        assert_eq!(game.game_result(), Some(GameOver::Winner(Color::White)));
+        assert!(game
+            .make_move(game.parse_uci_move("e7e5").unwrap())
+            .is_err());
+
+        // a lone king can't win on time
+        let mut game = Game::from_fen_string("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+        game.attach_clock(Clock::new(
+            TimeControl::sudden_death(secs(60)),
+            time.clone(),
+        ));
+        time.advance(secs(60));
+        assert_eq!(game.game_result(), Some(GameOver::Draw));
+    }
+}
@@ -0,0 +1,363 @@
+//! A compact binary format for collections of games.
+//!
+//! A database starts with the magic bytes `CGDB` and a version byte, followed by the games one
+//! after the other. Each game is stored as the length of its record in bytes as a little endian
+//! `u32`, followed by the record:
+//!
+//! * the number of tags, then for each tag the length of its name as a byte, the name, the
+//!   length of its value as a little endian `u16` and the value, all in UTF-8
+//! * the result: 0 if unknown, 1 if white won, 2 if black won and 3 for a draw
+//! * 0 if the game starts from the standard position, or 1 followed by the starting position as
+//!   a [`PackedPosition`]
+//! * the number of moves as a little endian `u16`, then one byte per move: the index of the move
+//!   in [`Game::ordered_moves`] of the position it was played in
+//!
+//! Since no position has more than 218 legal moves, every move fits in a byte. Decoding a game
+//! replays its moves, so only legal games can be stored.
+
+use crate::{
+    game::{Game, GameOver},
+    packed::PackedPosition,
+    Color,
+};
+use std::{
+    convert::TryFrom,
+    io::{ErrorKind, Read, Write},
+};
+
+const MAGIC: [u8; 4] = *b"CGDB";
+const VERSION: u8 = 1;
+
+const STANDARD_START: u8 = 0;
+const PACKED_START: u8 = 1;
+
+/// A game together with its tags and result.
+#[derive(Debug, Clone)]
+pub struct GameRecord {
+    tags: Vec<(String, String)>,
+    result: Option<GameOver>,
+    game: Game,
+}
+
+impl GameRecord {
+    pub fn new(game: Game, tags: Vec<(String, String)>, result: Option<GameOver>) -> Self {
+        Self { tags, result, game }
+    }
+
+    /// The tags as pairs of name and value, in the order they were given.
+    pub fn tags(&self) -> &[(String, String)] {
+        &self.tags
+    }
+
+    /// Get the value of the first tag called `name`.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The result of the game, which may differ from [`Game::game_result`] when a player
+    /// resigned or lost on time.
+    pub fn result(&self) -> Option<GameOver> {
+        self.result
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn into_game(self) -> Game {
+        self.game
+    }
+
+    /// Encode the record, without the length in front of it.
+    ///
+    /// # Returns
+    /// * `Ok` with the encoded record.
+    /// * `Err` if a tag or the game is too long to be stored.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        let mut bytes = Vec::new();
+        bytes
+            .push(u8::try_from(self.tags.len()).map_err(|_| "too many tags to store".to_string())?);
+        for (name, value) in &self.tags {
+            let name_len =
+                u8::try_from(name.len()).map_err(|_| format!("tag name '{}' is too long", name))?;
+            let value_len = u16::try_from(value.len())
+                .map_err(|_| format!("value of tag '{}' is too long", name))?;
+            bytes.push(name_len);
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.extend_from_slice(&value_len.to_le_bytes());
+            bytes.extend_from_slice(value.as_bytes());
+        }
+        bytes.push(match self.result {
+            None => 0,
+            Some(GameOver::Winner(Color::White)) => 1,
+            Some(GameOver::Winner(Color::Black)) => 2,
+            Some(GameOver::Draw) => 3,
+        });
+
+        let mut replay = self.game.clone();
+        while replay.undo_move().is_some() {}
+        let start = PackedPosition::from_game(&replay)?;
+        if start == PackedPosition::from_game(&Game::default())? {
+            bytes.push(STANDARD_START);
+        } else {
+            bytes.push(PACKED_START);
+            bytes.extend_from_slice(start.as_bytes());
+        }
+
+        let moves = self.game.move_history();
+        let count =
+            u16::try_from(moves.len()).map_err(|_| "too many moves to store".to_string())?;
+        bytes.extend_from_slice(&count.to_le_bytes());
+        for &chess_move in moves {
+            let index = replay
+                .ordered_moves()
+                .iter()
+                .position(|&legal| legal == chess_move)
+                .unwrap();
+            bytes.push(index as u8);
+            replay.make_move(chess_move)?;
+        }
+        Ok(bytes)
+    }
+
+    /// Decode a record encoded by [`GameRecord::to_bytes`], replaying its moves.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let mut bytes = bytes;
+        let mut next = |len| take(&mut bytes, len);
+        let utf8 = |bytes: &[u8]| {
+            String::from_utf8(bytes.to_vec()).map_err(|_| "tag is not valid UTF-8".to_string())
+        };
+
+        let tag_count = next(1)?[0];
+        let mut tags = Vec::with_capacity(tag_count as usize);
+        for _ in 0..tag_count {
+            let name_len = next(1)?[0] as usize;
+            let name = utf8(next(name_len)?)?;
+            let value_len = next(2)?;
+            let value_len = u16::from_le_bytes([value_len[0], value_len[1]]) as usize;
+            tags.push((name, utf8(next(value_len)?)?));
+        }
+        let result = match next(1)?[0] {
+            0 => None,
+            1 => Some(GameOver::Winner(Color::White)),
+            2 => Some(GameOver::Winner(Color::Black)),
+            3 => Some(GameOver::Draw),
+            result => return Err(format!("invalid result {}", result)),
+        };
+        let mut game = match next(1)?[0] {
+            STANDARD_START => Game::default(),
+            PACKED_START => PackedPosition::from_bytes(next(PackedPosition::SIZE)?)?.to_game(),
+            start => return Err(format!("invalid starting position kind {}", start)),
+        };
+
+        let count = next(2)?;
+        let count = u16::from_le_bytes([count[0], count[1]]) as usize;
+        for &index in next(count)? {
+            let chess_move = *game
+                .ordered_moves()
+                .get(index as usize)
+                .ok_or_else(|| format!("invalid move index {}", index))?;
+            game.make_move(chess_move)?;
+        }
+        if !bytes.is_empty() {
+            return Err("trailing bytes after game record".to_string());
+        }
+        Ok(Self::new(game, tags, result))
+    }
+}
+
+/// Split the first `len` bytes off `bytes`.
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8], String> {
+    if bytes.len() < len {
+        return Err("truncated game record".to_string());
+    }
+    let (taken, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Ok(taken)
+}
+
+/// Writes games to a database.
+///
+/// Games are written straight to the underlying writer, so wrapping a file in a
+/// [`std::io::BufWriter`] is recommended.
+#[derive(Debug)]
+pub struct GameWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> GameWriter<W> {
+    /// Start a database by writing its header to `writer`.
+    pub fn new(mut writer: W) -> Result<Self, String> {
+        writer.write_all(&MAGIC).map_err(|e| e.to_string())?;
+        writer.write_all(&[VERSION]).map_err(|e| e.to_string())?;
+        Ok(Self { writer })
+    }
+
+    pub fn write(&mut self, record: &GameRecord) -> Result<(), String> {
+        let bytes = record.to_bytes()?;
+        let len = u32::try_from(bytes.len()).map_err(|_| "game record is too long".to_string())?;
+        self.writer
+            .write_all(&len.to_le_bytes())
+            .and_then(|_| self.writer.write_all(&bytes))
+            .map_err(|e| e.to_string())
+    }
+
+    /// Flush and return the underlying writer.
+    pub fn into_inner(mut self) -> Result<W, String> {
+        self.writer.flush().map_err(|e| e.to_string())?;
+        Ok(self.writer)
+    }
+}
+
+/// Reads the games of a database one at a time.
+///
+/// Games are read straight from the underlying reader, so wrapping a file in a
+/// [`std::io::BufReader`] is recommended.
+#[derive(Debug)]
+pub struct GameReader<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> GameReader<R> {
+    /// Start reading a database by checking its header.
+    pub fn new(mut reader: R) -> Result<Self, String> {
+        let mut header = [0; 5];
+        reader.read_exact(&mut header).map_err(|e| e.to_string())?;
+        if header[..4] != MAGIC {
+            return Err("not a game database".to_string());
+        }
+        if header[4] != VERSION {
+            return Err(format!("unsupported game database version {}", header[4]));
+        }
+        Ok(Self { reader })
+    }
+
+    /// Read the length of the next record, or `None` at the end of the database.
+    fn read_len(&mut self) -> Result<Option<usize>, String> {
+        let mut len = [0; 4];
+        let mut read = 0;
+        while read < len.len() {
+            match self.reader.read(&mut len[read..]) {
+                Ok(0) if read == 0 => return Ok(None),
+                Ok(0) => return Err("truncated game database".to_string()),
+                Ok(n) => read += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+        Ok(Some(u32::from_le_bytes(len) as usize))
+    }
+}
+
+impl<R: Read> Iterator for GameReader<R> {
+    type Item = Result<GameRecord, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let len = match self.read_len() {
+            Ok(len) => len?,
+            Err(e) => return Some(Err(e)),
+        };
+        let mut bytes = vec![0; len];
+        if let Err(e) = self.reader.read_exact(&mut bytes) {
+            return Some(Err(e.to_string()));
+        }
+        Some(GameRecord::from_bytes(&bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(game: &mut Game, moves: &[&str]) {
+        for uci in moves {
+            game.make_move(game.parse_uci_move(uci).unwrap()).unwrap();
+        }
+    }
+
+    fn tags(tags: &[(&str, &str)]) -> Vec<(String, String)> {
+        tags.iter()
+            .map(|&(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn record_round_trip() {
+        let mut game = Game::default();
+        play(&mut game, &["e2e4", "e7e5", "g1f3", "b8c6", "f1b5", "a7a6"]);
+        let record = GameRecord::new(
+            game,
+            tags(&[("White", "Kasparov"), ("Black", "Топалов")]),
+            Some(GameOver::Draw),
+        );
+        let bytes = record.to_bytes().unwrap();
+        // tags, result, start, move count and one byte per move
+        assert_eq!(bytes.len(), 1 + 16 + 22 + 1 + 1 + 2 + 6);
+
+        let decoded = GameRecord::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.tags(), record.tags());
+        assert_eq!(decoded.tag("Black"), Some("Топалов"));
+        assert_eq!(decoded.tag("Event"), None);
+        assert_eq!(decoded.result(), Some(GameOver::Draw));
+        assert_eq!(decoded.game().move_history(), record.game().move_history());
+        assert_eq!(
+            decoded.game().to_fen_string(),
+            record.game().to_fen_string()
+        );
+
+        assert!(GameRecord::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        let mut invalid = bytes;
+        *invalid.last_mut().unwrap() = 200;
+        assert!(GameRecord::from_bytes(&invalid).is_err());
+    }
+
+    #[test]
+    fn custom_start() {
+        let mut game = Game::from_fen_string("4k3/1P6/8/8/8/8/8/4K2R w K - 0 40").unwrap();
+        play(&mut game, &["b7b8n", "e8f7", "e1g1"]);
+        let record = GameRecord::new(game, vec![], None);
+        let bytes = record.to_bytes().unwrap();
+        assert_eq!(bytes.len(), 1 + 1 + 1 + PackedPosition::SIZE + 2 + 3);
+
+        let decoded = GameRecord::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.result(), None);
+        assert_eq!(decoded.game().move_history(), record.game().move_history());
+        assert_eq!(
+            decoded.game().start_fen_string(),
+            record.game().start_fen_string()
+        );
+    }
+
+    #[test]
+    fn stream() {
+        let mut games = vec![Game::default(); 3];
+        play(&mut games[1], &["d2d4", "d7d5"]);
+        play(&mut games[2], &["f2f3", "e7e5", "g2g4", "d8h4"]);
+
+        let mut writer = GameWriter::new(Vec::new()).unwrap();
+        for game in &games {
+            let result = game.game_result();
+            writer
+                .write(&GameRecord::new(game.clone(), vec![], result))
+                .unwrap();
+        }
+        let bytes = writer.into_inner().unwrap();
+
+        let records: Vec<GameRecord> = GameReader::new(bytes.as_slice())
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(records.len(), 3);
+        for (record, game) in records.iter().zip(&games) {
+            assert_eq!(record.game().to_fen_string(), game.to_fen_string());
+        }
+        assert_eq!(records[2].result(), Some(GameOver::Winner(Color::Black)));
+
+        let mut truncated = GameReader::new(&bytes[..bytes.len() - 2]).unwrap();
+        assert!(truncated.nth(2).unwrap().is_err());
+        assert!(GameReader::new(&b"PGN\n\n"[..]).is_err());
+    }
+}
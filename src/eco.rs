@@ -0,0 +1,201 @@
+//! Classification of games by the Encyclopaedia of Chess Openings.
+//!
+//! The crate embeds a table of openings covering every code from A00 to E99, each with the moves
+//! of its main line. Openings are matched by the position their line reaches, using
+//! [`Game::position_hash`], so a game that transposes into an opening is classified the same as
+//! one that follows its moves in order.
+
+use crate::{game::Game, pgn};
+use std::collections::HashMap;
+
+/// The openings, one per line, as the ECO code, the name and the moves, separated by tabs.
+const TABLE: &str = include_str!("eco.tsv");
+
+/// An opening of the ECO table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Opening {
+    eco: String,
+    name: String,
+    variation: Option<String>,
+    moves: String,
+    plies: usize,
+}
+
+impl Opening {
+    /// The ECO code, such as `C42`.
+    pub fn eco(&self) -> &str {
+        &self.eco
+    }
+
+    /// The name of the opening, such as `Petrov's Defense`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The name of the variation, such as `Classical Attack`.
+    pub fn variation(&self) -> Option<&str> {
+        self.variation.as_deref()
+    }
+
+    /// The name of the opening followed by the name of its variation, if any.
+    pub fn full_name(&self) -> String {
+        match &self.variation {
+            Some(variation) => format!("{}: {}", self.name, variation),
+            None => self.name.clone(),
+        }
+    }
+
+    /// The moves of the opening in PGN movetext, such as `1. e4 e5 2. Nf3 Nf6`.
+    pub fn moves(&self) -> &str {
+        &self.moves
+    }
+
+    /// The number of moves of the opening, counting the moves of both players.
+    pub fn plies(&self) -> usize {
+        self.plies
+    }
+}
+
+/// Finds the openings of games in the ECO table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EcoClassifier {
+    openings: Vec<Opening>,
+    positions: HashMap<u64, usize>,
+}
+
+impl EcoClassifier {
+    /// Load the embedded ECO table.
+    pub fn new() -> Self {
+        Self::from_table(TABLE).expect("invalid embedded ECO table")
+    }
+
+    /// Load an ECO table in the format of the embedded one.
+    ///
+    /// When several openings reach the same position, the first one is kept.
+    ///
+    /// # Returns
+    /// * `Ok` with the classifier.
+    /// * `Err` if a line is malformed, or its moves are not legal.
+    fn from_table(table: &str) -> Result<Self, String> {
+        let mut openings = Vec::new();
+        let mut positions = HashMap::new();
+        for line in table.lines().filter(|line| !line.trim().is_empty()) {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let (eco, full_name, moves) = match fields.as_slice() {
+                [eco, full_name, moves] => (*eco, *full_name, *moves),
+                _ => return Err(format!("invalid ECO table line '{}'", line)),
+            };
+            let (name, variation) = match full_name.find(": ") {
+                Some(i) => (&full_name[..i], Some(full_name[i + 2..].to_string())),
+                None => (full_name, None),
+            };
+            let game = pgn::parse_game(moves)
+                .map_err(|e| format!("{} in ECO table line '{}'", e, line))?
+                .into_game();
+            positions
+                .entry(game.position_hash())
+                .or_insert(openings.len());
+            openings.push(Opening {
+                eco: eco.to_string(),
+                name: name.to_string(),
+                variation,
+                moves: moves.to_string(),
+                plies: game.move_history().len(),
+            });
+        }
+        Ok(Self {
+            openings,
+            positions,
+        })
+    }
+
+    /// The openings of the table, in order.
+    pub fn openings(&self) -> &[Opening] {
+        &self.openings
+    }
+
+    /// Find the opening of the current position of `game`.
+    pub fn lookup(&self, game: &Game) -> Option<&Opening> {
+        self.positions
+            .get(&game.position_hash())
+            .map(|&i| &self.openings[i])
+    }
+
+    /// Find the opening of `game` by replaying its moves from its starting position.
+    ///
+    /// # Returns
+    /// The opening of the last position of the game found in the table, which is the deepest
+    /// opening the game reached, or `None` if the game left the table from its first move.
+    pub fn classify(&self, game: &Game) -> Option<&Opening> {
+        let mut replay = game.clone();
+        while replay.undo_move().is_some() {}
+        let mut opening = self.lookup(&replay);
+        for &chess_move in game.move_history() {
+            replay.make_move(chess_move).unwrap();
+            opening = self.lookup(&replay).or(opening);
+        }
+        opening
+    }
+}
+
+impl Default for EcoClassifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(moves: &str) -> Game {
+        pgn::parse_game(moves).unwrap().into_game()
+    }
+
+    #[test]
+    fn table() {
+        let classifier = EcoClassifier::new();
+        let mut codes: Vec<&str> = classifier.openings().iter().map(Opening::eco).collect();
+        codes.dedup();
+        assert_eq!(codes.len(), 500);
+        assert_eq!((codes[0], codes[499]), ("A00", "E99"));
+
+        let petrov = &classifier.openings()[classifier
+            .openings()
+            .iter()
+            .position(|opening| opening.eco() == "C42")
+            .unwrap()];
+        assert_eq!(petrov.name(), "Petrov's Defense");
+        assert_eq!(petrov.variation(), None);
+        assert_eq!(petrov.plies(), 4);
+    }
+
+    #[test]
+    fn classify() {
+        let classifier = EcoClassifier::new();
+        let najdorf = classifier
+            .classify(&play(
+                "1. e4 c5 2. Nf3 d6 3. d4 cxd4 4. Nxd4 Nf6 5. Nc3 a6 6. h3",
+            ))
+            .unwrap();
+        assert_eq!(najdorf.eco(), "B90");
+        assert_eq!(najdorf.full_name(), "Sicilian Defense: Najdorf Variation");
+
+        // the Four Knights reached from the Réti
+        let transposed = classifier
+            .classify(&play("1. Nf3 Nc6 2. e4 e5 3. Nc3 Nf6"))
+            .unwrap();
+        assert_eq!(transposed.eco(), "C47");
+        assert_eq!(transposed.name(), "Four Knights Game");
+
+        assert_eq!(classifier.classify(&Game::default()), None);
+        assert_eq!(classifier.classify(&play("1. h4 e5")), None);
+        assert_eq!(
+            classifier
+                .lookup(&play("1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 4. Ba4"))
+                .unwrap()
+                .eco(),
+            "C70"
+        );
+    }
+}
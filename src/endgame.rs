@@ -0,0 +1,1008 @@
+//! Generation and probing of small distance-to-mate endgame tables.
+//!
+//! A table covers every placement of one material signature, such as `KQK`, `KRK`, `KBNK` or
+//! `KPK`: a king and up to [`MAX_EXTRA_PIECES`] further pieces against a lone king. The tables
+//! are built by retrograde analysis, starting from every checkmate and working backwards one ply
+//! at a time, so each position gets the exact number of plies to mate with best play. Positions
+//! that can reach a smaller table, by promotion or by the lone king capturing a piece, are
+//! resolved through that table, which is generated first.
+//!
+//! Symmetric placements are only stored once: the strong side's king is mirrored onto the files
+//! a to d, and for tables without pawns also onto the triangle a1-d1-d4. Tables are saved with
+//! their values compressed by an adaptive range coder, which predicts each value from the one
+//! before it, since neighbouring placements mostly share their distance to mate. This shrinks
+//! `KBNK` from 5 MB to 1.5 MB.
+//!
+//! Castling and the fifty-move rule are not taken into account.
+
+use crate::{chess_board::ChessBoard, game::Game, piece::PieceType, Color, Piece};
+use bitboard::{Bitboard, Position, INCREASING_A1_B1};
+use std::{collections::HashMap, fs, path::Path};
+
+/// The largest number of pieces besides the two kings that tables can be generated for.
+pub const MAX_EXTRA_PIECES: usize = 2;
+
+const MAGIC: [u8; 4] = *b"CEGT";
+const VERSION: u8 = 1;
+
+const PROMOTIONS: [PieceType; 4] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+];
+
+/// The squares a1, b1, c1, d1, b2, c2, d2, c3, d3 and d4.
+const TRIANGLE: [usize; 10] = [0, 1, 2, 3, 9, 10, 11, 18, 19, 27];
+
+/// Number of bits of the probabilities of the range coder.
+const PROBABILITY_BITS: u32 = 11;
+/// How fast the probabilities of the range coder adapt, as a right shift.
+const ADAPTATION_SHIFT: u32 = 5;
+/// The range coder shifts out a byte whenever its range falls below this.
+const TOP_RANGE: u32 = 1 << 24;
+
+/// Counter of a position where the lone king can escape into a draw.
+const ESCAPE: u8 = u8::MAX;
+
+/// The squares of the strong king, the lone king and then the other pieces, from a1 = 0 to
+/// h8 = 63.
+type Squares = [usize; 2 + MAX_EXTRA_PIECES];
+
+/// The value of a position in an endgame table, from the point of view of the current player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dtm {
+    /// The current player mates in the given number of plies.
+    Win(u32),
+    /// The current player is mated in the given number of plies.
+    Loss(u32),
+    Draw,
+}
+
+/// A distance-to-mate table for one material signature.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndgameTable {
+    signature: String,
+    /// The strong side's pieces besides the king, in the order of the signature.
+    pieces: Vec<PieceType>,
+    /// One byte per stored placement, with the strong side to move first: 0 for draws, and
+    /// otherwise the number of plies to mate plus one.
+    values: Vec<u8>,
+}
+
+impl EndgameTable {
+    /// Generate the table for a material signature such as `KQK` or `KBNK`, listing the strong
+    /// side's pieces and then the lone king.
+    ///
+    /// Tables that the signature can turn into by a capture or a promotion are generated too.
+    ///
+    /// # Returns
+    /// * `Ok` with the table.
+    /// * `Err` if the signature is not supported.
+    pub fn generate(signature: &str) -> Result<Self, String> {
+        let pieces = parse_signature(signature)?;
+        Ok(generate_table(pieces, &mut HashMap::new()))
+    }
+
+    /// The material signature, such as `KQK`.
+    pub fn signature(&self) -> &str {
+        &self.signature
+    }
+
+    /// Look up the current position of `game`, which may have either color as the strong side.
+    ///
+    /// # Returns
+    /// * `Some` with the distance to mate.
+    /// * `None` if the material on the board does not match the table.
+    pub fn probe(&self, game: &Game) -> Option<Dtm> {
+        let board = game.board();
+        let strong_side = strong_side(&board)?;
+        // the tables are generated with white as the strong side
+        let flip = if strong_side.is_white() { 0 } else { 56 };
+
+        let mut strong_king = None;
+        let mut lone_king = None;
+        let mut pieces = Vec::new();
+        for (square, &position) in INCREASING_A1_B1.iter().enumerate() {
+            let piece = match board.get_piece(position) {
+                Some(piece) => piece,
+                None => continue,
+            };
+            let square = square ^ flip;
+            match (piece.kind(), piece.color() == strong_side) {
+                (PieceType::King, true) => strong_king = Some(square),
+                (PieceType::King, false) => lone_king = Some(square),
+                (kind, _) => pieces.push((kind, square)),
+            }
+        }
+
+        pieces.sort_by_key(|&(kind, _)| piece_order(kind));
+        let kinds: Vec<PieceType> = pieces.iter().map(|&(kind, _)| kind).collect();
+        if kinds != self.pieces {
+            return None;
+        }
+
+        let strong_to_move = game.current_player() == strong_side;
+        let value = self.value(strong_to_move, strong_king?, lone_king?, &pieces);
+        Some(match value {
+            0 => Dtm::Draw,
+            value if strong_to_move => Dtm::Win(value as u32 - 1),
+            value => Dtm::Loss(value as u32 - 1),
+        })
+    }
+
+    /// Save the table to a file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        fs::write(path, self.to_bytes()).map_err(|e| e.to_string())
+    }
+
+    /// Load a table saved by [`EndgameTable::save`].
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let bytes = fs::read(path).map_err(|e| e.to_string())?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Encode the table as a header with the signature, followed by the compressed values.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(6 + self.signature.len());
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(VERSION);
+        bytes.push(self.signature.len() as u8);
+        bytes.extend_from_slice(self.signature.as_bytes());
+        bytes.append(&mut compress(&self.values));
+        bytes
+    }
+
+    /// Decode a table encoded by [`EndgameTable::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < 6 || bytes[..4] != MAGIC {
+            return Err("not an endgame table".to_string());
+        }
+        if bytes[4] != VERSION {
+            return Err(format!("unsupported endgame table version {}", bytes[4]));
+        }
+        let signature_end = 6 + bytes[5] as usize;
+        let signature = bytes
+            .get(6..signature_end)
+            .and_then(|signature| std::str::from_utf8(signature).ok())
+            .ok_or("truncated endgame table")?;
+        let pieces = parse_signature(signature)?;
+
+        let values = decompress(&bytes[signature_end..], stored_size(&pieces))
+            .ok_or_else(|| format!("corrupt endgame table {}", signature))?;
+        Ok(Self {
+            signature: signature.to_string(),
+            pieces,
+            values,
+        })
+    }
+
+    /// Look up a placement with white as the strong side.
+    ///
+    /// `pieces` must be sorted in the order of the signature.
+    fn value(
+        &self,
+        strong_to_move: bool,
+        strong_king: usize,
+        lone_king: usize,
+        pieces: &[(PieceType, usize)],
+    ) -> u8 {
+        let has_pawns = self.pieces.contains(&PieceType::Pawn);
+        let symmetry = Symmetry::canonical(strong_king, has_pawns);
+        let strong_king = symmetry.apply(strong_king);
+        let (king_index, king_squares) = if has_pawns {
+            (rank(strong_king) * 4 + file(strong_king), 32)
+        } else {
+            let king_index = TRIANGLE
+                .iter()
+                .position(|&square| square == strong_king)
+                .expect("the symmetry moves the king into the triangle");
+            (king_index, TRIANGLE.len())
+        };
+
+        let side = if strong_to_move { 0 } else { 1 };
+        let mut index = (side * king_squares + king_index) * 64 + symmetry.apply(lone_king);
+        for &(_, square) in pieces {
+            index = index * 64 + symmetry.apply(square);
+        }
+        self.values[index]
+    }
+}
+
+/// Find the side with pieces besides the king, if the other side only has its king.
+fn strong_side(board: &ChessBoard) -> Option<Color> {
+    let has_pieces = |color| {
+        INCREASING_A1_B1.iter().any(|&position| {
+            matches!(board.get_piece(position), Some(piece) if piece.color() == color && piece.kind() != PieceType::King)
+        })
+    };
+    match (has_pieces(Color::White), has_pieces(Color::Black)) {
+        (_, false) => Some(Color::White),
+        (false, true) => Some(Color::Black),
+        (true, true) => None,
+    }
+}
+
+fn parse_signature(signature: &str) -> Result<Vec<PieceType>, String> {
+    let invalid = || format!("invalid endgame signature '{}'", signature);
+    let pieces = signature
+        .strip_prefix('K')
+        .and_then(|rest| rest.strip_suffix('K'))
+        .ok_or_else(invalid)?;
+    let mut kinds = pieces
+        .chars()
+        .map(|c| match c {
+            'Q' => Ok(PieceType::Queen),
+            'R' => Ok(PieceType::Rook),
+            'B' => Ok(PieceType::Bishop),
+            'N' => Ok(PieceType::Knight),
+            'P' => Ok(PieceType::Pawn),
+            _ => Err(invalid()),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if kinds.len() > MAX_EXTRA_PIECES {
+        return Err(format!(
+            "endgame tables support at most {} pieces besides the kings",
+            MAX_EXTRA_PIECES
+        ));
+    }
+    kinds.sort_by_key(|&kind| piece_order(kind));
+    Ok(kinds)
+}
+
+fn signature_of(pieces: &[PieceType]) -> String {
+    let mut signature = "K".to_string();
+    for &kind in pieces {
+        signature.push(Piece::white(kind).fen_char());
+    }
+    signature.push('K');
+    signature
+}
+
+/// The order of pieces in a signature.
+fn piece_order(kind: PieceType) -> u8 {
+    match kind {
+        PieceType::King => 0,
+        PieceType::Queen => 1,
+        PieceType::Rook => 2,
+        PieceType::Bishop => 3,
+        PieceType::Knight => 4,
+        PieceType::Pawn => 5,
+    }
+}
+
+/// The squares the strong king is mirrored onto before storing a placement.
+fn king_squares(has_pawns: bool) -> Vec<usize> {
+    if has_pawns {
+        (0..64).filter(|&square| file(square) < 4).collect()
+    } else {
+        TRIANGLE.to_vec()
+    }
+}
+
+fn stored_size(pieces: &[PieceType]) -> usize {
+    let has_pawns = pieces.contains(&PieceType::Pawn);
+    2 * king_squares(has_pawns).len() * 64usize.pow(1 + pieces.len() as u32)
+}
+
+/// A mirroring of the board that maps a placement onto its stored placement.
+#[derive(Debug, Clone, Copy)]
+struct Symmetry {
+    flip_files: bool,
+    flip_ranks: bool,
+    flip_diagonal: bool,
+}
+
+impl Symmetry {
+    fn canonical(strong_king: usize, has_pawns: bool) -> Self {
+        let mut symmetry = Self {
+            flip_files: file(strong_king) > 3,
+            // pawns only move up the board
+            flip_ranks: !has_pawns && rank(strong_king) > 3,
+            flip_diagonal: false,
+        };
+        let king = symmetry.apply(strong_king);
+        symmetry.flip_diagonal = !has_pawns && rank(king) > file(king);
+        symmetry
+    }
+
+    fn apply(&self, mut square: usize) -> usize {
+        if self.flip_files {
+            square ^= 7;
+        }
+        if self.flip_ranks {
+            square ^= 56;
+        }
+        if self.flip_diagonal {
+            square = (square >> 3) | ((square & 7) << 3);
+        }
+        square
+    }
+}
+
+fn file(square: usize) -> usize {
+    square % 8
+}
+
+fn rank(square: usize) -> usize {
+    square / 8
+}
+
+fn position(square: usize) -> Position {
+    INCREASING_A1_B1[square]
+}
+
+fn square_of(position: Position) -> usize {
+    (u8::from(position.rank()) * 8 + u8::from(position.file())) as usize
+}
+
+fn squares_of(bitboard: Bitboard) -> impl Iterator<Item = usize> {
+    bitboard.positions().into_iter().map(square_of)
+}
+
+fn king_targets(square: usize) -> Bitboard {
+    Bitboard::king_targets(position(square))
+}
+
+/// The squares attacked by a white piece.
+fn attacks(kind: PieceType, square: usize, occupancy: Bitboard) -> Bitboard {
+    let position = position(square);
+    match kind {
+        PieceType::Pawn => [position.up_left(), position.up_right()]
+            .iter()
+            .flatten()
+            .fold(Bitboard::empty(), |attacks, &target| {
+                attacks | Bitboard::with_one(target)
+            }),
+        PieceType::Knight => Bitboard::knight_targets(position),
+        PieceType::Bishop => Bitboard::bishop_targets(position, occupancy),
+        PieceType::Rook => Bitboard::rook_targets(position, occupancy),
+        PieceType::Queen => Bitboard::queen_targets(position, occupancy),
+        PieceType::King => Bitboard::king_targets(position),
+    }
+}
+
+/// Generate the table for `pieces`, and first every smaller table it depends on.
+fn generate_table(
+    pieces: Vec<PieceType>,
+    tables: &mut HashMap<String, EndgameTable>,
+) -> EndgameTable {
+    for index in 0..pieces.len() {
+        let mut captured = pieces.clone();
+        captured.remove(index);
+        ensure_table(captured, tables);
+        if pieces[index] == PieceType::Pawn {
+            for &promotion in &PROMOTIONS {
+                let mut promoted = pieces.clone();
+                promoted[index] = promotion;
+                promoted.sort_by_key(|&kind| piece_order(kind));
+                ensure_table(promoted, tables);
+            }
+        }
+    }
+
+    let values = Generator::new(&pieces, tables).run();
+    let has_pawns = pieces.contains(&PieceType::Pawn);
+    let placements = 64usize.pow(1 + pieces.len() as u32);
+    let mut stored = Vec::with_capacity(stored_size(&pieces));
+    for side in 0..2 {
+        for &king in &king_squares(has_pawns) {
+            let offset = (side * 64 + king) * placements;
+            stored.extend_from_slice(&values[offset..offset + placements]);
+        }
+    }
+
+    EndgameTable {
+        signature: signature_of(&pieces),
+        pieces,
+        values: stored,
+    }
+}
+
+fn ensure_table(pieces: Vec<PieceType>, tables: &mut HashMap<String, EndgameTable>) {
+    let signature = signature_of(&pieces);
+    if !tables.contains_key(&signature) {
+        let table = generate_table(pieces, tables);
+        tables.insert(signature, table);
+    }
+}
+
+/// Retrograde analysis of every placement of one signature, with white as the strong side.
+///
+/// Placements are indexed by the side to move, the white king, the black king and the other
+/// pieces, six bits per square.
+struct Generator<'a> {
+    pieces: &'a [PieceType],
+    tables: &'a HashMap<String, EndgameTable>,
+    /// The number of placements with one side to move.
+    size: usize,
+    /// 0 while unresolved, and otherwise the number of plies to mate plus one.
+    values: Vec<u8>,
+    /// For every placement with black to move, the number of moves that are not yet known to
+    /// lose, or [`ESCAPE`] if black has a move that does not lose.
+    counters: Vec<u8>,
+    /// For every placement with black to move, the longest mate after capturing a piece.
+    longest_captures: Vec<u8>,
+    /// Placements waiting to be resolved, by the number of plies to mate.
+    queues: Vec<Vec<u32>>,
+}
+
+impl<'a> Generator<'a> {
+    fn new(pieces: &'a [PieceType], tables: &'a HashMap<String, EndgameTable>) -> Self {
+        let size = 64usize.pow(2 + pieces.len() as u32);
+        Self {
+            pieces,
+            tables,
+            size,
+            values: vec![0; 2 * size],
+            counters: vec![0; size],
+            longest_captures: vec![0; size],
+            queues: Vec::new(),
+        }
+    }
+
+    /// Resolve every placement.
+    ///
+    /// # Returns
+    /// The value of every placement, indexed like the placements.
+    fn run(mut self) -> Vec<u8> {
+        for index in 0..2 * self.size {
+            let (white_to_move, squares) = self.decode(index);
+            if !self.is_legal(white_to_move, &squares) {
+                continue;
+            }
+            if white_to_move {
+                self.initialize_white(index, &squares);
+            } else {
+                self.initialize_black(index, &squares);
+            }
+        }
+
+        let mut plies = 0;
+        while plies < self.queues.len() {
+            let queue = std::mem::take(&mut self.queues[plies]);
+            for index in queue {
+                let index = index as usize;
+                if self.values[index] != 0 {
+                    continue;
+                }
+                self.values[index] = plies as u8 + 1;
+                let (white_to_move, squares) = self.decode(index);
+                if white_to_move {
+                    self.resolve_win(plies, &squares);
+                } else {
+                    self.resolve_loss(plies, &squares);
+                }
+            }
+            plies += 1;
+        }
+
+        self.values
+    }
+
+    /// Queue the promotions of a placement with white to move that lead to mate.
+    fn initialize_white(&mut self, index: usize, squares: &Squares) {
+        let occupancy = self.occupancy(squares);
+        for (i, &kind) in self.pieces.iter().enumerate() {
+            let square = squares[2 + i];
+            if kind != PieceType::Pawn || rank(square) != 6 || occupancy & position(square + 8) != 0
+            {
+                continue;
+            }
+            for &promotion in &PROMOTIONS {
+                let mut pieces = self.other_pieces(squares, Some(i));
+                pieces.push((promotion, square + 8));
+                let value = self.lookup(false, squares[0], squares[1], pieces);
+                if value != 0 {
+                    // black is mated `value - 1` plies after the promotion
+                    self.enqueue(value as usize, index);
+                }
+            }
+        }
+    }
+
+    /// Count the moves of a placement with black to move, and queue it if it is already lost.
+    fn initialize_black(&mut self, index: usize, squares: &Squares) {
+        let mut moves = 0;
+        let mut escape = false;
+        let mut longest_capture = None;
+        for (target, captured) in self.black_moves(squares) {
+            match captured {
+                Some(i) => {
+                    let pieces = self.other_pieces(squares, Some(i));
+                    match self.lookup(true, squares[0], target, pieces) {
+                        0 => escape = true,
+                        value => longest_capture = longest_capture.max(Some(value - 1)),
+                    }
+                }
+                None => moves += 1,
+            }
+        }
+
+        let index = index - self.size;
+        if escape {
+            self.counters[index] = ESCAPE;
+            return;
+        }
+        self.counters[index] = moves;
+        self.longest_captures[index] = longest_capture.unwrap_or(0);
+        match longest_capture {
+            Some(longest) if moves == 0 => self.enqueue(longest as usize + 1, index + self.size),
+            None if moves == 0 && self.is_black_in_check(squares) => {
+                self.enqueue(0, index + self.size)
+            }
+            _ => {}
+        }
+    }
+
+    /// Black is mated in `plies` plies: every white move into the placement mates one ply
+    /// later.
+    fn resolve_loss(&mut self, plies: usize, squares: &Squares) {
+        let occupancy = self.occupancy(squares);
+        let mut predecessors = Vec::new();
+        for source in squares_of(king_targets(squares[0]) & !occupancy & !king_targets(squares[1]))
+        {
+            let mut predecessor = *squares;
+            predecessor[0] = source;
+            predecessors.push(predecessor);
+        }
+        for (i, &kind) in self.pieces.iter().enumerate() {
+            let square = squares[2 + i];
+            let sources = match kind {
+                PieceType::Pawn => {
+                    let mut sources = Bitboard::empty();
+                    if rank(square) >= 2 && occupancy & position(square - 8) == 0 {
+                        sources |= position(square - 8);
+                        if rank(square) == 3 && occupancy & position(square - 16) == 0 {
+                            sources |= position(square - 16);
+                        }
+                    }
+                    sources
+                }
+                _ => attacks(kind, square, occupancy) & !occupancy,
+            };
+            for source in squares_of(sources) {
+                let mut predecessor = *squares;
+                predecessor[2 + i] = source;
+                predecessors.push(predecessor);
+            }
+        }
+
+        for predecessor in predecessors {
+            if self.is_legal(true, &predecessor) {
+                let index = self.encode(true, &predecessor);
+                if self.values[index] == 0 {
+                    self.enqueue(plies + 1, index);
+                }
+            }
+        }
+    }
+
+    /// White mates in `plies` plies: every black move into the placement loses, and a black
+    /// placement where every move loses is mated one ply after its longest defence.
+    fn resolve_win(&mut self, plies: usize, squares: &Squares) {
+        let occupancy = self.occupancy(squares);
+        for source in squares_of(king_targets(squares[1]) & !occupancy & !king_targets(squares[0]))
+        {
+            let mut predecessor = *squares;
+            predecessor[1] = source;
+            let index = self.encode(false, &predecessor);
+            let counter = &mut self.counters[index - self.size];
+            if self.values[index] != 0 || *counter == ESCAPE || *counter == 0 {
+                continue;
+            }
+            *counter -= 1;
+            if *counter == 0 {
+                let longest = plies.max(self.longest_captures[index - self.size] as usize);
+                self.enqueue(longest + 1, index);
+            }
+        }
+    }
+
+    fn enqueue(&mut self, plies: usize, index: usize) {
+        if self.queues.len() <= plies {
+            self.queues.resize_with(plies + 1, Vec::new);
+        }
+        self.queues[plies].push(index as u32);
+    }
+
+    /// Look up a placement with the given pieces in the table for their signature.
+    fn lookup(
+        &self,
+        white_to_move: bool,
+        white_king: usize,
+        black_king: usize,
+        mut pieces: Vec<(PieceType, usize)>,
+    ) -> u8 {
+        pieces.sort_by_key(|&(kind, _)| piece_order(kind));
+        let kinds: Vec<PieceType> = pieces.iter().map(|&(kind, _)| kind).collect();
+        self.tables[&signature_of(&kinds)].value(white_to_move, white_king, black_king, &pieces)
+    }
+
+    /// The pieces besides the kings, leaving out the piece with index `skip`.
+    fn other_pieces(&self, squares: &Squares, skip: Option<usize>) -> Vec<(PieceType, usize)> {
+        self.pieces
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| Some(i) != skip)
+            .map(|(i, &kind)| (kind, squares[2 + i]))
+            .collect()
+    }
+
+    fn encode(&self, white_to_move: bool, squares: &Squares) -> usize {
+        let mut index = if white_to_move { 0 } else { 1 };
+        for &square in &squares[..2 + self.pieces.len()] {
+            index = index * 64 + square;
+        }
+        index
+    }
+
+    fn decode(&self, mut index: usize) -> (bool, Squares) {
+        let mut squares = [0; 2 + MAX_EXTRA_PIECES];
+        for square in squares[..2 + self.pieces.len()].iter_mut().rev() {
+            *square = index % 64;
+            index /= 64;
+        }
+        (index == 0, squares)
+    }
+
+    fn occupancy(&self, squares: &Squares) -> Bitboard {
+        Bitboard::with_ones(
+            squares[..2 + self.pieces.len()]
+                .iter()
+                .map(|&square| position(square)),
+        )
+    }
+
+    fn is_legal(&self, white_to_move: bool, squares: &Squares) -> bool {
+        let squares_used = &squares[..2 + self.pieces.len()];
+        for (i, &square) in squares_used.iter().enumerate() {
+            if squares_used[i + 1..].contains(&square) {
+                return false;
+            }
+        }
+        if king_targets(squares[0]) & position(squares[1]) != 0 {
+            return false;
+        }
+        for (i, &kind) in self.pieces.iter().enumerate() {
+            if kind == PieceType::Pawn && (rank(squares[2 + i]) == 0 || rank(squares[2 + i]) == 7) {
+                return false;
+            }
+        }
+        // the side that is not to move may not be in check
+        !white_to_move || !self.is_black_in_check(squares)
+    }
+
+    fn is_black_in_check(&self, squares: &Squares) -> bool {
+        self.white_attacks(squares, self.occupancy(squares), None) & position(squares[1]) != 0
+    }
+
+    /// The squares attacked by white, leaving out the piece with index `skip`.
+    fn white_attacks(
+        &self,
+        squares: &Squares,
+        occupancy: Bitboard,
+        skip: Option<usize>,
+    ) -> Bitboard {
+        let mut white_attacks = king_targets(squares[0]);
+        for (i, &kind) in self.pieces.iter().enumerate() {
+            if Some(i) != skip {
+                white_attacks |= attacks(kind, squares[2 + i], occupancy);
+            }
+        }
+        white_attacks
+    }
+
+    /// The legal moves of the black king, with the index of the captured piece, if any.
+    fn black_moves(&self, squares: &Squares) -> Vec<(usize, Option<usize>)> {
+        // the king must not shield the squares behind it from sliding pieces
+        let occupancy = self.occupancy(squares) & !Bitboard::with_one(position(squares[1]));
+        let attacks = self.white_attacks(squares, occupancy, None);
+        let mut moves = Vec::new();
+        for target in squares_of(king_targets(squares[1]) & !king_targets(squares[0])) {
+            let captured = squares[2..2 + self.pieces.len()]
+                .iter()
+                .position(|&square| square == target);
+            let attacks = match captured {
+                Some(i) => self.white_attacks(squares, occupancy, Some(i)),
+                None => attacks,
+            };
+            if attacks & position(target) == 0 {
+                moves.push((target, captured));
+            }
+        }
+        moves
+    }
+}
+
+/// The adaptive probabilities of the range coder: for every previous value, a binary tree
+/// over the bits of the next value, from the highest bit down.
+fn initial_probabilities() -> Vec<u16> {
+    vec![1 << (PROBABILITY_BITS - 1); 256 * 256]
+}
+
+/// Move `probability` of a zero bit towards the bit that was coded.
+fn adapt(probability: &mut u16, bit: bool) {
+    if bit {
+        *probability -= *probability >> ADAPTATION_SHIFT;
+    } else {
+        *probability += ((1 << PROBABILITY_BITS) - *probability) >> ADAPTATION_SHIFT;
+    }
+}
+
+/// Compress table values with a binary range coder, in the style of LZMA.
+fn compress(values: &[u8]) -> Vec<u8> {
+    let mut encoder = Encoder {
+        low: 0,
+        range: u32::MAX,
+        cache: 0,
+        cache_size: 1,
+        bytes: Vec::new(),
+    };
+    let mut probabilities = initial_probabilities();
+    let mut previous = 0;
+    for &value in values {
+        let context = &mut probabilities[previous as usize * 256..][..256];
+        let mut node = 1;
+        for shift in (0..8).rev() {
+            let bit = value >> shift & 1 == 1;
+            encoder.encode(&mut context[node], bit);
+            node = node * 2 + bit as usize;
+        }
+        previous = value;
+    }
+    for _ in 0..5 {
+        encoder.shift_low();
+    }
+    encoder.bytes
+}
+
+/// Decompress `len` values compressed by [`compress`].
+///
+/// # Returns
+/// * `Some` with the values.
+/// * `None` if `bytes` is truncated or has bytes left over.
+fn decompress(bytes: &[u8], len: usize) -> Option<Vec<u8>> {
+    let mut decoder = Decoder {
+        code: 0,
+        range: u32::MAX,
+        bytes: bytes.iter(),
+    };
+    for _ in 0..5 {
+        decoder.code = decoder.code << 8 | *decoder.bytes.next()? as u32;
+    }
+    let mut probabilities = initial_probabilities();
+    let mut values = Vec::with_capacity(len);
+    let mut previous = 0;
+    for _ in 0..len {
+        let context = &mut probabilities[previous as usize * 256..][..256];
+        let mut node = 1;
+        while node < 256 {
+            let bit = decoder.decode(&mut context[node])?;
+            node = node * 2 + bit as usize;
+        }
+        previous = (node - 256) as u8;
+        values.push(previous);
+    }
+    if decoder.bytes.next().is_some() {
+        return None;
+    }
+    Some(values)
+}
+
+struct Encoder {
+    low: u64,
+    range: u32,
+    /// The last byte shifted out of `low`, held back in case a carry reaches it.
+    cache: u8,
+    /// The number of bytes held back: `cache` followed by `0xff` bytes.
+    cache_size: usize,
+    bytes: Vec<u8>,
+}
+
+impl Encoder {
+    fn encode(&mut self, probability: &mut u16, bit: bool) {
+        let bound = (self.range >> PROBABILITY_BITS) * *probability as u32;
+        if bit {
+            self.low += bound as u64;
+            self.range -= bound;
+        } else {
+            self.range = bound;
+        }
+        adapt(probability, bit);
+        while self.range < TOP_RANGE {
+            self.range <<= 8;
+            self.shift_low();
+        }
+    }
+
+    fn shift_low(&mut self) {
+        if self.low < 0xff00_0000 || self.low >= 1 << 32 {
+            let carry = (self.low >> 32) as u8;
+            self.bytes.push(self.cache.wrapping_add(carry));
+            for _ in 1..self.cache_size {
+                self.bytes.push(0xffu8.wrapping_add(carry));
+            }
+            self.cache_size = 0;
+            self.cache = (self.low >> 24) as u8;
+        }
+        self.cache_size += 1;
+        self.low = (self.low & 0x00ff_ffff) << 8;
+    }
+}
+
+struct Decoder<'a> {
+    code: u32,
+    range: u32,
+    bytes: std::slice::Iter<'a, u8>,
+}
+
+impl Decoder<'_> {
+    fn decode(&mut self, probability: &mut u16) -> Option<bool> {
+        let bound = (self.range >> PROBABILITY_BITS) * *probability as u32;
+        let bit = self.code >= bound;
+        if bit {
+            self.code -= bound;
+            self.range -= bound;
+        } else {
+            self.range = bound;
+        }
+        adapt(probability, bit);
+        if self.range < TOP_RANGE {
+            self.range <<= 8;
+            self.code = self.code << 8 | *self.bytes.next()? as u32;
+        }
+        Some(bit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn longest_win(table: &EndgameTable) -> u8 {
+        let half = table.values.len() / 2;
+        table.values[..half].iter().copied().max().unwrap() - 1
+    }
+
+    #[test]
+    fn signatures() {
+        assert_eq!(
+            parse_signature("KNBK"),
+            Ok(vec![PieceType::Bishop, PieceType::Knight])
+        );
+        assert_eq!(signature_of(&parse_signature("KPQK").unwrap()), "KQPK");
+        assert!(parse_signature("KQ").is_err());
+        assert!(parse_signature("KXK").is_err());
+        assert!(EndgameTable::generate("KQRBK").is_err());
+    }
+
+    #[test]
+    fn king_and_queen() {
+        let table = EndgameTable::generate("KQK").unwrap();
+        // the longest mate takes ten moves
+        assert_eq!(longest_win(&table), 19);
+
+        let game = Game::from_fen_string("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1").unwrap();
+        assert_eq!(table.probe(&game), Some(Dtm::Win(1)));
+        let game = Game::from_fen_string("k5Q1/8/1K6/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(table.probe(&game), Some(Dtm::Loss(0)));
+        // stalemate
+        let game = Game::from_fen_string("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(table.probe(&game), Some(Dtm::Draw));
+        let game = Game::from_fen_string("8/8/8/8/8/8/2K5/kQ6 b - - 0 1").unwrap();
+        assert_eq!(table.probe(&game), Some(Dtm::Loss(0)));
+        // the queen is lost
+        let game = Game::from_fen_string("8/8/8/8/8/8/1k6/1Q5K b - - 0 1").unwrap();
+        assert_eq!(table.probe(&game), Some(Dtm::Draw));
+
+        // black as the strong side
+        let game = Game::from_fen_string("K5q1/8/1k6/8/8/8/8/8 w - - 0 1").unwrap();
+        assert_eq!(table.probe(&game), Some(Dtm::Loss(0)));
+        let game = Game::from_fen_string("K7/8/1k6/8/8/8/8/6q1 b - - 0 1").unwrap();
+        assert_eq!(table.probe(&game), Some(Dtm::Win(1)));
+
+        assert_eq!(table.probe(&Game::default()), None);
+        let game = Game::from_fen_string("k7/8/1K6/8/8/8/8/6R1 w - - 0 1").unwrap();
+        assert_eq!(table.probe(&game), None);
+    }
+
+    #[test]
+    fn king_and_rook() {
+        let table = EndgameTable::generate("KRK").unwrap();
+        // the longest mate takes sixteen moves
+        assert_eq!(longest_win(&table), 31);
+
+        let game = Game::from_fen_string("k7/8/1K6/8/8/8/8/6R1 w - - 0 1").unwrap();
+        assert_eq!(table.probe(&game), Some(Dtm::Win(1)));
+    }
+
+    #[test]
+    fn king_and_pawn() {
+        let table = EndgameTable::generate("KPK").unwrap();
+
+        // the king on the sixth rank in front of its pawn wins with either side to move
+        let game = Game::from_fen_string("3k4/8/3K4/3P4/8/8/8/8 w - - 0 1").unwrap();
+        assert!(matches!(table.probe(&game), Some(Dtm::Win(_))));
+        let game = Game::from_fen_string("3k4/8/3K4/3P4/8/8/8/8 b - - 0 1").unwrap();
+        assert!(matches!(table.probe(&game), Some(Dtm::Loss(_))));
+        // only the promotion mates
+        let game = Game::from_fen_string("k7/2P5/1K6/8/8/8/8/8 w - - 0 1").unwrap();
+        assert_eq!(table.probe(&game), Some(Dtm::Win(1)));
+
+        // stalemate, and the lone king taking the opposition
+        let game = Game::from_fen_string("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(table.probe(&game), Some(Dtm::Draw));
+        let game = Game::from_fen_string("8/4k3/8/4K3/4P3/8/8/8 w - - 0 1").unwrap();
+        assert_eq!(table.probe(&game), Some(Dtm::Draw));
+        let game = Game::from_fen_string("8/4k3/8/4K3/4P3/8/8/8 b - - 0 1").unwrap();
+        assert!(matches!(table.probe(&game), Some(Dtm::Loss(_))));
+        // the lone king reaches the corner in front of a rook pawn
+        let game = Game::from_fen_string("k7/8/8/8/8/8/P7/7K w - - 0 1").unwrap();
+        assert_eq!(table.probe(&game), Some(Dtm::Draw));
+
+        // black as the strong side
+        let game = Game::from_fen_string("8/8/8/8/8/1k6/2p5/K7 b - - 0 1").unwrap();
+        assert_eq!(table.probe(&game), Some(Dtm::Win(1)));
+    }
+
+    fn saved_table(signature: &str) -> EndgameTable {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/endgame")
+            .join(format!("{}.egt", signature));
+        EndgameTable::load(path).unwrap()
+    }
+
+    #[test]
+    fn bishop_and_knight() {
+        let table = saved_table("KBNK");
+        // the longest mate takes 33 moves
+        assert_eq!(longest_win(&table), 65);
+
+        let game = Game::from_fen_string("7k/8/6KN/8/8/8/8/6B1 w - - 0 1").unwrap();
+        assert_eq!(table.probe(&game), Some(Dtm::Win(1)));
+        let game = Game::from_fen_string("7k/8/6KN/4B3/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(table.probe(&game), Some(Dtm::Loss(0)));
+        // the bishop can't reach the long diagonal in time
+        let game = Game::from_fen_string("7k/8/6KN/8/8/8/8/5B2 w - - 0 1").unwrap();
+        assert_ne!(table.probe(&game), Some(Dtm::Win(1)));
+    }
+
+    #[test]
+    #[ignore = "generating KBNK takes minutes without optimizations"]
+    fn generate_bishop_and_knight() {
+        assert_eq!(EndgameTable::generate("KBNK"), Ok(saved_table("KBNK")));
+    }
+
+    #[test]
+    fn compression() {
+        let values: Vec<u8> = (0..10_000u32)
+            .map(|i| (i * i % 251) as u8 & if i % 7 == 0 { 0xff } else { 0x0f })
+            .collect();
+        let bytes = compress(&values);
+        assert_eq!(decompress(&bytes, values.len()), Some(values.clone()));
+        assert_eq!(decompress(&bytes[..bytes.len() - 1], values.len()), None);
+        assert_eq!(decompress(&bytes, values.len() + 1000), None);
+        assert_eq!(compress(&[]), vec![0; 5]);
+        assert_eq!(decompress(&[0; 5], 0), Some(vec![]));
+    }
+
+    #[test]
+    fn save_and_load() {
+        let table = EndgameTable::generate("KNK").unwrap();
+        assert!(table.values.iter().all(|&value| value == 0));
+
+        let bytes = table.to_bytes();
+        // a table of draws compresses to a small fraction of its size
+        assert!(bytes.len() < 2 * 10 * 64 * 64 / 20);
+        assert_eq!(EndgameTable::from_bytes(&bytes), Ok(table.clone()));
+        assert!(EndgameTable::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(EndgameTable::from_bytes(&[&bytes[..], &[0]].concat()).is_err());
+        assert!(EndgameTable::from_bytes(b"not a table").is_err());
+
+        let path = std::env::temp_dir().join(format!("KNK-{}.egt", std::process::id()));
+        table.save(&path).unwrap();
+        assert_eq!(EndgameTable::load(&path), Ok(table));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,236 @@
+use crate::{
+    chess_move::ChessMove,
+    game::{GameOver, GameOverReason},
+    move_info::{CheckKind, MoveInfo},
+    Color, Piece,
+};
+use bitboard::*;
+use std::fmt::Debug;
+
+/// Something that happened in a [`Game`], as passed to the listeners registered with
+/// [`Game::subscribe`].
+///
+/// Making a move fires [`GameEvent::MoveMade`] first, followed by the events for what the move
+/// did, and finally [`GameEvent::GameOver`] if the move ended the game.
+///
+/// [`Game`]: crate::game::Game
+/// [`Game::subscribe`]: crate::game::Game::subscribe
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameEvent {
+    MoveMade(MoveInfo),
+    Capture {
+        piece: Piece,
+        square: Position,
+    },
+    Check(CheckKind),
+    Promotion {
+        square: Position,
+        piece: Piece,
+    },
+    Castle {
+        color: Color,
+        kingside: bool,
+    },
+    GameOver {
+        result: GameOver,
+        reason: GameOverReason,
+    },
+    /// A move was taken back.
+    Undo(ChessMove),
+    /// A player offered a draw.
+    DrawOffer(Color),
+}
+
+impl GameEvent {
+    /// List the events for a move that was just made, not including [`GameEvent::GameOver`].
+    pub(crate) fn for_move(move_info: MoveInfo) -> Vec<GameEvent> {
+        let mut events = vec![GameEvent::MoveMade(move_info)];
+        if let (Some(piece), Some(square)) = (move_info.captured(), move_info.captured_square()) {
+            events.push(GameEvent::Capture { piece, square });
+        }
+        if let Some(promotion) = move_info.promotion() {
+            events.push(GameEvent::Promotion {
+                square: move_info.chess_move().to(),
+                piece: promotion.create_piece(move_info.player()),
+            });
+        }
+        if let ChessMove::Castle { king_to, .. } = move_info.chess_move() {
+            events.push(GameEvent::Castle {
+                color: move_info.player(),
+                kingside: king_to.file() == File::G,
+            });
+        }
+        if let Some(check) = move_info.check() {
+            events.push(GameEvent::Check(check));
+        }
+        events
+    }
+}
+
+/// Identifies a listener registered with [`Game::subscribe`], to remove it again.
+///
+/// [`Game::subscribe`]: crate::game::Game::subscribe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ListenerId(u64);
+
+/// Listeners must be `Sync` as well as `Send`, so that a [`Game`] can be shared between threads.
+///
+/// [`Game`]: crate::game::Game
+type Listener = Box<dyn FnMut(&GameEvent) + Send + Sync>;
+
+/// The listeners registered on a game.
+///
+/// Listeners are not copied when a game is cloned, so that the clones made while searching or
+/// analysing a game don't fire events.
+#[derive(Default)]
+pub(crate) struct Listeners {
+    next_id: u64,
+    listeners: Vec<(ListenerId, Listener)>,
+}
+
+impl Listeners {
+    pub(crate) fn add(&mut self, listener: Listener) -> ListenerId {
+        let id = ListenerId(self.next_id);
+        self.next_id += 1;
+        self.listeners.push((id, listener));
+        id
+    }
+
+    pub(crate) fn remove(&mut self, id: ListenerId) -> bool {
+        let len = self.listeners.len();
+        self.listeners.retain(|(listener_id, _)| *listener_id != id);
+        self.listeners.len() != len
+    }
+
+    /// Pass `event` to every listener, in the order they were registered.
+    pub(crate) fn fire(&mut self, event: GameEvent) {
+        for (_, listener) in &mut self.listeners {
+            listener(&event);
+        }
+    }
+}
+
+impl Clone for Listeners {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl Debug for Listeners {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Listeners({})", self.listeners.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Game;
+    use std::sync::{Arc, Mutex};
+
+    fn record(game: &mut Game) -> Arc<Mutex<Vec<GameEvent>>> {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&events);
+        game.subscribe(move |event| recorded.lock().unwrap().push(*event));
+        events
+    }
+
+    fn play(game: &mut Game, uci: &str) {
+        game.make_move(game.parse_uci_move(uci).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn move_events() {
+        let mut game = Game::from_fen_string("8/1P6/6k1/8/8/8/8/4K2R w K - 0 1").unwrap();
+        let events = record(&mut game);
+        play(&mut game, "b7b8q");
+        play(&mut game, "g6g5");
+        play(&mut game, "e1g1");
+
+        let events = events.lock().unwrap();
+        assert!(matches!(events[0], GameEvent::MoveMade(_)));
+        assert_eq!(
+            events[1..3],
+            [
+                GameEvent::Promotion {
+                    square: B8,
+                    piece: Piece::queen(Color::White)
+                },
+                GameEvent::MoveMade(game.move_info_history()[1]),
+            ]
+        );
+        assert_eq!(
+            events[4..],
+            [GameEvent::Castle {
+                color: Color::White,
+                kingside: true
+            }]
+        );
+    }
+
+    #[test]
+    fn capture_check_and_game_over() {
+        let mut game = Game::from_fen_string("6k1/5ppp/8/8/8/8/r7/R6K w - - 0 1").unwrap();
+        let events = record(&mut game);
+        play(&mut game, "a1a2");
+        assert_eq!(
+            events.lock().unwrap()[1..],
+            [GameEvent::Capture {
+                piece: Piece::rook(Color::Black),
+                square: A2
+            }]
+        );
+
+        events.lock().unwrap().clear();
+        play(&mut game, "g8h8");
+        play(&mut game, "a2a8");
+        let events = events.lock().unwrap();
+        assert_eq!(
+            events[2..],
+            [
+                GameEvent::Check(CheckKind::Checkmate),
+                GameEvent::GameOver {
+                    result: GameOver::Winner(Color::White),
+                    reason: GameOverReason::Checkmate
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn undo_and_draw_offer() {
+        let mut game = Game::default();
+        let first = record(&mut game);
+        let second = record(&mut game);
+        play(&mut game, "e2e4");
+        game.undo_move();
+        game.offer_draw(Color::Black);
+        game.clone().undo_move();
+
+        assert_eq!(
+            first.lock().unwrap()[1..],
+            [
+                GameEvent::Undo(ChessMove::Regular { from: E2, to: E4 }),
+                GameEvent::DrawOffer(Color::Black)
+            ]
+        );
+        assert_eq!(*first.lock().unwrap(), *second.lock().unwrap());
+    }
+
+    #[test]
+    fn unsubscribe() {
+        let mut game = Game::default();
+        let events = record(&mut game);
+        let id = game.subscribe(|_| panic!("unsubscribed listener called"));
+        assert!(game.unsubscribe(id));
+        assert!(!game.unsubscribe(id));
+        play(&mut game, "e2e4");
+        assert_eq!(events.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn game_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Game>();
+    }
+}
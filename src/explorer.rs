@@ -0,0 +1,275 @@
+//! Statistics of the moves played from each position of a collection of games, like the opening
+//! explorer of Lichess.
+//!
+//! Positions are identified by [`Game::position_hash`], so the statistics of a position include
+//! every game that reached it, whatever the move order. Ratings are read from the `WhiteElo` and
+//! `BlackElo` tags.
+
+use crate::{
+    chess_move::ChessMove,
+    database::GameRecord,
+    game::{Game, GameOver},
+    Color,
+};
+use std::collections::HashMap;
+
+/// The statistics of a move played from a position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct MoveStats {
+    games: u32,
+    white_wins: u32,
+    draws: u32,
+    black_wins: u32,
+    white_rating_sum: u64,
+    white_rated: u32,
+    black_rating_sum: u64,
+    black_rated: u32,
+}
+
+/// A move played from a position, with the results of the games it was played in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExplorerMove {
+    chess_move: ChessMove,
+    san: String,
+    stats: MoveStats,
+}
+
+impl ExplorerMove {
+    pub fn chess_move(&self) -> ChessMove {
+        self.chess_move
+    }
+
+    /// The move in standard algebraic notation.
+    pub fn san(&self) -> &str {
+        &self.san
+    }
+
+    /// The number of games the move was played in.
+    pub fn games(&self) -> u32 {
+        self.stats.games
+    }
+
+    pub fn white_wins(&self) -> u32 {
+        self.stats.white_wins
+    }
+
+    pub fn draws(&self) -> u32 {
+        self.stats.draws
+    }
+
+    pub fn black_wins(&self) -> u32 {
+        self.stats.black_wins
+    }
+
+    /// The percentage of the games won by white, out of the games with a result.
+    pub fn white_percentage(&self) -> f64 {
+        self.percentage(self.stats.white_wins)
+    }
+
+    pub fn draw_percentage(&self) -> f64 {
+        self.percentage(self.stats.draws)
+    }
+
+    pub fn black_percentage(&self) -> f64 {
+        self.percentage(self.stats.black_wins)
+    }
+
+    /// The average rating of the white players, over the games with a `WhiteElo` tag.
+    pub fn average_white_rating(&self) -> Option<u32> {
+        average(self.stats.white_rating_sum, self.stats.white_rated)
+    }
+
+    /// The average rating of the black players, over the games with a `BlackElo` tag.
+    pub fn average_black_rating(&self) -> Option<u32> {
+        average(self.stats.black_rating_sum, self.stats.black_rated)
+    }
+
+    fn percentage(&self, count: u32) -> f64 {
+        let finished = self.stats.white_wins + self.stats.draws + self.stats.black_wins;
+        if finished == 0 {
+            0.0
+        } else {
+            100.0 * count as f64 / finished as f64
+        }
+    }
+}
+
+fn average(sum: u64, count: u32) -> Option<u32> {
+    if count == 0 {
+        None
+    } else {
+        Some((sum / count as u64) as u32)
+    }
+}
+
+/// The moves played from each position of a collection of games.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpeningExplorer {
+    max_plies: usize,
+    positions: HashMap<u64, HashMap<ChessMove, MoveStats>>,
+}
+
+impl OpeningExplorer {
+    /// Create an empty explorer that records the first `max_plies` moves of each game.
+    pub fn new(max_plies: usize) -> Self {
+        Self {
+            max_plies,
+            positions: HashMap::new(),
+        }
+    }
+
+    /// Add the moves of a game, counting its result and the ratings of its players.
+    pub fn add_record(&mut self, record: &GameRecord) {
+        let rating = |tag| {
+            record
+                .tag(tag)
+                .and_then(|rating: &str| rating.trim().parse::<u32>().ok())
+        };
+        let (white_rating, black_rating) = (rating("WhiteElo"), rating("BlackElo"));
+
+        let mut replay = record.game().clone();
+        while replay.undo_move().is_some() {}
+        for &chess_move in record.game().move_history().iter().take(self.max_plies) {
+            let stats = self
+                .positions
+                .entry(replay.position_hash())
+                .or_default()
+                .entry(chess_move)
+                .or_default();
+            stats.games += 1;
+            match record.result() {
+                Some(GameOver::Winner(Color::White)) => stats.white_wins += 1,
+                Some(GameOver::Winner(Color::Black)) => stats.black_wins += 1,
+                Some(GameOver::Draw) => stats.draws += 1,
+                None => {}
+            }
+            if let Some(rating) = white_rating {
+                stats.white_rating_sum += rating as u64;
+                stats.white_rated += 1;
+            }
+            if let Some(rating) = black_rating {
+                stats.black_rating_sum += rating as u64;
+                stats.black_rated += 1;
+            }
+            replay.make_move(chess_move).unwrap();
+        }
+    }
+
+    /// Add the games read from a PGN file or a game database.
+    ///
+    /// # Returns
+    /// The errors of the games that couldn't be read, with their position in `records`.
+    pub fn add_records<I>(&mut self, records: I) -> Vec<(usize, String)>
+    where
+        I: IntoIterator<Item = Result<GameRecord, String>>,
+    {
+        let mut errors = Vec::new();
+        for (i, record) in records.into_iter().enumerate() {
+            match record {
+                Ok(record) => self.add_record(&record),
+                Err(e) => errors.push((i, e)),
+            }
+        }
+        errors
+    }
+
+    /// List the moves played from the current position of `game`, the most played first.
+    pub fn moves(&self, game: &Game) -> Vec<ExplorerMove> {
+        let moves = match self.positions.get(&game.position_hash()) {
+            Some(moves) => moves,
+            None => return vec![],
+        };
+        let mut moves: Vec<ExplorerMove> = moves
+            .iter()
+            .filter_map(|(&chess_move, &stats)| {
+                Some(ExplorerMove {
+                    chess_move,
+                    san: game.to_san_string(chess_move).ok()?,
+                    stats,
+                })
+            })
+            .collect();
+        moves.sort_by(|a, b| b.games().cmp(&a.games()).then_with(|| a.san.cmp(&b.san)));
+        moves
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn::PgnReader;
+
+    const PGN: &str = r#"[WhiteElo "2000"]
+[BlackElo "1800"]
+[Result "1-0"]
+1. e4 e5 2. Nf3 Nc6 3. Bc4 1-0
+
+[WhiteElo "2200"]
+[Result "1/2-1/2"]
+1. Nf3 Nc6 2. e4 e5 3. Bb5 1/2-1/2
+
+[Result "0-1"]
+1. e4 c5 0-1
+
+[Result "*"]
+1. d4 *
+"#;
+
+    fn explorer() -> OpeningExplorer {
+        let mut explorer = OpeningExplorer::new(40);
+        assert!(explorer
+            .add_records(PgnReader::new(PGN.as_bytes()))
+            .is_empty());
+        explorer
+    }
+
+    fn play(moves: &[&str]) -> Game {
+        let mut game = Game::default();
+        for san in moves {
+            game.make_move(game.parse_san_move(san).unwrap()).unwrap();
+        }
+        game
+    }
+
+    #[test]
+    fn start_position() {
+        let moves = explorer().moves(&Game::default());
+        let summary: Vec<(&str, u32)> = moves.iter().map(|m| (m.san(), m.games())).collect();
+        assert_eq!(summary, vec![("e4", 2), ("Nf3", 1), ("d4", 1)]);
+
+        let e4 = &moves[0];
+        assert_eq!((e4.white_wins(), e4.draws(), e4.black_wins()), (1, 0, 1));
+        assert_eq!(e4.white_percentage(), 50.0);
+        assert_eq!(e4.average_white_rating(), Some(2000));
+        assert_eq!(e4.average_black_rating(), Some(1800));
+        assert_eq!(moves[2].white_percentage(), 0.0);
+        assert_eq!(moves[2].average_white_rating(), None);
+    }
+
+    #[test]
+    fn transpositions() {
+        let explorer = explorer();
+        let moves = explorer.moves(&play(&["e4", "e5", "Nf3", "Nc6"]));
+        let summary: Vec<(&str, f64, f64, Option<u32>)> = moves
+            .iter()
+            .map(|m| {
+                (
+                    m.san(),
+                    m.white_percentage(),
+                    m.draw_percentage(),
+                    m.average_white_rating(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("Bb5", 0.0, 100.0, Some(2200)),
+                ("Bc4", 100.0, 0.0, Some(2000))
+            ]
+        );
+
+        assert!(explorer.moves(&play(&["a3"])).is_empty());
+        assert!(OpeningExplorer::new(1).moves(&play(&["e4"])).is_empty());
+    }
+}
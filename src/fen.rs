@@ -118,7 +118,7 @@ impl FromStr for Fen {
     }
 }
 
-fn board_from_fen_part_0(part0: &str) -> Result<ChessBoard, String> {
+pub(crate) fn board_from_fen_part_0(part0: &str) -> Result<ChessBoard, String> {
     let empty = Bitboard::empty();
     let (
         mut white_kings,
@@ -1,18 +1,32 @@
 use crate::{
     chess_board::ChessBoard,
-    chess_move::{CastlingRights, ChessMove, MoveManager},
+    chess_move::{CastlingRights, ChessMove, MoveManager, PromotionPiece},
+    clock::Clock,
+    events::{GameEvent, ListenerId, Listeners},
     fen::Fen,
-    Color,
+    illegal_move::{self, IllegalMove},
+    move_info::{self, MoveInfo, PositionState, SquareChange},
+    san, zobrist, Color,
 };
-use bitboard::{File, Position, Rank};
+use bitboard::{Bitboard, File, Position, Rank};
 use std::{collections::HashSet, str::FromStr};
 
 /// A game of chess.
+///
+/// Cloning a game copies the position and the moves made so far, but not the attached clock or
+/// the listeners, so that clones made to replay or search the game can make moves after a flag
+/// has fallen and don't fire events.
 #[derive(Debug)]
 pub struct Game {
     current_player: Color,
     move_manager: MoveManager,
     board: ChessBoard,
+    move_info_history: Vec<MoveInfo>,
+    clock: Option<Clock>,
+    draw_offer: Option<Color>,
+    drawn_by_agreement: bool,
+    game_over_announced: bool,
+    listeners: Listeners,
 }
 
 impl Game {
@@ -21,6 +35,12 @@ impl Game {
             current_player,
             move_manager,
             board,
+            move_info_history: vec![],
+            clock: None,
+            draw_offer: None,
+            drawn_by_agreement: false,
+            game_over_announced: false,
+            listeners: Listeners::default(),
         }
     }
 
@@ -39,10 +59,61 @@ impl Game {
         self.move_manager.get_legal_moves()
     }
 
+    /// Get a list of the captures and promotions available to the current player.
+    ///
+    /// Unlike [`Game::get_moves`], this does not require the full set of legal moves.
+    /// If `include_checks` is `true`, quiet moves that give check are included as well.
+    pub fn get_tactical_moves(&self, include_checks: bool) -> Vec<ChessMove> {
+        self.move_manager
+            .evaluate_tactical_moves(&self.board, self.current_player, include_checks)
+    }
+
+    /// Get every piece of `color` that attacks `square`.
+    pub fn attackers_of(&self, square: Position, color: Color) -> Bitboard {
+        self.board.attackers_of(square, color)
+    }
+
+    /// Get every square attacked by at least one piece of `color`.
+    pub fn attacked_squares(&self, color: Color) -> Bitboard {
+        self.board.attacked_squares(color)
+    }
+
+    /// Get the number of pieces of `color` that attack `square`.
+    pub fn control_count(&self, square: Position, color: Color) -> u32 {
+        self.board.control_count(square, color)
+    }
+
+    /// Returns `true` if the current player is in check.
+    pub(crate) fn is_in_check(&self) -> bool {
+        self.move_manager
+            .is_in_check(&self.board, self.current_player)
+    }
+
+    pub(crate) fn move_manager(&self) -> &MoveManager {
+        &self.move_manager
+    }
+
+    /// The square the current player can capture en passant on, if any.
+    pub(crate) fn en_passant_target(&self) -> Option<Position> {
+        match self.current_player {
+            Color::White => self.move_manager.white_en_passant_target(),
+            Color::Black => self.move_manager.black_en_passant_target(),
+        }
+    }
+
     pub(crate) fn castling_rights(&self) -> CastlingRights {
         self.move_manager.castling_rights()
     }
 
+    /// Get a hash of the current position that is the same however the position was reached.
+    ///
+    /// The hash covers the pieces, the player to move, the castling rights and the en passant
+    /// target when an en passant capture is possible, but not the clocks. It doesn't change
+    /// between runs, so it can be stored.
+    pub fn position_hash(&self) -> u64 {
+        zobrist::hash(self)
+    }
+
     /// Get a list of all possible moves for the current player from `from`.
     pub fn get_moves_from(&self, from: Position) -> Vec<ChessMove> {
         let moves = self.move_manager.get_legal_moves();
@@ -56,49 +127,367 @@ impl Game {
         moves_from
     }
 
-    /// Returns `true` if the game is over (if a checkmate or stalemate has been reached).
+    /// Get the legal moves of the current player in a fixed order: by the square moved from,
+    /// then the square moved to, then the promotion piece from knight to queen.
+    pub fn ordered_moves(&self) -> Vec<ChessMove> {
+        let square = |pos: Position| u8::from(pos.rank()) * 8 + u8::from(pos.file());
+        let mut moves: Vec<ChessMove> = self.get_moves().iter().copied().collect();
+        moves.sort_by_key(|chess_move| {
+            let promotion = match chess_move {
+                ChessMove::Promotion { piece, .. } => *piece as u8 + 1,
+                _ => 0,
+            };
+            (
+                square(chess_move.from()),
+                square(chess_move.to()),
+                promotion,
+            )
+        });
+        moves
+    }
+
+    /// Find the legal move of the current player from `from` to `to`, or explain why there is
+    /// none.
+    ///
+    /// `promotion` must be given exactly when a pawn moves to the last rank. Castling is given as
+    /// the move of the king.
+    ///
+    /// # Returns
+    /// * `Ok` with the matching legal move.
+    /// * `Err` with the reason the move is illegal.
+    pub fn explain_move(
+        &self,
+        from: Position,
+        to: Position,
+        promotion: Option<PromotionPiece>,
+    ) -> Result<ChessMove, IllegalMove> {
+        illegal_move::explain_move(self, from, to, promotion)
+    }
+
+    /// List the changes `chess_move` would make to the squares of the board, such as the piece
+    /// removed by an en passant capture or the rook moved by castling.
+    ///
+    /// # Returns
+    /// * `Ok` with the changes, in the order they happen.
+    /// * `Err` if the move is not legal.
+    pub fn square_changes(&self, chess_move: ChessMove) -> Result<Vec<SquareChange>, &'static str> {
+        if !self.move_manager.is_legal(chess_move) {
+            return Err("illegal move");
+        }
+        let piece = self.board.get_piece(chess_move.from()).unwrap();
+        let captured_square = move_info::captured_square(chess_move);
+        let captured = self
+            .board
+            .get_piece(captured_square)
+            .map(|captured| (captured, captured_square));
+        Ok(move_info::square_changes(chess_move, piece, captured))
+    }
+
+    /// Write a legal move of the current player in standard algebraic notation, such as `Nf3`,
+    /// `exd5`, `e8=Q+` or `O-O-O`.
+    ///
+    /// # Returns
+    /// * `Ok` with the move in SAN.
+    /// * `Err` if the move is not legal.
+    pub fn to_san_string(&self, chess_move: ChessMove) -> Result<String, &'static str> {
+        san::to_san(self, chess_move)
+    }
+
+    /// Parse a move for the current player written in standard algebraic notation.
+    ///
+    /// Check and annotation suffixes such as `+` or `!?` are ignored, the `=` before a promotion
+    /// piece may be left out, and castling may be written with zeros.
+    ///
+    /// # Returns
+    /// * `Ok` with the matching legal move.
+    /// * `Err` if the string does not describe exactly one legal move.
+    pub fn parse_san_move(&self, san: &str) -> Result<ChessMove, String> {
+        san::parse_san(self, san)
+    }
+
+    /// Parse a move for the current player written in UCI long algebraic notation, such as
+    /// `e2e4` or `e7e8q`.
+    ///
+    /// # Returns
+    /// * `Ok` with the matching legal move.
+    /// * `Err` if the string does not describe a legal move.
+    pub fn parse_uci_move(&self, uci: &str) -> Result<ChessMove, String> {
+        self.move_manager
+            .get_legal_moves()
+            .iter()
+            .copied()
+            .find(|chess_move| chess_move.to_uci_string() == uci)
+            .ok_or_else(|| format!("illegal move '{}'", uci))
+    }
+
+    /// Statically evaluate the exchange of pieces started by `chess_move`.
+    ///
+    /// # Returns
+    /// * `Some` with the expected material gain, in centipawns, for the current player.
+    ///   A negative value means that the moving piece can be won by the opponent.
+    /// * `None` if the move is not legal.
+    pub fn static_exchange_evaluation(&self, chess_move: ChessMove) -> Option<i32> {
+        if !self.move_manager.is_legal(chess_move) {
+            return None;
+        }
+        Some(self.move_manager.static_exchange_evaluation(
+            &self.board,
+            self.current_player,
+            chess_move,
+        ))
+    }
+
+    /// Returns `true` if the game is over (if a checkmate or stalemate has been reached, a flag
+    /// has fallen on the attached clock, or a draw has been agreed).
     pub fn is_over(&self) -> bool {
         self.move_manager.get_legal_moves().is_empty()
+            || self.flag_fallen().is_some()
+            || self.drawn_by_agreement
     }
 
     /// Returns the result of the game, or `None` if the game is not over.
+    ///
+    /// A player whose flag falls loses, unless their opponent doesn't have enough material to
+    /// checkmate, in which case the game is drawn.
     pub fn game_result(&self) -> Option<GameOver> {
-        if self.is_over() {
-            if self
-                .move_manager
-                .is_in_check(&self.board, self.current_player())
-            {
-                Some(GameOver::Winner(self.current_player().opponent()))
-            } else {
-                Some(GameOver::Draw)
+        match self.game_over_reason()? {
+            GameOverReason::Checkmate => Some(GameOver::Winner(self.current_player().opponent())),
+            GameOverReason::Stalemate | GameOverReason::Agreement => Some(GameOver::Draw),
+            GameOverReason::FlagFall => {
+                let flagged = self.flag_fallen().unwrap();
+                if self.board.has_mating_material(flagged.opponent()) {
+                    Some(GameOver::Winner(flagged.opponent()))
+                } else {
+                    Some(GameOver::Draw)
+                }
             }
-        } else {
+        }
+    }
+
+    /// Returns how the game ended, or `None` if the game is not over.
+    pub fn game_over_reason(&self) -> Option<GameOverReason> {
+        if self.drawn_by_agreement {
+            Some(GameOverReason::Agreement)
+        } else if self.flag_fallen().is_some() {
+            Some(GameOverReason::FlagFall)
+        } else if !self.move_manager.get_legal_moves().is_empty() {
             None
+        } else if self.is_in_check() {
+            Some(GameOverReason::Checkmate)
+        } else {
+            Some(GameOverReason::Stalemate)
         }
     }
 
     /// Make a move.
     ///
     /// # Returns
-    /// * `Ok` if the move was successful.
+    /// * `Ok` with information about the move if the move was successful.
     /// * `Err` if the game is over.
     /// * `Err` if the move is not legal.
-    pub fn make_move(&mut self, chess_move: ChessMove) -> Result<(), &'static str> {
+    /// * `Err` if the flag of the current player has fallen on the attached clock.
+    pub fn make_move(&mut self, chess_move: ChessMove) -> Result<MoveInfo, &'static str> {
         if self.is_over() {
+            self.announce_game_over();
             Err("game is over")
         } else if !self.move_manager.is_legal(chess_move) {
             Err("illegal move")
         } else {
+            if let Some(clock) = &mut self.clock {
+                if let Err(e) = clock.press() {
+                    self.announce_game_over();
+                    return Err(e);
+                }
+            }
+            self.make_move_unevaluated(chess_move);
+            self.evaluate_legal_moves();
+            // a draw offer lapses when the opponent moves instead of accepting it
+            if self.draw_offer == Some(self.current_player) {
+                self.draw_offer = None;
+            }
+
+            let move_info = *self.move_info_history.last().unwrap();
+            for event in GameEvent::for_move(move_info) {
+                self.listeners.fire(event);
+            }
+            if self.is_over() {
+                if let Some(clock) = &mut self.clock {
+                    clock.stop();
+                }
+                self.announce_game_over();
+            }
+
+            Ok(move_info)
+        }
+    }
+
+    /// Fire [`GameEvent::GameOver`] if the game is over and it hasn't been fired yet.
+    fn announce_game_over(&mut self) {
+        if self.game_over_announced {
+            return;
+        }
+        if let (Some(result), Some(reason)) = (self.game_result(), self.game_over_reason()) {
+            self.game_over_announced = true;
+            self.listeners.fire(GameEvent::GameOver { result, reason });
+        }
+    }
+
+    /// Make a move that is known to be legal, without evaluating the legal moves of the next
+    /// player.
+    ///
+    /// The moves returned by [`Game::get_moves`] are stale until
+    /// [`Game::evaluate_legal_moves`] is called.
+    pub(crate) fn make_move_unevaluated(&mut self, chess_move: ChessMove) {
+        let before = PositionState::of(self);
+        let piece = self.board.get_piece(chess_move.from()).unwrap();
+        let captured =
             self.move_manager
                 .make_move(&mut self.board, self.current_player, chess_move);
-            self.current_player = self.current_player.opponent();
-            self.move_manager
-                .evaluate_legal_moves(&self.board, self.current_player);
+        self.current_player = self.current_player.opponent();
+        let move_info = MoveInfo::new(self, chess_move, piece, captured, before);
+        self.move_info_history.push(move_info);
+    }
 
-            Ok(())
+    pub(crate) fn evaluate_legal_moves(&mut self) {
+        self.move_manager
+            .evaluate_legal_moves(&self.board, self.current_player);
+        if let Some(mut move_info) = self.move_info_history.pop() {
+            move_info.update_checkmate(self);
+            self.move_info_history.push(move_info);
         }
     }
 
+    /// Undo the last move.
+    ///
+    /// The attached clock, if any, is switched back to the player whose move was undone, without
+    /// giving back the time they spent.
+    ///
+    /// # Returns
+    /// The move that was undone, or `None` if no moves have been made.
+    pub fn undo_move(&mut self) -> Option<ChessMove> {
+        let chess_move = self.move_manager.undo_move(&mut self.board)?;
+        self.move_info_history.pop();
+        self.draw_offer = None;
+        self.drawn_by_agreement = false;
+        self.game_over_announced = false;
+        self.current_player = self.current_player.opponent();
+        if let Some(clock) = &mut self.clock {
+            if clock.running().is_some() {
+                clock.start(self.current_player);
+            }
+        }
+        self.move_manager
+            .evaluate_legal_moves(&self.board, self.current_player);
+        self.listeners.fire(GameEvent::Undo(chess_move));
+        Some(chess_move)
+    }
+
+    /// Offer a draw on behalf of `color`. The offer stands until the opponent accepts it with
+    /// [`Game::accept_draw`] or makes a move.
+    ///
+    /// # Returns
+    /// * `Ok` if the offer was made.
+    /// * `Err` if the game is over.
+    pub fn offer_draw(&mut self, color: Color) -> Result<(), &'static str> {
+        if self.is_over() {
+            return Err("game is over");
+        }
+        self.draw_offer = Some(color);
+        self.listeners.fire(GameEvent::DrawOffer(color));
+        Ok(())
+    }
+
+    /// Get the player whose draw offer is standing, if any.
+    pub fn draw_offer(&self) -> Option<Color> {
+        self.draw_offer
+    }
+
+    /// Accept the standing draw offer, ending the game in a draw.
+    ///
+    /// # Returns
+    /// * `Ok` if the game was drawn.
+    /// * `Err` if the game is over.
+    /// * `Err` if no draw has been offered.
+    pub fn accept_draw(&mut self) -> Result<(), &'static str> {
+        if self.is_over() {
+            return Err("game is over");
+        }
+        if self.draw_offer.take().is_none() {
+            return Err("no draw has been offered");
+        }
+        self.drawn_by_agreement = true;
+        if let Some(clock) = &mut self.clock {
+            clock.stop();
+        }
+        self.announce_game_over();
+        Ok(())
+    }
+
+    /// Register a listener that is called with every [`GameEvent`] of the game, after any
+    /// listeners registered before it.
+    ///
+    /// Listeners are not copied when the game is cloned.
+    pub fn subscribe<F>(&mut self, listener: F) -> ListenerId
+    where
+        F: FnMut(&GameEvent) + Send + Sync + 'static,
+    {
+        self.listeners.add(Box::new(listener))
+    }
+
+    /// Remove a listener registered with [`Game::subscribe`].
+    ///
+    /// # Returns
+    /// `true` if the listener was registered.
+    pub fn unsubscribe(&mut self, id: ListenerId) -> bool {
+        self.listeners.remove(id)
+    }
+
+    /// Get the moves made so far, in the order they were made.
+    pub fn move_history(&self) -> &[ChessMove] {
+        self.move_manager.move_history()
+    }
+
+    /// Get information about the moves made so far, in the order they were made.
+    pub fn move_info_history(&self) -> &[MoveInfo] {
+        &self.move_info_history
+    }
+
+    /// Attach a clock to the game, replacing any clock already attached, and start it for the
+    /// current player.
+    ///
+    /// The clock is not copied when the game is cloned.
+    pub fn attach_clock(&mut self, mut clock: Clock) {
+        if !self.is_over() {
+            clock.start(self.current_player);
+        }
+        self.clock = Some(clock);
+    }
+
+    /// Remove the clock from the game, stopping it.
+    pub fn detach_clock(&mut self) -> Option<Clock> {
+        let mut clock = self.clock.take()?;
+        clock.stop();
+        Some(clock)
+    }
+
+    pub fn clock(&self) -> Option<&Clock> {
+        self.clock.as_ref()
+    }
+
+    fn flag_fallen(&self) -> Option<Color> {
+        self.clock.as_ref().and_then(Clock::flag_fallen)
+    }
+
+    /// Get the number of half moves since the last capture or pawn move.
+    pub fn half_moves(&self) -> u32 {
+        self.move_manager.half_moves()
+    }
+
+    /// Get the number of the current full move, starting at 1 and incremented after each move by black.
+    pub fn full_moves(&self) -> u32 {
+        self.move_manager.full_moves()
+    }
+
     pub fn from_fen_string(fen: &str) -> Result<Self, String> {
         let fen = Fen::from_str(fen)?;
         let board = fen.board();
@@ -116,6 +505,31 @@ impl Game {
         Ok(Self::new(fen.current_player(), mm, board))
     }
 
+    /// Create a game from a position, with the en passant target of `state` belonging to
+    /// `current_player`.
+    pub(crate) fn from_position(
+        board: ChessBoard,
+        current_player: Color,
+        state: PositionState,
+    ) -> Self {
+        let (white_en_passant_target, black_en_passant_target) = match current_player {
+            Color::White => (state.en_passant_target(), None),
+            Color::Black => (None, state.en_passant_target()),
+        };
+        let mut mm = MoveManager::new(
+            vec![],
+            vec![],
+            HashSet::new(),
+            white_en_passant_target,
+            black_en_passant_target,
+            state.castling_rights(),
+            state.half_moves(),
+            state.full_moves(),
+        );
+        mm.evaluate_legal_moves(&board, current_player);
+        Self::new(current_player, mm, board)
+    }
+
     pub fn to_fen_string(&self) -> String {
         Fen::new(
             self.board(),
@@ -129,11 +543,37 @@ impl Game {
         .to_string()
     }
 
+    /// Get the FEN string of the position the game started from, before any of the moves in
+    /// [`Game::move_history`] were made.
+    pub fn start_fen_string(&self) -> String {
+        let mut game = self.clone();
+        while game.move_manager.undo_move(&mut game.board).is_some() {
+            game.current_player = game.current_player.opponent();
+        }
+        game.to_fen_string()
+    }
+
     pub fn to_pretty_string(&self) -> String {
         self.board().to_pretty_string()
     }
 }
 
+impl Clone for Game {
+    fn clone(&self) -> Self {
+        Self {
+            current_player: self.current_player,
+            move_manager: self.move_manager.clone(),
+            board: self.board,
+            move_info_history: self.move_info_history.clone(),
+            clock: None,
+            draw_offer: self.draw_offer,
+            drawn_by_agreement: self.drawn_by_agreement,
+            game_over_announced: self.game_over_announced,
+            listeners: self.listeners.clone(),
+        }
+    }
+}
+
 impl Default for Game {
     fn default() -> Self {
         let board = ChessBoard::default();
@@ -150,6 +590,17 @@ pub enum GameOver {
     Draw,
 }
 
+/// How a game ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameOverReason {
+    Checkmate,
+    Stalemate,
+    /// A player ran out of time on the clock.
+    FlagFall,
+    /// The players agreed to a draw.
+    Agreement,
+}
+
 impl GameOver {
     pub fn unwrap_winner(self) -> Color {
         match self {
@@ -250,6 +701,38 @@ mod tests {
                 king_to: G1,
             })
             .is_err());
+
+        // the king can't castle out of check
+        let game = Game::from_fen_string("4k3/4r3/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        assert!(!game.get_moves().iter().any(|m| m.is_castle()));
+
+        // an attack on b1 only concerns the rook, which may pass it
+        let game = Game::from_fen_string("1r2k3/8/8/8/8/8/8/R3K3 w Q - 0 1").unwrap();
+        assert!(game.get_moves().contains(&ChessMove::Castle {
+            rook_from: A1,
+            rook_to: D1,
+            king_from: E1,
+            king_to: C1,
+        }));
+    }
+
+    #[test]
+    fn rook_moves_and_captures_lose_castling_rights() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        let mut game = Game::from_fen_string(fen).unwrap();
+        game.make_move(regular(H1, H4)).unwrap();
+        assert!(!game.castling_rights().white_kingside());
+        assert!(game.castling_rights().white_queenside());
+
+        let mut game = Game::from_fen_string(fen).unwrap();
+        game.make_move(regular(A1, A8)).unwrap();
+        assert!(!game.castling_rights().black_queenside());
+        assert!(game.castling_rights().black_kingside());
+
+        let mut game = Game::from_fen_string(fen).unwrap();
+        game.make_move(regular(H1, H8)).unwrap();
+        assert!(!game.castling_rights().black_kingside());
+        assert!(game.castling_rights().black_queenside());
     }
 
     #[test]
@@ -389,6 +872,106 @@ mod tests {
         assert_eq!(game.game_result().unwrap(), GameOver::Draw);
     }
 
+    #[test]
+    fn parse_uci_move_test() {
+        let game = setup_promotion_game();
+        assert_eq!(
+            game.parse_uci_move("a7b8n"),
+            Ok(ChessMove::Promotion {
+                from: A7,
+                to: B8,
+                piece: PromotionPiece::Knight,
+            })
+        );
+        assert_eq!(
+            game.parse_uci_move("a7b8n").unwrap().to_uci_string(),
+            "a7b8n"
+        );
+        assert!(game.parse_uci_move("a7b8").is_err());
+
+        let game = setup_castle_game();
+        assert_eq!(
+            game.parse_uci_move("e1g1"),
+            Ok(ChessMove::Castle {
+                rook_from: H1,
+                rook_to: F1,
+                king_from: E1,
+                king_to: G1,
+            })
+        );
+        assert!(game.parse_uci_move("e2e4").is_err());
+    }
+
+    #[test]
+    fn static_exchange_evaluation_test() {
+        // undefended pawn
+        let game =
+            Game::from_fen_string("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1").unwrap();
+        assert_eq!(game.static_exchange_evaluation(regular(E1, E5)), Some(100));
+
+        // the queens on e1 and h8 join the exchange as x-ray attackers
+        let game =
+            Game::from_fen_string("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1")
+                .unwrap();
+        assert_eq!(game.static_exchange_evaluation(regular(D3, E5)), Some(-200));
+
+        // a quiet move to a safe square neither wins nor loses material
+        let game = Game::default();
+        assert_eq!(game.static_exchange_evaluation(regular(E2, E4)), Some(0));
+        assert_eq!(game.static_exchange_evaluation(regular(E2, E5)), None);
+    }
+
+    #[test]
+    fn get_tactical_moves_test() {
+        let game = Game::from_fen_string("4k3/1P6/8/3p4/4P3/8/8/4K2R w K - 0 1").unwrap();
+        let tactical_moves: HashSet<_> = game.get_tactical_moves(false).into_iter().collect();
+        let mut expected: HashSet<_> = ChessMove::promotion_moves(B7, B8).into_iter().collect();
+        expected.insert(regular(E4, D5));
+        assert_eq!(tactical_moves, expected);
+
+        let tactical_moves: HashSet<_> = game.get_tactical_moves(true).into_iter().collect();
+        expected.insert(regular(H1, H8));
+        assert_eq!(tactical_moves, expected);
+    }
+
+    #[test]
+    fn undo_move_test() {
+        let mut game = Game::from_fen_string(&setup_castle_game().to_fen_string()).unwrap();
+        let mut fens = vec![game.to_fen_string()];
+        for chess_move in [
+            ChessMove::Castle {
+                rook_from: H1,
+                rook_to: F1,
+                king_from: E1,
+                king_to: G1,
+            },
+            regular(G5, G2),
+            regular(G1, G2),
+            regular(E5, E4),
+            regular(D2, D4),
+            ChessMove::EnPassant {
+                from: E4,
+                to: D3,
+                taken_original_index: D2,
+                taken_index: D4,
+            },
+        ] {
+            game.make_move(chess_move).unwrap();
+            fens.push(game.to_fen_string());
+        }
+        assert_eq!(game.move_history().len(), 6);
+        assert_eq!(game.start_fen_string(), fens[0]);
+
+        fens.pop();
+        while let Some(fen) = fens.pop() {
+            assert!(game.undo_move().is_some());
+            assert_eq!(game.to_fen_string(), fen);
+        }
+        assert_eq!(game.undo_move(), None);
+        assert!(game.move_history().is_empty());
+        assert_eq!(game.get_moves(), setup_castle_game().get_moves());
+    }
+
     #[test]
     fn from_fen_test() {
         use ChessMove::*;
@@ -414,7 +997,7 @@ mod tests {
                 .collect(),
                 None,
                 None,
-                CastlingRights::default(),
+                CastlingRights::new(false, false, false, false),
                 99,
                 50
             )
@@ -563,6 +1146,28 @@ mod tests {
         // assert!(false);
     }
 
+    #[test]
+    fn clone_of_flagged_game() {
+        use crate::clock::{Clock, ManualTimeSource, TimeControl};
+        use std::time::Duration;
+
+        let time = ManualTimeSource::new();
+        let mut game = Game::default();
+        game.attach_clock(Clock::new(
+            TimeControl::sudden_death(Duration::from_secs(60)),
+            time.clone(),
+        ));
+        game.make_move(regular(E2, E4)).unwrap();
+        time.advance(Duration::from_secs(61));
+        assert!(game.is_over());
+
+        let mut clone = game.clone();
+        assert!(clone.clock().is_none());
+        assert!(clone.make_move(regular(E7, E5)).is_ok());
+        assert_eq!(clone.undo_move(), Some(regular(E7, E5)));
+        assert_eq!(clone.undo_move(), Some(regular(E2, E4)));
+    }
+
     /// Set up a game where en passant is possible
     fn setup_game_1() -> Game {
         let mut game = Game::default();
@@ -0,0 +1,438 @@
+use crate::{
+    chess_board::ChessBoard,
+    chess_move::{ChessMove, PromotionPiece},
+    game::Game,
+    piece::{Piece, PieceType},
+    tactics::{pins, PinKind},
+    Color,
+};
+use bitboard::*;
+use std::fmt::Display;
+
+/// The reason a proposed move is illegal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IllegalMove {
+    GameOver,
+    NoPiece,
+    OpponentPiece,
+    OwnPieceOnTarget,
+    /// The piece doesn't move in that direction or that far.
+    CannotMoveThatWay,
+    PathBlocked {
+        blocker: Position,
+    },
+    /// A pawn reaching the last rank didn't promote, or a move that isn't a pawn reaching the
+    /// last rank did.
+    InvalidPromotion,
+    /// The piece is pinned to its king.
+    Pinned {
+        pinner: Position,
+    },
+    /// The king is in check, and the move doesn't resolve it.
+    InCheck {
+        checkers: Bitboard,
+    },
+    /// The king would move onto an attacked square.
+    IntoCheck {
+        attackers: Bitboard,
+    },
+    /// The move would expose the king to check without a pinned piece moving, which happens
+    /// when an en passant capture clears a rank.
+    ExposesKing {
+        attackers: Bitboard,
+    },
+    NoCastlingRights,
+    CastlingThroughCheck {
+        square: Position,
+    },
+}
+
+impl Display for IllegalMove {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IllegalMove::GameOver => write!(f, "the game is over"),
+            IllegalMove::NoPiece => write!(f, "there is no piece on that square"),
+            IllegalMove::OpponentPiece => write!(f, "that piece belongs to the opponent"),
+            IllegalMove::OwnPieceOnTarget => {
+                write!(f, "the target square is occupied by your own piece")
+            }
+            IllegalMove::CannotMoveThatWay => write!(f, "the piece can't move that way"),
+            IllegalMove::PathBlocked { blocker } => {
+                write!(f, "the path is blocked by the piece on {}", blocker)
+            }
+            IllegalMove::InvalidPromotion => write!(
+                f,
+                "a pawn must promote when it reaches the last rank, and only then"
+            ),
+            IllegalMove::Pinned { pinner } => {
+                write!(
+                    f,
+                    "the piece is pinned to the king by the piece on {}",
+                    pinner
+                )
+            }
+            IllegalMove::InCheck { .. } => {
+                write!(f, "the king is in check, and the move doesn't resolve it")
+            }
+            IllegalMove::IntoCheck { .. } => write!(f, "the king would move into check"),
+            IllegalMove::ExposesKing { .. } => {
+                write!(f, "the move would expose the king to check")
+            }
+            IllegalMove::NoCastlingRights => write!(
+                f,
+                "castling is no longer allowed, since the king or rook has moved"
+            ),
+            IllegalMove::CastlingThroughCheck { square } => {
+                write!(
+                    f,
+                    "the king can't castle through the attacked square {}",
+                    square
+                )
+            }
+        }
+    }
+}
+
+/// Find the legal move of the current player from `from` to `to`, or the reason there is none.
+pub(crate) fn explain_move(
+    game: &Game,
+    from: Position,
+    to: Position,
+    promotion: Option<PromotionPiece>,
+) -> Result<ChessMove, IllegalMove> {
+    let board = game.board();
+    let player = game.current_player();
+    if game.is_over() {
+        return Err(IllegalMove::GameOver);
+    }
+    let piece = board.get_piece(from).ok_or(IllegalMove::NoPiece)?;
+    if piece.color() != player {
+        return Err(IllegalMove::OpponentPiece);
+    }
+
+    let candidates: Vec<ChessMove> = game
+        .move_manager()
+        .evaluate_legal_moves_from(&board, from, player)
+        .into_iter()
+        .filter(|chess_move| chess_move.to() == to)
+        .collect();
+    if candidates.is_empty() {
+        return Err(explain_movement(game, &board, piece, from, to));
+    }
+    let chess_move = candidates
+        .into_iter()
+        .find(|&chess_move| match chess_move {
+            ChessMove::Promotion { piece, .. } => Some(piece) == promotion,
+            _ => promotion.is_none(),
+        })
+        .ok_or(IllegalMove::InvalidPromotion)?;
+
+    if game.get_moves().contains(&chess_move) {
+        Ok(chess_move)
+    } else {
+        Err(explain_check(game, &board, piece, chess_move))
+    }
+}
+
+/// Explain why the piece on `from` can't reach `to`, ignoring whether its king is in check.
+fn explain_movement(
+    game: &Game,
+    board: &ChessBoard,
+    piece: Piece,
+    from: Position,
+    to: Position,
+) -> IllegalMove {
+    if board.has_piece_of_color_at(piece.color(), to) {
+        return IllegalMove::OwnPieceOnTarget;
+    }
+
+    let lines: &[bool] = match piece.kind() {
+        PieceType::Pawn => return explain_pawn_movement(board, piece.color(), from, to),
+        PieceType::King => return explain_castle(game, board, piece.color(), from, to),
+        PieceType::Knight => &[],
+        PieceType::Bishop => &[true],
+        PieceType::Rook => &[false],
+        PieceType::Queen => &[true, false],
+    };
+    for &diagonal in lines {
+        if line_targets(from, Bitboard::with_one(to), diagonal) & to != 0 {
+            let between = line_targets(from, Bitboard::with_one(to), diagonal)
+                & line_targets(to, Bitboard::with_one(from), diagonal);
+            // the first piece in the way is the one the piece itself runs into
+            let blocker = line_targets(from, board.full_occupancy(), diagonal)
+                & between
+                & board.full_occupancy();
+            if let Some(blocker) = blocker.first_position() {
+                return IllegalMove::PathBlocked { blocker };
+            }
+        }
+    }
+    IllegalMove::CannotMoveThatWay
+}
+
+fn explain_pawn_movement(
+    board: &ChessBoard,
+    color: Color,
+    from: Position,
+    to: Position,
+) -> IllegalMove {
+    let (forward, start_rank) = match color {
+        Color::White => (from.up(), Rank::Two),
+        Color::Black => (from.down(), Rank::Seven),
+    };
+    let forward = match forward {
+        Some(forward) => forward,
+        None => return IllegalMove::CannotMoveThatWay,
+    };
+    let double_forward = match color {
+        Color::White => forward.up(),
+        Color::Black => forward.down(),
+    };
+
+    if to == forward {
+        IllegalMove::PathBlocked { blocker: to }
+    } else if from.rank() == start_rank && Some(to) == double_forward {
+        let blocker = if board.has_piece_at(forward) {
+            forward
+        } else {
+            to
+        };
+        IllegalMove::PathBlocked { blocker }
+    } else {
+        // including diagonal moves without anything to capture
+        IllegalMove::CannotMoveThatWay
+    }
+}
+
+fn explain_castle(
+    game: &Game,
+    board: &ChessBoard,
+    color: Color,
+    from: Position,
+    to: Position,
+) -> IllegalMove {
+    let rights = game.castling_rights();
+    let (king_from, kingside, queenside, kingside_right, queenside_right) = match color {
+        Color::White => (
+            E1,
+            [F1, G1],
+            [D1, C1, B1],
+            rights.white_kingside(),
+            rights.white_queenside(),
+        ),
+        Color::Black => (
+            E8,
+            [F8, G8],
+            [D8, C8, B8],
+            rights.black_kingside(),
+            rights.black_queenside(),
+        ),
+    };
+    let (squares, has_right): (&[Position], bool) = if from == king_from && to == kingside[1] {
+        (&kingside, kingside_right)
+    } else if from == king_from && to == queenside[1] {
+        (&queenside, queenside_right)
+    } else {
+        return IllegalMove::CannotMoveThatWay;
+    };
+
+    if !has_right {
+        return IllegalMove::NoCastlingRights;
+    }
+    let checkers = board.attackers_of(king_from, color.opponent());
+    if checkers != Bitboard::empty() {
+        return IllegalMove::InCheck { checkers };
+    }
+    if let Some(&blocker) = squares.iter().find(|&&square| board.has_piece_at(square)) {
+        return IllegalMove::PathBlocked { blocker };
+    }
+    // the king passes the first two squares, while the third only has to be empty for the rook
+    for &square in &squares[..2] {
+        let attackers = board.attackers_of(square, color.opponent());
+        if attackers == Bitboard::empty() {
+            continue;
+        }
+        return if square == to {
+            IllegalMove::IntoCheck { attackers }
+        } else {
+            IllegalMove::CastlingThroughCheck { square }
+        };
+    }
+    IllegalMove::CannotMoveThatWay
+}
+
+/// Explain why a move that the piece can make leaves its king in check.
+fn explain_check(
+    game: &Game,
+    board: &ChessBoard,
+    piece: Piece,
+    chess_move: ChessMove,
+) -> IllegalMove {
+    let player = piece.color();
+    let mut after_move = *board;
+    game.move_manager()
+        .dry_run_move(&mut after_move, player, chess_move);
+    let king = after_move
+        .get_bitboard(player, PieceType::King)
+        .first_position()
+        .unwrap();
+    let attackers = after_move.attackers_of(king, player.opponent());
+    if piece.kind() == PieceType::King {
+        return IllegalMove::IntoCheck { attackers };
+    }
+
+    let checkers = board.attackers_of(king, player.opponent());
+    if checkers != Bitboard::empty() {
+        return IllegalMove::InCheck { checkers };
+    }
+    pins(board, player)
+        .into_iter()
+        .find(|pin| pin.pinned() == chess_move.from() && pin.kind() == PinKind::Absolute)
+        .map(|pin| IllegalMove::Pinned {
+            pinner: pin.pinner(),
+        })
+        .unwrap_or(IllegalMove::ExposesKing { attackers })
+}
+
+fn line_targets(pos: Position, occupancy: Bitboard, diagonal: bool) -> Bitboard {
+    if diagonal {
+        Bitboard::bishop_targets(pos, occupancy)
+    } else {
+        Bitboard::rook_targets(pos, occupancy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn explain(fen: &str, from: Position, to: Position) -> Result<ChessMove, IllegalMove> {
+        Game::from_fen_string(fen)
+            .unwrap()
+            .explain_move(from, to, None)
+    }
+
+    #[test]
+    fn movement() {
+        let game = Game::default();
+        assert_eq!(
+            game.explain_move(E2, E4, None),
+            Ok(ChessMove::Regular { from: E2, to: E4 })
+        );
+        assert_eq!(game.explain_move(E3, E4, None), Err(IllegalMove::NoPiece));
+        assert_eq!(
+            game.explain_move(E7, E5, None),
+            Err(IllegalMove::OpponentPiece)
+        );
+        assert_eq!(
+            game.explain_move(E2, E5, None),
+            Err(IllegalMove::CannotMoveThatWay)
+        );
+        assert_eq!(
+            game.explain_move(E2, D3, None),
+            Err(IllegalMove::CannotMoveThatWay)
+        );
+        assert_eq!(
+            game.explain_move(A1, A2, None),
+            Err(IllegalMove::OwnPieceOnTarget)
+        );
+        assert_eq!(
+            game.explain_move(D1, D3, None),
+            Err(IllegalMove::PathBlocked { blocker: D2 })
+        );
+        assert_eq!(
+            game.explain_move(F1, C4, None),
+            Err(IllegalMove::PathBlocked { blocker: E2 })
+        );
+        assert_eq!(
+            game.explain_move(G1, E2, None),
+            Err(IllegalMove::OwnPieceOnTarget)
+        );
+        assert_eq!(
+            game.explain_move(G1, G3, None),
+            Err(IllegalMove::CannotMoveThatWay)
+        );
+    }
+
+    #[test]
+    fn promotion() {
+        let game = Game::from_fen_string("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            game.explain_move(A7, A8, None),
+            Err(IllegalMove::InvalidPromotion)
+        );
+        assert_eq!(
+            game.explain_move(A7, A8, Some(PromotionPiece::Queen)),
+            Ok(ChessMove::Promotion {
+                from: A7,
+                to: A8,
+                piece: PromotionPiece::Queen
+            })
+        );
+        assert_eq!(
+            game.explain_move(E1, E2, Some(PromotionPiece::Queen)),
+            Err(IllegalMove::InvalidPromotion)
+        );
+    }
+
+    #[test]
+    fn king_safety() {
+        let pinned = "4k3/4r3/8/8/8/8/4N3/4K3 w - - 0 1";
+        assert_eq!(
+            explain(pinned, E2, C3),
+            Err(IllegalMove::Pinned { pinner: E7 })
+        );
+
+        let in_check = "4k3/8/8/8/8/8/3N4/r3K3 w - - 0 1";
+        assert_eq!(
+            explain(in_check, D2, F3),
+            Err(IllegalMove::InCheck {
+                checkers: Bitboard::with_one(A1)
+            })
+        );
+        assert!(explain(in_check, D2, B1).is_ok());
+        assert_eq!(
+            explain(in_check, E1, D1),
+            Err(IllegalMove::IntoCheck {
+                attackers: Bitboard::with_one(A1)
+            })
+        );
+    }
+
+    #[test]
+    fn castling() {
+        assert_eq!(
+            explain("4kr2/8/8/8/8/8/8/4K2R w K - 0 1", E1, G1),
+            Err(IllegalMove::CastlingThroughCheck { square: F1 })
+        );
+        assert_eq!(
+            explain("4k3/8/8/8/8/8/8/4K2R w - - 0 1", E1, G1),
+            Err(IllegalMove::NoCastlingRights)
+        );
+        assert_eq!(
+            explain("4k3/8/8/8/8/8/8/4KB1R w K - 0 1", E1, G1),
+            Err(IllegalMove::PathBlocked { blocker: F1 })
+        );
+        assert_eq!(
+            explain("4k3/4r3/8/8/8/8/8/4K2R w K - 0 1", E1, G1),
+            Err(IllegalMove::InCheck {
+                checkers: Bitboard::with_one(E7)
+            })
+        );
+        assert!(explain("4k3/8/8/8/8/8/8/4K2R w K - 0 1", E1, G1)
+            .unwrap()
+            .is_castle());
+        // the rook may pass an attacked square
+        assert!(explain("1r2k3/8/8/8/8/8/8/R3K3 w Q - 0 1", E1, C1)
+            .unwrap()
+            .is_castle());
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            IllegalMove::Pinned { pinner: E7 }.to_string(),
+            "the piece is pinned to the king by the piece on e7"
+        );
+    }
+}
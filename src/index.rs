@@ -0,0 +1,266 @@
+//! An index of the positions reached in a collection of games.
+//!
+//! Every position of every game is recorded by its [`Game::position_hash`], so a query finds the
+//! games that reached a position however they got there. Games are identified by the order they
+//! were added in, starting from 0, which matches their order in the PGN file or game database
+//! they were read from.
+//!
+//! An index is saved as the magic bytes `CPIX`, a version byte, the number of games as a little
+//! endian `u32`, the number of entries as a little endian `u64`, and then the entries sorted by
+//! hash. Each entry holds the hash as a `u64`, the game id as a `u32` and the ply as a `u16`,
+//! all little endian.
+
+use crate::{database::GameRecord, game::Game};
+use std::{
+    collections::HashMap,
+    convert::{TryFrom, TryInto},
+    fs,
+    path::Path,
+};
+
+const MAGIC: [u8; 4] = *b"CPIX";
+const VERSION: u8 = 1;
+
+const HEADER_SIZE: usize = 17;
+const ENTRY_SIZE: usize = 14;
+
+/// A game that reached a position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PositionHit {
+    game_id: u32,
+    ply: u16,
+}
+
+impl PositionHit {
+    pub(crate) fn new(game_id: u32, ply: u16) -> Self {
+        Self { game_id, ply }
+    }
+
+    pub fn game_id(&self) -> u32 {
+        self.game_id
+    }
+
+    /// The number of moves made in the game before the position was reached, so the starting
+    /// position is at ply 0.
+    pub fn ply(&self) -> u16 {
+        self.ply
+    }
+}
+
+/// An index of the positions reached in a collection of games.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PositionIndex {
+    game_count: u32,
+    positions: HashMap<u64, Vec<PositionHit>>,
+}
+
+impl PositionIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of games added to the index.
+    pub fn game_count(&self) -> u32 {
+        self.game_count
+    }
+
+    /// The number of distinct positions in the index.
+    pub fn position_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Add every position of `game`, from its starting position to its current one.
+    ///
+    /// # Returns
+    /// The id of the game.
+    pub fn add_game(&mut self, game: &Game) -> u32 {
+        let game_id = self.game_count;
+        self.game_count += 1;
+
+        let mut replay = game.clone();
+        while replay.undo_move().is_some() {}
+        let moves = game.move_history();
+        for ply in 0..=moves.len().min(u16::MAX as usize) {
+            if ply > 0 {
+                replay.make_move(moves[ply - 1]).unwrap();
+            }
+            self.positions
+                .entry(replay.position_hash())
+                .or_default()
+                .push(PositionHit {
+                    game_id,
+                    ply: ply as u16,
+                });
+        }
+        game_id
+    }
+
+    /// Add the games read from a PGN file or a game database, in order.
+    ///
+    /// A game that can't be read still takes up an id, so that ids keep matching the order of
+    /// the games in the source.
+    ///
+    /// # Returns
+    /// The ids of the games that couldn't be read, with the reason.
+    pub fn add_records<I>(&mut self, records: I) -> Vec<(u32, String)>
+    where
+        I: IntoIterator<Item = Result<GameRecord, String>>,
+    {
+        let mut errors = Vec::new();
+        for record in records {
+            match record {
+                Ok(record) => {
+                    self.add_game(record.game());
+                }
+                Err(e) => {
+                    errors.push((self.game_count, e));
+                    self.game_count += 1;
+                }
+            }
+        }
+        errors
+    }
+
+    /// Find the games that reached the current position of `game`, in the order they were added.
+    pub fn search(&self, game: &Game) -> &[PositionHit] {
+        self.search_hash(game.position_hash())
+    }
+
+    /// Find the games that reached the position with the given [`Game::position_hash`].
+    pub fn search_hash(&self, hash: u64) -> &[PositionHit] {
+        self.positions.get(&hash).map_or(&[], Vec::as_slice)
+    }
+
+    /// Save the index to a file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        fs::write(path, self.to_bytes()).map_err(|e| e.to_string())
+    }
+
+    /// Load an index saved by [`PositionIndex::save`].
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let bytes = fs::read(path).map_err(|e| e.to_string())?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Encode the index as a header followed by its entries.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut hashes: Vec<u64> = self.positions.keys().copied().collect();
+        hashes.sort_unstable();
+        let entry_count: usize = self.positions.values().map(Vec::len).sum();
+
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + entry_count * ENTRY_SIZE);
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&self.game_count.to_le_bytes());
+        bytes.extend_from_slice(&(entry_count as u64).to_le_bytes());
+        for hash in hashes {
+            for hit in &self.positions[&hash] {
+                bytes.extend_from_slice(&hash.to_le_bytes());
+                bytes.extend_from_slice(&hit.game_id.to_le_bytes());
+                bytes.extend_from_slice(&hit.ply.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// Decode an index encoded by [`PositionIndex::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < HEADER_SIZE || bytes[..4] != MAGIC {
+            return Err("not a position index".to_string());
+        }
+        if bytes[4] != VERSION {
+            return Err(format!("unsupported position index version {}", bytes[4]));
+        }
+        let game_count = u32::from_le_bytes(bytes[5..9].try_into().unwrap());
+        let entry_count = u64::from_le_bytes(bytes[9..HEADER_SIZE].try_into().unwrap());
+        let entries = &bytes[HEADER_SIZE..];
+        if usize::try_from(entry_count)
+            .ok()
+            .and_then(|count| count.checked_mul(ENTRY_SIZE))
+            != Some(entries.len())
+        {
+            return Err("truncated position index".to_string());
+        }
+
+        let mut positions: HashMap<u64, Vec<PositionHit>> = HashMap::new();
+        for entry in entries.chunks_exact(ENTRY_SIZE) {
+            let hash = u64::from_le_bytes(entry[..8].try_into().unwrap());
+            let game_id = u32::from_le_bytes(entry[8..12].try_into().unwrap());
+            let ply = u16::from_le_bytes(entry[12..].try_into().unwrap());
+            if game_id >= game_count {
+                return Err(format!("invalid game id {} in position index", game_id));
+            }
+            positions
+                .entry(hash)
+                .or_default()
+                .push(PositionHit { game_id, ply });
+        }
+        Ok(Self {
+            game_count,
+            positions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn::PgnReader;
+
+    const PGN: &str = r#"[Round "1"]
+1. Nf3 Nf6 2. Nc3 *
+
+[Round "2"]
+1. Nc3 Nf6 2. Nf3 d5 *
+
+[Round "3"]
+1. e4 ?? *
+
+[Round "4"]
+1. e4 e5 *
+"#;
+
+    fn position(moves: &[&str]) -> Game {
+        let mut game = Game::default();
+        for uci in moves {
+            game.make_move(game.parse_uci_move(uci).unwrap()).unwrap();
+        }
+        game
+    }
+
+    fn index() -> PositionIndex {
+        let mut index = PositionIndex::new();
+        let errors = index.add_records(PgnReader::new(PGN.as_bytes()));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, 2);
+        index
+    }
+
+    #[test]
+    fn search() {
+        let index = index();
+        assert_eq!(index.game_count(), 4);
+
+        let hits = index.search(&position(&["g1f3", "g8f6", "b1c3"]));
+        let hits: Vec<(u32, u16)> = hits.iter().map(|hit| (hit.game_id(), hit.ply())).collect();
+        assert_eq!(hits, vec![(0, 3), (1, 3)]);
+
+        let start = index.search(&Game::default());
+        assert_eq!(start.len(), 3);
+        assert!(start.iter().all(|hit| hit.ply() == 0));
+        assert_eq!(index.search(&position(&["e2e4", "e7e5"]))[0].game_id(), 3);
+        assert!(index.search(&position(&["d2d4"])).is_empty());
+    }
+
+    #[test]
+    fn persistence() {
+        let index = index();
+        let bytes = index.to_bytes();
+        let loaded = PositionIndex::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded, index);
+        assert_eq!(loaded.position_count(), index.position_count());
+
+        assert!(PositionIndex::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(PositionIndex::from_bytes(b"CEGT").is_err());
+    }
+}
@@ -8,12 +8,42 @@ use std::{
 
 mod chess_board;
 mod chess_move;
+mod clock;
+mod database;
+mod eco;
+mod endgame;
+mod events;
+mod explorer;
 mod game;
+mod illegal_move;
+mod index;
+mod move_info;
+mod packed;
+mod pgn;
 mod piece;
 mod fen;
 pub mod prelude;
+mod problem;
+mod puzzle;
+mod query;
+mod retro;
+mod san;
+mod search;
+#[cfg(feature = "serde")]
+mod serialization;
+mod syzygy;
+mod tactics;
+mod uci;
+mod uci_client;
+mod xboard;
+mod zobrist;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
 pub enum Color {
     Black,
     White,
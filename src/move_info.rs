@@ -0,0 +1,417 @@
+use crate::{
+    chess_move::{CastlingRights, ChessMove, PromotionPiece},
+    game::Game,
+    piece::{Piece, PieceType},
+    Color,
+};
+use bitboard::*;
+
+/// Whether a move gives check, and how.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckKind {
+    Check,
+    /// The king is attacked by two pieces at once, so only a king move can answer it.
+    DoubleCheck,
+    Checkmate,
+}
+
+/// The castling rights, en passant target and clocks of a position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PositionState {
+    castling_rights: CastlingRights,
+    en_passant_target: Option<Position>,
+    half_moves: u32,
+    full_moves: u32,
+}
+
+impl PositionState {
+    pub(crate) fn new(
+        castling_rights: CastlingRights,
+        en_passant_target: Option<Position>,
+        half_moves: u32,
+        full_moves: u32,
+    ) -> Self {
+        Self {
+            castling_rights,
+            en_passant_target,
+            half_moves,
+            full_moves,
+        }
+    }
+
+    pub(crate) fn of(game: &Game) -> Self {
+        Self {
+            castling_rights: game.castling_rights(),
+            en_passant_target: game.en_passant_target(),
+            half_moves: game.half_moves(),
+            full_moves: game.full_moves(),
+        }
+    }
+
+    pub fn castling_rights(&self) -> CastlingRights {
+        self.castling_rights
+    }
+
+    /// The square the player to move can capture en passant on, if any.
+    pub fn en_passant_target(&self) -> Option<Position> {
+        self.en_passant_target
+    }
+
+    /// The number of half moves since the last capture or pawn move.
+    pub fn half_moves(&self) -> u32 {
+        self.half_moves
+    }
+
+    pub fn full_moves(&self) -> u32 {
+        self.full_moves
+    }
+}
+
+/// A change to a single square made by a move.
+///
+/// The changes of a move are listed in the order they happen: a piece is lifted from its square,
+/// any captured piece is removed, the piece is placed on its target square, and a promoted pawn
+/// is then transformed. A castling move lifts and places the king, then the rook.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SquareChange {
+    Lifted {
+        square: Position,
+        piece: Piece,
+    },
+    Placed {
+        square: Position,
+        piece: Piece,
+    },
+    /// A captured piece is taken off the board.
+    Removed {
+        square: Position,
+        piece: Piece,
+    },
+    /// A pawn placed on the last rank is replaced by the piece it promotes to.
+    Promoted {
+        square: Position,
+        from: Piece,
+        to: Piece,
+    },
+}
+
+impl SquareChange {
+    pub fn square(&self) -> Position {
+        match *self {
+            SquareChange::Lifted { square, .. }
+            | SquareChange::Placed { square, .. }
+            | SquareChange::Removed { square, .. }
+            | SquareChange::Promoted { square, .. } => square,
+        }
+    }
+}
+
+/// The square a piece captured by `chess_move` is on, which is the target square of the move
+/// except for en passant.
+pub(crate) fn captured_square(chess_move: ChessMove) -> Position {
+    match chess_move {
+        ChessMove::EnPassant { taken_index, .. } => taken_index,
+        _ => chess_move.to(),
+    }
+}
+
+/// List the square changes of `chess_move`, made by `piece`, capturing `captured` on its square.
+pub(crate) fn square_changes(
+    chess_move: ChessMove,
+    piece: Piece,
+    captured: Option<(Piece, Position)>,
+) -> Vec<SquareChange> {
+    let mut changes = Vec::with_capacity(4);
+    changes.push(SquareChange::Lifted {
+        square: chess_move.from(),
+        piece,
+    });
+    if let Some((piece, square)) = captured {
+        changes.push(SquareChange::Removed { square, piece });
+    }
+    changes.push(SquareChange::Placed {
+        square: chess_move.to(),
+        piece,
+    });
+    match chess_move {
+        ChessMove::Promotion {
+            to,
+            piece: promotion,
+            ..
+        } => {
+            changes.push(SquareChange::Promoted {
+                square: to,
+                from: piece,
+                to: promotion.create_piece(piece.color()),
+            });
+        }
+        ChessMove::Castle {
+            rook_from, rook_to, ..
+        } => {
+            let rook = Piece::rook(piece.color());
+            changes.push(SquareChange::Lifted {
+                square: rook_from,
+                piece: rook,
+            });
+            changes.push(SquareChange::Placed {
+                square: rook_to,
+                piece: rook,
+            });
+        }
+        _ => {}
+    }
+    changes
+}
+
+/// Everything about a move that was made, as returned by [`Game::make_move`] and kept in
+/// [`Game::move_info_history`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MoveInfo {
+    chess_move: ChessMove,
+    piece: Piece,
+    captured: Option<(Piece, Position)>,
+    checkers: Bitboard,
+    check: Option<CheckKind>,
+    before: PositionState,
+    after: PositionState,
+}
+
+impl MoveInfo {
+    /// Collect the information about `chess_move`, which has just been made in `game`.
+    ///
+    /// A check is never reported as [`CheckKind::Checkmate`] here, since the legal moves of
+    /// `game` may not have been evaluated yet. See [`MoveInfo::update_checkmate`].
+    pub(crate) fn new(
+        game: &Game,
+        chess_move: ChessMove,
+        piece: Piece,
+        captured: Option<Piece>,
+        before: PositionState,
+    ) -> Self {
+        let board = game.board();
+        let player = game.current_player();
+        let checkers = board
+            .get_bitboard(player, PieceType::King)
+            .first_position()
+            .map(|king| board.attackers_of(king, player.opponent()))
+            .unwrap_or_else(Bitboard::empty);
+        let check = match checkers.positions().len() {
+            0 => None,
+            1 => Some(CheckKind::Check),
+            _ => Some(CheckKind::DoubleCheck),
+        };
+        Self {
+            chess_move,
+            piece,
+            captured: captured.map(|captured| (captured, captured_square(chess_move))),
+            checkers,
+            check,
+            before,
+            after: PositionState::of(game),
+        }
+    }
+
+    /// Mark the check as checkmate if `game`, with its legal moves evaluated, is over.
+    pub(crate) fn update_checkmate(&mut self, game: &Game) {
+        if self.check.is_some() && game.is_over() {
+            self.check = Some(CheckKind::Checkmate);
+        }
+    }
+
+    pub fn chess_move(&self) -> ChessMove {
+        self.chess_move
+    }
+
+    /// The piece that moved, which is the pawn for a promotion and the king for castling.
+    pub fn piece(&self) -> Piece {
+        self.piece
+    }
+
+    pub fn player(&self) -> Color {
+        self.piece.color()
+    }
+
+    pub fn captured(&self) -> Option<Piece> {
+        self.captured.map(|(piece, _)| piece)
+    }
+
+    /// The square the captured piece was on, which differs from the target square of the move
+    /// for en passant.
+    pub fn captured_square(&self) -> Option<Position> {
+        self.captured.map(|(_, square)| square)
+    }
+
+    pub fn is_capture(&self) -> bool {
+        self.captured.is_some()
+    }
+
+    pub fn promotion(&self) -> Option<PromotionPiece> {
+        match self.chess_move {
+            ChessMove::Promotion { piece, .. } => Some(piece),
+            _ => None,
+        }
+    }
+
+    pub fn is_castle(&self) -> bool {
+        self.chess_move.is_castle()
+    }
+
+    /// The pieces giving check after the move.
+    pub fn checkers(&self) -> Bitboard {
+        self.checkers
+    }
+
+    /// # Returns
+    /// * `Some` with the kind of check if the move gives check.
+    /// * `None` if it doesn't.
+    pub fn check(&self) -> Option<CheckKind> {
+        self.check
+    }
+
+    /// The changes the move made to the squares of the board. See [`SquareChange`].
+    pub fn square_changes(&self) -> Vec<SquareChange> {
+        square_changes(self.chess_move, self.piece, self.captured)
+    }
+
+    /// The state of the position before the move.
+    pub fn before(&self) -> PositionState {
+        self.before
+    }
+
+    /// The state of the position after the move.
+    pub fn after(&self) -> PositionState {
+        self.after
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(game: &mut Game, uci: &str) -> MoveInfo {
+        let chess_move = game.parse_uci_move(uci).unwrap();
+        game.make_move(chess_move).unwrap()
+    }
+
+    #[test]
+    fn capture_and_clocks() {
+        let mut game = Game::default();
+        let info = play(&mut game, "e2e4");
+        assert_eq!(info.piece(), Piece::pawn(Color::White));
+        assert_eq!(info.captured(), None);
+        assert_eq!(info.check(), None);
+        assert_eq!(info.before().en_passant_target(), None);
+        assert_eq!(info.after().full_moves(), 1);
+
+        play(&mut game, "d7d5");
+        let info = play(&mut game, "e4d5");
+        assert_eq!(info.captured(), Some(Piece::pawn(Color::Black)));
+        assert_eq!(info.captured_square(), Some(D5));
+        assert_eq!(info.before().full_moves(), 2);
+        assert_eq!(info.after().half_moves(), 0);
+
+        play(&mut game, "g8f6");
+        let info = play(&mut game, "g1f3");
+        assert_eq!(info.before().half_moves(), 1);
+        assert_eq!(info.after().half_moves(), 2);
+        assert_eq!(game.move_info_history().len(), 5);
+        assert_eq!(game.move_info_history()[4], info);
+
+        game.undo_move();
+        assert_eq!(game.move_info_history().len(), 4);
+    }
+
+    #[test]
+    fn en_passant_and_castling() {
+        let mut game =
+            Game::from_fen_string("r3k2r/pppp1ppp/8/8/4p3/8/PPPPPPPP/R3K2R w KQkq - 0 1").unwrap();
+        play(&mut game, "d2d4");
+        let info = play(&mut game, "e4d3");
+        assert!(info.chess_move().is_en_passant());
+        assert_eq!(info.captured_square(), Some(D4));
+
+        let info = play(&mut game, "e1g1");
+        assert!(info.is_castle());
+        assert_eq!(info.piece(), Piece::king(Color::White));
+        assert!(info.before().castling_rights().white_queenside());
+        assert!(!info.after().castling_rights().white_queenside());
+        assert!(!info.after().castling_rights().white_kingside());
+        assert!(info.after().castling_rights().black_kingside());
+    }
+
+    #[test]
+    fn square_changes_of_moves() {
+        let mut game = Game::from_fen_string("r3k3/1P1p4/8/4P3/8/8/8/4K2R b K - 0 1").unwrap();
+        play(&mut game, "d7d5");
+        let white_pawn = Piece::pawn(Color::White);
+        assert_eq!(
+            game.square_changes(game.parse_uci_move("e5d6").unwrap()),
+            Ok(vec![
+                SquareChange::Lifted {
+                    square: E5,
+                    piece: white_pawn
+                },
+                SquareChange::Removed {
+                    square: D5,
+                    piece: Piece::pawn(Color::Black)
+                },
+                SquareChange::Placed {
+                    square: D6,
+                    piece: white_pawn
+                },
+            ])
+        );
+        assert_eq!(
+            game.square_changes(game.parse_uci_move("b7a8n").unwrap()),
+            Ok(vec![
+                SquareChange::Lifted {
+                    square: B7,
+                    piece: white_pawn
+                },
+                SquareChange::Removed {
+                    square: A8,
+                    piece: Piece::rook(Color::Black)
+                },
+                SquareChange::Placed {
+                    square: A8,
+                    piece: white_pawn
+                },
+                SquareChange::Promoted {
+                    square: A8,
+                    from: white_pawn,
+                    to: Piece::knight(Color::White)
+                },
+            ])
+        );
+
+        let castle = game.parse_uci_move("e1g1").unwrap();
+        let changes = game.square_changes(castle).unwrap();
+        let squares: Vec<Position> = changes.iter().map(|change| change.square()).collect();
+        assert_eq!(squares, vec![E1, G1, H1, F1]);
+        assert_eq!(play(&mut game, "e1g1").square_changes(), changes);
+
+        assert!(game
+            .square_changes(ChessMove::Regular { from: E1, to: E2 })
+            .is_err());
+    }
+
+    #[test]
+    fn checks() {
+        let mut game = Game::from_fen_string("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+        let info = play(&mut game, "d1a4");
+        assert_eq!(info.check(), Some(CheckKind::Check));
+        assert_eq!(info.checkers(), Bitboard::with_one(A4));
+
+        let mut game = Game::from_fen_string("4k3/8/8/8/4N3/8/8/4RK2 w - - 0 1").unwrap();
+        let info = play(&mut game, "e4d6");
+        assert_eq!(info.check(), Some(CheckKind::DoubleCheck));
+
+        let mut game = Game::from_fen_string("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let info = play(&mut game, "a1a8");
+        assert_eq!(info.check(), Some(CheckKind::Checkmate));
+        assert_eq!(
+            game.move_info_history()[0].check(),
+            Some(CheckKind::Checkmate)
+        );
+    }
+}
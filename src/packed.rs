@@ -0,0 +1,351 @@
+//! A compact binary encoding of positions, for storing large numbers of them.
+//!
+//! A [`PackedPosition`] takes [`PackedPosition::SIZE`] bytes, laid out as follows:
+//!
+//! - bytes 0 to 7: the occupancy bitboard, little endian, with bit 0 for a1 and bit 63 for h8
+//! - bytes 8 to 23: a 4-bit code for each occupied square in the order of the occupancy bits,
+//!   two to a byte with the low nibble first, followed by zero nibbles
+//! - byte 24: bit 0 is set when black is to move, bits 1 to 4 hold the castling rights in the
+//!   order `KQkq`
+//! - byte 25: the en passant target of the player to move from a1 = 0 to h8 = 63, or 255 for none
+//! - bytes 26 and 27: the half move clock, little endian
+//! - bytes 28 to 31: the full move number, little endian
+//!
+//! A piece code holds the piece type from 1 for a pawn to 6 for a king, plus 8 for black pieces.
+//!
+//! Since every position has the same size, files of packed positions can be read and written in
+//! bulk with [`read_packed_positions`] and [`write_packed_positions`], and indexed directly.
+
+use crate::{
+    chess_board::ChessBoard, chess_move::CastlingRights, game::Game, move_info::PositionState,
+    piece::PieceType, Color, Piece,
+};
+use bitboard::INCREASING_A1_B1;
+use std::{
+    convert::{TryFrom, TryInto},
+    io::{Read, Write},
+};
+
+const PIECES: usize = 8;
+const FLAGS: usize = 24;
+const EN_PASSANT: usize = 25;
+const HALF_MOVES: usize = 26;
+const FULL_MOVES: usize = 28;
+
+const MAX_PIECES: usize = 32;
+const BLACK: u8 = 8;
+const NO_EN_PASSANT: u8 = u8::MAX;
+
+/// A position packed into a fixed number of bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PackedPosition([u8; PackedPosition::SIZE]);
+
+impl PackedPosition {
+    /// The number of bytes of a packed position.
+    pub const SIZE: usize = 32;
+
+    /// Pack a position.
+    ///
+    /// # Returns
+    /// * `Ok` with the packed position.
+    /// * `Err` if the board holds more than 32 pieces, or the half move clock doesn't fit
+    ///   in 16 bits.
+    pub fn new(
+        board: &ChessBoard,
+        current_player: Color,
+        state: PositionState,
+    ) -> Result<Self, String> {
+        let mut bytes = [0; Self::SIZE];
+        let mut occupancy = 0u64;
+        let mut count = 0;
+        for (square, &position) in INCREASING_A1_B1.iter().enumerate() {
+            let piece = match board.get_piece(position) {
+                Some(piece) => piece,
+                None => continue,
+            };
+            if count == MAX_PIECES {
+                return Err(format!("cannot pack more than {} pieces", MAX_PIECES));
+            }
+            occupancy |= 1 << square;
+            bytes[PIECES + count / 2] |= piece_code(piece) << (4 * (count % 2));
+            count += 1;
+        }
+        bytes[..PIECES].copy_from_slice(&occupancy.to_le_bytes());
+
+        let rights = state.castling_rights();
+        bytes[FLAGS] = current_player.is_black() as u8
+            | (rights.white_kingside() as u8) << 1
+            | (rights.white_queenside() as u8) << 2
+            | (rights.black_kingside() as u8) << 3
+            | (rights.black_queenside() as u8) << 4;
+        bytes[EN_PASSANT] = match state.en_passant_target() {
+            Some(target) => INCREASING_A1_B1
+                .iter()
+                .position(|&position| position == target)
+                .unwrap() as u8,
+            None => NO_EN_PASSANT,
+        };
+        let half_moves = u16::try_from(state.half_moves())
+            .map_err(|_| format!("cannot pack {} half moves", state.half_moves()))?;
+        bytes[HALF_MOVES..FULL_MOVES].copy_from_slice(&half_moves.to_le_bytes());
+        bytes[FULL_MOVES..].copy_from_slice(&state.full_moves().to_le_bytes());
+        Ok(Self(bytes))
+    }
+
+    /// Pack the current position of `game`.
+    pub fn from_game(game: &Game) -> Result<Self, String> {
+        Self::new(
+            &game.board(),
+            game.current_player(),
+            PositionState::of(game),
+        )
+    }
+
+    /// Read a packed position from the first [`PackedPosition::SIZE`] bytes of `bytes`.
+    ///
+    /// # Returns
+    /// * `Ok` with the packed position.
+    /// * `Err` if `bytes` is too short or doesn't hold a valid packed position.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let bytes: [u8; Self::SIZE] = bytes
+            .get(..Self::SIZE)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| format!("a packed position takes {} bytes", Self::SIZE))?;
+        let packed = Self(bytes);
+        packed.validate()?;
+        Ok(packed)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; Self::SIZE] {
+        &self.0
+    }
+
+    /// Unpack the board, the player to move and the state of the position.
+    pub fn unpack(&self) -> (ChessBoard, Color, PositionState) {
+        (self.board(), self.current_player(), self.state())
+    }
+
+    /// Create a game starting from the packed position.
+    pub fn to_game(&self) -> Game {
+        Game::from_position(self.board(), self.current_player(), self.state())
+    }
+
+    pub fn board(&self) -> ChessBoard {
+        let mut board = ChessBoard::default();
+        board.clear();
+        for (index, square) in self.squares().enumerate() {
+            let piece = decode_piece(self.piece_code(index)).unwrap();
+            board.set_piece(INCREASING_A1_B1[square], piece);
+        }
+        board
+    }
+
+    pub fn current_player(&self) -> Color {
+        if self.0[FLAGS] & 1 == 1 {
+            Color::Black
+        } else {
+            Color::White
+        }
+    }
+
+    pub fn state(&self) -> PositionState {
+        let flag = |bit: u8| self.0[FLAGS] & 1 << bit != 0;
+        let en_passant_target = match self.0[EN_PASSANT] {
+            NO_EN_PASSANT => None,
+            square => Some(INCREASING_A1_B1[square as usize]),
+        };
+        PositionState::new(
+            CastlingRights::new(flag(1), flag(2), flag(3), flag(4)),
+            en_passant_target,
+            u16::from_le_bytes([self.0[HALF_MOVES], self.0[HALF_MOVES + 1]]) as u32,
+            u32::from_le_bytes(self.0[FULL_MOVES..].try_into().unwrap()),
+        )
+    }
+
+    fn occupancy(&self) -> u64 {
+        u64::from_le_bytes(self.0[..PIECES].try_into().unwrap())
+    }
+
+    /// The occupied squares, from a1 = 0 to h8 = 63.
+    fn squares(&self) -> impl Iterator<Item = usize> {
+        let occupancy = self.occupancy();
+        (0..64).filter(move |square| occupancy & 1 << square != 0)
+    }
+
+    fn piece_code(&self, index: usize) -> u8 {
+        self.0[PIECES + index / 2] >> (4 * (index % 2)) & 0xf
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let count = self.occupancy().count_ones() as usize;
+        if count > MAX_PIECES {
+            return Err(format!("packed position has {} pieces", count));
+        }
+        for index in 0..MAX_PIECES {
+            let code = self.piece_code(index);
+            match decode_piece(code) {
+                Some(_) if index < count => {}
+                None if index >= count && code == 0 => {}
+                _ => return Err(format!("invalid piece code {} in packed position", code)),
+            }
+        }
+        if self.0[FLAGS] >> 5 != 0 {
+            return Err(format!(
+                "invalid flags {:#x} in packed position",
+                self.0[FLAGS]
+            ));
+        }
+        if self.0[EN_PASSANT] >= 64 && self.0[EN_PASSANT] != NO_EN_PASSANT {
+            return Err(format!(
+                "invalid en passant target {} in packed position",
+                self.0[EN_PASSANT]
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn piece_code(piece: Piece) -> u8 {
+    let kind = PieceType::all_iter()
+        .position(|kind| kind == piece.kind())
+        .unwrap() as u8;
+    let color = if piece.color().is_black() { BLACK } else { 0 };
+    color | (kind + 1)
+}
+
+fn decode_piece(code: u8) -> Option<Piece> {
+    let color = if code & BLACK != 0 {
+        Color::Black
+    } else {
+        Color::White
+    };
+    let kind = PieceType::all_iter().nth(((code & !BLACK) as usize).checked_sub(1)?)?;
+    Some(Piece::new(color, kind))
+}
+
+/// Write `positions` one after the other.
+pub fn write_packed_positions<W: Write>(
+    writer: &mut W,
+    positions: &[PackedPosition],
+) -> Result<(), String> {
+    let mut bytes = Vec::with_capacity(positions.len() * PackedPosition::SIZE);
+    for position in positions {
+        bytes.extend_from_slice(position.as_bytes());
+    }
+    writer.write_all(&bytes).map_err(|e| e.to_string())
+}
+
+/// Read packed positions written by [`write_packed_positions`] until the end of `reader`.
+///
+/// # Returns
+/// * `Ok` with the positions read.
+/// * `Err` if reading fails, the input doesn't hold a whole number of positions, or one
+///   of them is invalid.
+pub fn read_packed_positions<R: Read>(reader: &mut R) -> Result<Vec<PackedPosition>, String> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes).map_err(|e| e.to_string())?;
+    if bytes.len() % PackedPosition::SIZE != 0 {
+        return Err(format!(
+            "{} bytes is not a whole number of packed positions",
+            bytes.len()
+        ));
+    }
+    bytes
+        .chunks_exact(PackedPosition::SIZE)
+        .map(PackedPosition::from_bytes)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitboard::*;
+
+    fn play(game: &mut Game, uci: &str) {
+        game.make_move(game.parse_uci_move(uci).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut game = Game::default();
+        for uci in ["e2e4", "c7c5", "e4e5", "d7d5"] {
+            play(&mut game, uci);
+        }
+        let packed = PackedPosition::from_game(&game).unwrap();
+        assert_eq!(packed.state().en_passant_target(), Some(D6));
+        assert_eq!(packed.to_game().to_fen_string(), game.to_fen_string());
+        assert_eq!(packed.to_game().get_moves(), game.get_moves());
+        assert!(game
+            .get_moves()
+            .iter()
+            .any(|chess_move| chess_move.is_en_passant()));
+
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/8/8/8/8/8/8/R3K2R b Kq - 17 93",
+            "8/8/8/4k3/8/8/8/4K3 w - - 0 1",
+        ] {
+            let game = Game::from_fen_string(fen).unwrap();
+            let packed = PackedPosition::from_game(&game).unwrap();
+            let (board, player, state) = packed.unpack();
+            assert_eq!(board, game.board());
+            assert_eq!(player, game.current_player());
+            assert_eq!(state, PositionState::of(&game));
+            assert_eq!(packed.to_game().to_fen_string(), game.to_fen_string());
+        }
+    }
+
+    #[test]
+    fn layout() {
+        let packed = PackedPosition::from_game(&Game::default()).unwrap();
+        let bytes = packed.as_bytes();
+        assert_eq!(bytes[..8], 0xffff_0000_0000_ffffu64.to_le_bytes());
+        // R N B Q K B N R, then eight pawns
+        assert_eq!(bytes[8..12], [0x24, 0x53, 0x36, 0x42]);
+        assert_eq!(
+            bytes[12..20],
+            [0x11, 0x11, 0x11, 0x11, 0x99, 0x99, 0x99, 0x99]
+        );
+        assert_eq!(bytes[20..24], [0xac, 0xdb, 0xbe, 0xca]);
+        assert_eq!(bytes[24..], [0b11110, 255, 0, 0, 1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn invalid_bytes() {
+        let bytes = *PackedPosition::from_game(&Game::default())
+            .unwrap()
+            .as_bytes();
+        assert!(PackedPosition::from_bytes(&bytes[..31]).is_err());
+        for (index, value) in [(8, 0x20), (8, 0x27), (24, 0x20), (25, 64), (25, 200)] {
+            let mut invalid = bytes;
+            invalid[index] = value;
+            assert!(PackedPosition::from_bytes(&invalid).is_err());
+        }
+
+        let game = Game::default();
+        let mut board = game.board();
+        board.clear();
+        for &position in &INCREASING_A1_B1[..33] {
+            board.set_piece(position, Piece::pawn(Color::White));
+        }
+        assert!(PackedPosition::new(&board, Color::White, PositionState::of(&game)).is_err());
+    }
+
+    #[test]
+    fn bulk() {
+        let mut game = Game::default();
+        let mut positions = vec![PackedPosition::from_game(&game).unwrap()];
+        for uci in ["g1f3", "g8f6", "b1c3"] {
+            play(&mut game, uci);
+            positions.push(PackedPosition::from_game(&game).unwrap());
+        }
+        let mut bytes = Vec::new();
+        write_packed_positions(&mut bytes, &positions).unwrap();
+        assert_eq!(bytes.len(), 4 * PackedPosition::SIZE);
+        assert_eq!(
+            read_packed_positions(&mut bytes.as_slice()).unwrap(),
+            positions
+        );
+        assert!(read_packed_positions(&mut &bytes[1..]).is_err());
+    }
+}
@@ -0,0 +1,253 @@
+//! Reading games in Portable Game Notation.
+//!
+//! Each game is read into a [`GameRecord`] with its tags, its result and its main line.
+//! Comments, variations, move numbers and numeric annotation glyphs are skipped. A game that
+//! has a `FEN` tag starts from that position.
+
+use crate::{
+    database::GameRecord,
+    game::{Game, GameOver},
+    Color,
+};
+use std::io::{BufRead, Lines};
+
+/// Reads the games of a PGN file one at a time.
+#[derive(Debug)]
+pub struct PgnReader<R: BufRead> {
+    lines: Lines<R>,
+    /// The first tag of the next game, read while looking for the end of the current one.
+    pending: Option<String>,
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: reader.lines(),
+            pending: None,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<GameRecord, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut text = String::new();
+        let mut in_movetext = false;
+        loop {
+            let line = match self.pending.take().map(Ok).or_else(|| self.lines.next()) {
+                Some(Ok(line)) => line,
+                Some(Err(e)) => return Some(Err(e.to_string())),
+                None if text.trim().is_empty() => return None,
+                None => break,
+            };
+            let trimmed = line.trim();
+            if trimmed.starts_with('[') && in_movetext {
+                self.pending = Some(line);
+                break;
+            }
+            if trimmed.starts_with('%') {
+                continue;
+            }
+            in_movetext |= !trimmed.is_empty() && !trimmed.starts_with('[');
+            text.push_str(&line);
+            text.push('\n');
+        }
+        Some(parse_game(&text))
+    }
+}
+
+/// Parse a single game in PGN.
+///
+/// # Returns
+/// * `Ok` with the game, its tags and its result.
+/// * `Err` if a tag is malformed, or a move is not legal.
+pub fn parse_game(pgn: &str) -> Result<GameRecord, String> {
+    let mut tags = Vec::new();
+    let mut movetext = String::new();
+    for line in pgn.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') && movetext.trim().is_empty() {
+            tags.push(parse_tag(trimmed)?);
+        } else {
+            movetext.push_str(line);
+            movetext.push('\n');
+        }
+    }
+
+    let mut game = match tags.iter().find(|(name, _)| name == "FEN") {
+        Some((_, fen)) => Game::from_fen_string(fen)?,
+        None => Game::default(),
+    };
+    let mut result = tags
+        .iter()
+        .find(|(name, _)| name == "Result")
+        .and_then(|(_, result)| parse_result(result))
+        .flatten();
+    for token in tokens(&movetext) {
+        if let Some(token_result) = parse_result(token) {
+            result = token_result;
+            break;
+        }
+        if token.starts_with('$') {
+            continue;
+        }
+        let san = match token.trim_start_matches(|c: char| c.is_ascii_digit()) {
+            numbered if numbered.starts_with('.') => numbered.trim_start_matches('.'),
+            _ => token,
+        };
+        if san.is_empty() {
+            continue;
+        }
+        let chess_move = game.parse_san_move(san)?;
+        game.make_move(chess_move)?;
+    }
+    Ok(GameRecord::new(game, tags, result))
+}
+
+/// Parse a tag pair such as `[Event "Casual game"]`.
+fn parse_tag(line: &str) -> Result<(String, String), String> {
+    let invalid = || format!("invalid tag '{}'", line);
+    let inner = line
+        .strip_prefix('[')
+        .and_then(|line| line.strip_suffix(']'))
+        .ok_or_else(invalid)?
+        .trim();
+    let (name, value) = inner.split_at(inner.find(char::is_whitespace).ok_or_else(invalid)?);
+    let value = value
+        .trim()
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .ok_or_else(invalid)?;
+
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        unescaped.push(if c == '\\' {
+            chars.next().ok_or_else(invalid)?
+        } else {
+            c
+        });
+    }
+    Ok((name.to_string(), unescaped))
+}
+
+/// Parse a game termination marker.
+///
+/// # Returns
+/// * `Some` with the result, which is `None` for an unfinished game.
+/// * `None` if `token` is not a termination marker.
+fn parse_result(token: &str) -> Option<Option<GameOver>> {
+    match token {
+        "1-0" => Some(Some(GameOver::Winner(Color::White))),
+        "0-1" => Some(Some(GameOver::Winner(Color::Black))),
+        "1/2-1/2" => Some(Some(GameOver::Draw)),
+        "*" => Some(None),
+        _ => None,
+    }
+}
+
+/// Split movetext into tokens, leaving out comments and variations.
+fn tokens(movetext: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut depth = 0;
+    let mut start = None;
+    let mut chars = movetext.char_indices();
+    while let Some((i, c)) = chars.next() {
+        let separator = c.is_whitespace() || "(){};".contains(c);
+        if separator {
+            if let Some(start) = start.take() {
+                if depth == 0 {
+                    tokens.push(&movetext[start..i]);
+                }
+            }
+        }
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            '{' => {
+                for (_, c) in &mut chars {
+                    if c == '}' {
+                        break;
+                    }
+                }
+            }
+            ';' => {
+                for (_, c) in &mut chars {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            _ if !separator && start.is_none() => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(start) = start {
+        if depth == 0 {
+            tokens.push(&movetext[start..]);
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PGN: &str = r#"[Event "Casual \"blitz\" game"]
+[White "Anderssen"]
+[Black "Kieseritzky"]
+[Result "1-0"]
+
+1. e4 e5 2. f4 exf4 3. Bc4 Qh4+ 4. Kf1 b5 {the bishop is offered} 5. Bxb5 Nf6
+6. Nf3 Qh6 7. d3 Nh5 8. Nh4 Qg5 (8... g6 9. Nf5) 9. Nf5 c6 10. g4 Nf6 11. Rg1 cxb5
+12. h4 Qg6 13. h5 Qg5 14. Qf3 Ng8 15. Bxf4 Qf6 16. Nc3 Bc5 17. Nd5 Qxb2 18. Bd6 Bxg1
+19. e5 Qxa1+ 20. Ke2 Na6 21. Nxg7+ Kd8 22. Qf6+ Nxf6 23. Be7# 1-0
+
+[Event "Opening"]
+[FEN "4k3/8/8/8/8/8/8/4K2R w K - 0 1"]
+
+1. O-O $1 Kd7 ; castled
+2. Rd1+ *
+"#;
+
+    #[test]
+    fn read_games() {
+        let games: Vec<GameRecord> = PgnReader::new(PGN.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(games.len(), 2);
+
+        let immortal = &games[0];
+        assert_eq!(immortal.tag("Event"), Some("Casual \"blitz\" game"));
+        assert_eq!(immortal.result(), Some(GameOver::Winner(Color::White)));
+        assert_eq!(immortal.game().move_history().len(), 45);
+        assert_eq!(
+            immortal.game().game_result(),
+            Some(GameOver::Winner(Color::White))
+        );
+
+        let second = &games[1];
+        assert_eq!(second.result(), None);
+        assert_eq!(
+            second.game().to_fen_string(),
+            "8/3k4/8/8/8/8/8/3R2K1 b - - 3 2"
+        );
+    }
+
+    #[test]
+    fn invalid_games() {
+        assert!(parse_game("1. e4 e5 2. Ke3 *").is_err());
+        assert!(parse_game("[Event Casual]\n\n1. e4 *").is_err());
+        assert_eq!(parse_game("").unwrap().game().move_history().len(), 0);
+        assert_eq!(
+            parse_game("1.e4 1...e5 2.Nf3")
+                .unwrap()
+                .game()
+                .move_history()
+                .len(),
+            3
+        );
+    }
+}
@@ -98,6 +98,11 @@ impl Display for Piece {
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
 pub enum PieceType {
     Pawn,
     Knight,
@@ -111,4 +116,18 @@ impl PieceType {
     pub fn all_iter() -> impl Iterator<Item = Self> {
         [Pawn, Knight, Bishop, Rook, Queen, King].iter().copied()
     }
+
+    /// The material value of the piece type, in centipawns.
+    ///
+    /// The king is given a value larger than all other material combined.
+    pub fn value(&self) -> i32 {
+        match self {
+            Pawn => 100,
+            Knight => 300,
+            Bishop => 300,
+            Rook => 500,
+            Queen => 900,
+            King => 20_000,
+        }
+    }
 }
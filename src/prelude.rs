@@ -1,6 +1,29 @@
 pub use crate::{
-    chess_move::ChessMove,
-    game::Game,
+    chess_move::{CastlingRights, ChessMove, PromotionPiece},
+    clock::{Clock, Increment, ManualTimeSource, Stage, SystemTimeSource, TimeControl, TimeSource},
+    database::{GameReader, GameRecord, GameWriter},
+    eco::{EcoClassifier, Opening},
+    endgame::{Dtm, EndgameTable, MAX_EXTRA_PIECES},
+    events::{GameEvent, ListenerId},
+    explorer::{ExplorerMove, OpeningExplorer},
+    game::{Game, GameOver, GameOverReason},
+    illegal_move::IllegalMove,
+    index::{PositionHit, PositionIndex},
+    move_info::{CheckKind, MoveInfo, PositionState, SquareChange},
+    packed::{read_packed_positions, write_packed_positions, PackedPosition},
+    pgn::{parse_game, PgnReader},
     piece::{Piece, PieceType},
+    problem::{
+        AttackingMove, DefendingMove, HelpmateSolver, MateSolution, MateSolver, SelfmateSolver,
+    },
+    puzzle::{Puzzle, PuzzleReader, PuzzleRecord, PuzzleStep},
+    query::{MaterialSignature, PositionQuery},
+    retro::{retro_moves, RetroMove},
+    search::{evaluate, is_mate_score, Search, SearchResult, MATE_SCORE},
+    syzygy::{RankedMove, Tablebase, Wdl, MAX_PIECES},
+    tactics::{discovered_attacks, pins, x_rays, DiscoveredAttack, Pin, PinKind, XRay},
+    uci::run_uci,
+    uci_client::{BestMove, EngineInfo, EngineOption, Score, SearchLimit, UciClient},
+    xboard::run_xboard,
     Color,
 };
@@ -0,0 +1,433 @@
+use crate::{
+    chess_move::ChessMove,
+    game::{Game, GameOver},
+    Color,
+};
+
+/// A move by the attacking side in a solution tree, together with every defence to it.
+///
+/// A move without defences delivers mate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttackingMove {
+    chess_move: ChessMove,
+    defences: Vec<DefendingMove>,
+}
+
+impl AttackingMove {
+    pub fn chess_move(&self) -> ChessMove {
+        self.chess_move
+    }
+
+    /// Every legal reply of the defending side.
+    pub fn defences(&self) -> &[DefendingMove] {
+        &self.defences
+    }
+
+    /// Returns `true` if the move delivers mate.
+    pub fn is_mate(&self) -> bool {
+        self.defences.is_empty()
+    }
+}
+
+/// A move by the defending side in a solution tree, together with every continuation that
+/// still forces mate in time.
+///
+/// A defence without continuations delivers mate, which only happens in selfmates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefendingMove {
+    chess_move: ChessMove,
+    replies: Vec<AttackingMove>,
+}
+
+impl DefendingMove {
+    pub fn chess_move(&self) -> ChessMove {
+        self.chess_move
+    }
+
+    /// Every continuation of the attacking side that still forces mate in time.
+    pub fn replies(&self) -> &[AttackingMove] {
+        &self.replies
+    }
+
+    /// Returns `true` if the attacking side has more than one way of continuing.
+    pub fn is_dual(&self) -> bool {
+        self.replies.len() > 1
+    }
+}
+
+/// Every solution to a direct mate or selfmate problem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MateSolution {
+    keys: Vec<AttackingMove>,
+}
+
+impl MateSolution {
+    /// Every key move, each with its full tree of defences and replies.
+    pub fn keys(&self) -> &[AttackingMove] {
+        &self.keys
+    }
+
+    /// Returns `true` if the problem has exactly one key move.
+    pub fn is_unique(&self) -> bool {
+        self.keys.len() == 1
+    }
+
+    /// Returns `true` if the problem has more than one key move, which is called a cook.
+    pub fn is_cooked(&self) -> bool {
+        self.keys.len() > 1
+    }
+
+    /// Every line of play that ends in a defence after which the attacking side has more than
+    /// one way of continuing.
+    pub fn duals(&self) -> Vec<Vec<ChessMove>> {
+        let mut duals = Vec::new();
+        for key in &self.keys {
+            collect_duals(key, &mut vec![], &mut duals);
+        }
+        duals
+    }
+}
+
+fn collect_duals(
+    attacking_move: &AttackingMove,
+    line: &mut Vec<ChessMove>,
+    duals: &mut Vec<Vec<ChessMove>>,
+) {
+    line.push(attacking_move.chess_move);
+    for defence in &attacking_move.defences {
+        line.push(defence.chess_move);
+        if defence.is_dual() {
+            duals.push(line.clone());
+        }
+        for reply in &defence.replies {
+            collect_duals(reply, line, duals);
+        }
+        line.pop();
+    }
+    line.pop();
+}
+
+/// Solves direct mate problems, where the current player forces mate in at most a given number
+/// of moves against any defence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MateSolver {
+    moves: u32,
+}
+
+impl MateSolver {
+    /// Create a solver for mate in `moves` moves.
+    pub fn new(moves: u32) -> Self {
+        Self { moves }
+    }
+
+    /// Find every key move that forces mate in at most the given number of moves.
+    ///
+    /// # Returns
+    /// * `Some` with every key move and its solution tree.
+    /// * `None` if there is no forced mate within the given number of moves.
+    pub fn solve(&self, game: &Game) -> Option<MateSolution> {
+        let keys = forced_mates(game, self.moves);
+        if keys.is_empty() {
+            None
+        } else {
+            Some(MateSolution { keys })
+        }
+    }
+}
+
+/// Solves helpmate problems, where both players cooperate so that the current player is mated
+/// on the opponent's move, after exactly a given number of moves by each player.
+///
+/// Helpmates are conventionally set with black to move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HelpmateSolver {
+    moves: u32,
+}
+
+impl HelpmateSolver {
+    /// Create a solver for helpmate in `moves` moves.
+    pub fn new(moves: u32) -> Self {
+        Self { moves }
+    }
+
+    /// Find every solution, as the full sequence of moves made by both players.
+    ///
+    /// # Returns
+    /// Every solution, or an empty list if there is none.
+    pub fn solve(&self, game: &Game) -> Vec<Vec<ChessMove>> {
+        let mut solutions = Vec::new();
+        if self.moves > 0 {
+            helpmates(
+                game,
+                2 * self.moves,
+                game.current_player(),
+                &mut vec![],
+                &mut solutions,
+            );
+        }
+        solutions
+    }
+}
+
+fn helpmates(
+    game: &Game,
+    plies_left: u32,
+    mated: Color,
+    line: &mut Vec<ChessMove>,
+    solutions: &mut Vec<Vec<ChessMove>>,
+) {
+    for chess_move in sorted_moves(game) {
+        let mut after_move = game.clone();
+        after_move.make_move(chess_move).unwrap();
+        line.push(chess_move);
+        match after_move.game_result() {
+            Some(GameOver::Winner(winner)) if winner != mated && plies_left == 1 => {
+                solutions.push(line.clone());
+            }
+            None if plies_left > 1 => {
+                helpmates(&after_move, plies_left - 1, mated, line, solutions);
+            }
+            _ => {}
+        }
+        line.pop();
+    }
+}
+
+/// Solves selfmate problems, where the current player forces the opponent to deliver mate in
+/// at most a given number of moves against any defence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelfmateSolver {
+    moves: u32,
+}
+
+impl SelfmateSolver {
+    /// Create a solver for selfmate in `moves` moves.
+    pub fn new(moves: u32) -> Self {
+        Self { moves }
+    }
+
+    /// Find every key move that forces the opponent to deliver mate in at most the given
+    /// number of moves.
+    ///
+    /// # Returns
+    /// * `Some` with every key move and its solution tree.
+    /// * `None` if there is no forced selfmate within the given number of moves.
+    pub fn solve(&self, game: &Game) -> Option<MateSolution> {
+        let keys = forced_selfmates(game, self.moves);
+        if keys.is_empty() {
+            None
+        } else {
+            Some(MateSolution { keys })
+        }
+    }
+}
+
+/// Find every move for the current player that forces the opponent to deliver mate in at most
+/// `moves` moves.
+fn forced_selfmates(game: &Game, moves: u32) -> Vec<AttackingMove> {
+    let mut selfmates = Vec::new();
+    if moves == 0 {
+        return selfmates;
+    }
+
+    let attacker = game.current_player();
+    for chess_move in sorted_moves(game) {
+        let mut after_move = game.clone();
+        after_move.make_move(chess_move).unwrap();
+        if after_move.is_over() {
+            continue;
+        }
+
+        let mut defences = Vec::new();
+        let mut refuted = false;
+        for defence in sorted_moves(&after_move) {
+            let mut after_defence = after_move.clone();
+            after_defence.make_move(defence).unwrap();
+            let replies = match after_defence.game_result() {
+                Some(GameOver::Winner(winner)) if winner != attacker => vec![],
+                Some(_) => {
+                    refuted = true;
+                    break;
+                }
+                None => {
+                    let replies = forced_selfmates(&after_defence, moves - 1);
+                    if replies.is_empty() {
+                        // the defender can avoid giving mate in time
+                        refuted = true;
+                        break;
+                    }
+                    replies
+                }
+            };
+            defences.push(DefendingMove {
+                chess_move: defence,
+                replies,
+            });
+        }
+        if !refuted {
+            selfmates.push(AttackingMove {
+                chess_move,
+                defences,
+            });
+        }
+    }
+
+    selfmates
+}
+
+/// Find every move for the current player that forces mate in at most `moves` moves.
+fn forced_mates(game: &Game, moves: u32) -> Vec<AttackingMove> {
+    let mut mates = Vec::new();
+    if moves == 0 {
+        return mates;
+    }
+
+    let attacker = game.current_player();
+    for chess_move in sorted_moves(game) {
+        let mut after_move = game.clone();
+        after_move.make_move(chess_move).unwrap();
+        match after_move.game_result() {
+            Some(GameOver::Winner(winner)) if winner == attacker => {
+                mates.push(AttackingMove {
+                    chess_move,
+                    defences: vec![],
+                });
+                continue;
+            }
+            Some(_) => continue,
+            None if moves == 1 => continue,
+            None => {}
+        }
+
+        let mut defences = Vec::new();
+        for defence in sorted_moves(&after_move) {
+            let mut after_defence = after_move.clone();
+            after_defence.make_move(defence).unwrap();
+            let replies = forced_mates(&after_defence, moves - 1);
+            if replies.is_empty() {
+                // this defence refutes the move
+                defences.clear();
+                break;
+            }
+            defences.push(DefendingMove {
+                chess_move: defence,
+                replies,
+            });
+        }
+        if !defences.is_empty() {
+            mates.push(AttackingMove {
+                chess_move,
+                defences,
+            });
+        }
+    }
+
+    mates
+}
+
+/// Get the legal moves of the current player, sorted so that solutions are listed in a
+/// predictable order.
+fn sorted_moves(game: &Game) -> Vec<ChessMove> {
+    let mut moves: Vec<ChessMove> = game.get_moves().iter().copied().collect();
+    moves.sort_by_cached_key(|chess_move| chess_move.to_uci_string());
+    moves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitboard::*;
+
+    fn regular(from: Position, to: Position) -> ChessMove {
+        ChessMove::Regular { from, to }
+    }
+
+    #[test]
+    fn mate_in_one() {
+        let game = Game::from_fen_string("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let solution = MateSolver::new(1).solve(&game).unwrap();
+        assert!(solution.is_unique());
+        assert_eq!(solution.keys()[0].chess_move(), regular(A1, A8));
+        assert!(solution.keys()[0].is_mate());
+
+        // either rook mates
+        let game = Game::from_fen_string("6k1/5ppp/8/8/8/8/8/RR4K1 w - - 0 1").unwrap();
+        let solution = MateSolver::new(1).solve(&game).unwrap();
+        assert!(solution.is_cooked());
+        let keys: Vec<_> = solution.keys().iter().map(|key| key.chess_move()).collect();
+        assert_eq!(keys, vec![regular(A1, A8), regular(B1, B8)]);
+    }
+
+    #[test]
+    fn mate_in_two() {
+        let game = Game::from_fen_string("k7/8/2K5/8/8/8/8/1R6 w - - 0 1").unwrap();
+        assert_eq!(MateSolver::new(1).solve(&game), None);
+
+        let solution = MateSolver::new(2).solve(&game).unwrap();
+        assert!(solution.is_unique());
+        assert!(solution.duals().is_empty());
+        assert_eq!(
+            solution.keys(),
+            &[AttackingMove {
+                chess_move: regular(C6, C7),
+                defences: vec![DefendingMove {
+                    chess_move: regular(A8, A7),
+                    replies: vec![AttackingMove {
+                        chess_move: regular(B1, A1),
+                        defences: vec![],
+                    }],
+                }],
+            }]
+        );
+    }
+
+    #[test]
+    fn helpmate_in_one() {
+        let game = Game::from_fen_string("7k/8/6K1/8/8/8/8/R7 b - - 0 1").unwrap();
+        assert_eq!(
+            HelpmateSolver::new(1).solve(&game),
+            vec![vec![regular(H8, G8), regular(A1, A8)]]
+        );
+        assert!(HelpmateSolver::new(1).solve(&Game::default()).is_empty());
+    }
+
+    #[test]
+    fn selfmate_in_one() {
+        // black can only move the b-pawn, which opens the long diagonal, so white just has to
+        // wait with the a-pawn
+        let game = Game::from_fen_string("b6k/1p3P1p/7P/8/8/8/P6P/6BK w - - 0 1").unwrap();
+        let solution = SelfmateSolver::new(1).solve(&game).unwrap();
+        assert!(solution.is_cooked());
+
+        let defences = vec![
+            DefendingMove {
+                chess_move: regular(B7, B5),
+                replies: vec![],
+            },
+            DefendingMove {
+                chess_move: regular(B7, B6),
+                replies: vec![],
+            },
+        ];
+        assert_eq!(
+            solution.keys(),
+            &[
+                AttackingMove {
+                    chess_move: regular(A2, A3),
+                    defences: defences.clone(),
+                },
+                AttackingMove {
+                    chess_move: regular(A2, A4),
+                    defences,
+                },
+            ]
+        );
+
+        assert_eq!(SelfmateSolver::new(1).solve(&Game::default()), None);
+    }
+
+    #[test]
+    fn no_mate() {
+        assert_eq!(MateSolver::new(2).solve(&Game::default()), None);
+    }
+}
@@ -0,0 +1,342 @@
+//! Tactics puzzles in the format of the Lichess puzzle database.
+//!
+//! Each line of the CSV file holds a puzzle id, the FEN of the starting position, the moves in
+//! UCI notation separated by spaces, the rating and its deviation, the popularity, the number of
+//! plays, the themes separated by spaces, and optionally the URL of the game and the opening
+//! tags. The first move is made by the opponent to set the puzzle up, and the remaining moves
+//! alternate between the solver and the opponent, ending with a move of the solver.
+
+use crate::{chess_move::ChessMove, game::Game, move_info::CheckKind, Color};
+use std::io::{BufRead, Lines};
+
+/// A puzzle read from the Lichess puzzle database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PuzzleRecord {
+    id: String,
+    fen: String,
+    moves: Vec<String>,
+    rating: u32,
+    rating_deviation: u32,
+    popularity: i32,
+    plays: u32,
+    themes: Vec<String>,
+    game_url: Option<String>,
+}
+
+impl PuzzleRecord {
+    /// Parse a line of the Lichess puzzle CSV file.
+    ///
+    /// # Returns
+    /// * `Ok` with the puzzle.
+    /// * `Err` if a field is missing or malformed.
+    pub fn from_csv_line(line: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = line.trim().split(',').collect();
+        if fields.len() < 8 {
+            return Err(format!("invalid puzzle line '{}'", line));
+        }
+        let number = |i: usize, name| {
+            fields[i]
+                .parse()
+                .map_err(|_| format!("invalid {} '{}' in puzzle {}", name, fields[i], fields[0]))
+        };
+        let words = |field: &str| field.split_whitespace().map(String::from).collect();
+        let moves: Vec<String> = words(fields[2]);
+        if moves.len() < 2 || moves.len() % 2 == 1 {
+            return Err(format!(
+                "puzzle {} must have a setup move and end with a move of the solver",
+                fields[0]
+            ));
+        }
+        Ok(Self {
+            id: fields[0].to_string(),
+            fen: fields[1].to_string(),
+            moves,
+            rating: number(3, "rating")?,
+            rating_deviation: number(4, "rating deviation")?,
+            popularity: fields[5].parse().map_err(|_| {
+                format!("invalid popularity '{}' in puzzle {}", fields[5], fields[0])
+            })?,
+            plays: number(6, "number of plays")?,
+            themes: words(fields[7]),
+            game_url: fields
+                .get(8)
+                .filter(|url| !url.is_empty())
+                .map(|url| url.to_string()),
+        })
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The position before the opponent's setup move.
+    pub fn fen(&self) -> &str {
+        &self.fen
+    }
+
+    /// The setup move followed by the solution, in UCI notation.
+    pub fn moves(&self) -> &[String] {
+        &self.moves
+    }
+
+    pub fn rating(&self) -> u32 {
+        self.rating
+    }
+
+    pub fn rating_deviation(&self) -> u32 {
+        self.rating_deviation
+    }
+
+    /// The difference between the votes for and against the puzzle, from -100 to 100.
+    pub fn popularity(&self) -> i32 {
+        self.popularity
+    }
+
+    pub fn plays(&self) -> u32 {
+        self.plays
+    }
+
+    /// The themes of the puzzle, such as `fork` or `mateIn2`.
+    pub fn themes(&self) -> &[String] {
+        &self.themes
+    }
+
+    pub fn has_theme(&self, theme: &str) -> bool {
+        self.themes.iter().any(|t| t == theme)
+    }
+
+    /// The URL of the game the puzzle was taken from.
+    pub fn game_url(&self) -> Option<&str> {
+        self.game_url.as_deref()
+    }
+}
+
+/// Reads the puzzles of a Lichess puzzle CSV file one at a time, skipping the header line.
+#[derive(Debug)]
+pub struct PuzzleReader<R: BufRead> {
+    lines: Lines<R>,
+}
+
+impl<R: BufRead> PuzzleReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: reader.lines(),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PuzzleReader<R> {
+    type Item = Result<PuzzleRecord, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e.to_string())),
+            };
+            if !line.trim().is_empty() && !line.starts_with("PuzzleId,") {
+                return Some(PuzzleRecord::from_csv_line(&line));
+            }
+        }
+    }
+}
+
+/// The outcome of a move played in a [`Puzzle`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PuzzleStep {
+    /// The move is correct, and the opponent answered with the given move.
+    Correct(ChessMove),
+    /// The move is correct and completes the solution.
+    Solved,
+    /// The move is not the solution. It is not played, so the solver can try again, but the
+    /// puzzle counts as failed.
+    Wrong,
+}
+
+/// A puzzle being played, with the opponent's setup move already made.
+///
+/// A move that gives checkmate is always accepted, even if the solution mates with another move.
+#[derive(Debug, Clone)]
+pub struct Puzzle {
+    game: Game,
+    solver: Color,
+    solution: Vec<ChessMove>,
+    /// The index in `solution` of the next move of the solver.
+    next: usize,
+    failed: bool,
+    solved: bool,
+}
+
+impl Puzzle {
+    /// Set up `record` by making the opponent's first move.
+    ///
+    /// # Returns
+    /// * `Ok` with the puzzle, ready for the solver's first move.
+    /// * `Err` if the FEN is invalid, or a move of the solution is not legal.
+    pub fn new(record: &PuzzleRecord) -> Result<Self, String> {
+        let mut game = Game::from_fen_string(&record.fen)?;
+        let mut solution = Vec::with_capacity(record.moves.len());
+        let mut replay = game.clone();
+        for uci in &record.moves {
+            let chess_move = replay.parse_uci_move(uci)?;
+            replay
+                .make_move(chess_move)
+                .map_err(|e| format!("{} '{}' in puzzle {}", e, uci, record.id))?;
+            solution.push(chess_move);
+        }
+        game.make_move(solution[0]).unwrap();
+        Ok(Self {
+            solver: game.current_player(),
+            game,
+            solution,
+            next: 1,
+            failed: false,
+            solved: false,
+        })
+    }
+
+    /// The current position of the puzzle.
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// The color of the solver.
+    pub fn solver(&self) -> Color {
+        self.solver
+    }
+
+    /// The move of the solution expected next, or `None` once the puzzle is solved.
+    pub fn hint(&self) -> Option<ChessMove> {
+        if self.solved {
+            None
+        } else {
+            Some(self.solution[self.next])
+        }
+    }
+
+    /// Play a move of the solver.
+    ///
+    /// # Returns
+    /// * `Ok` with whether the move is correct. A correct move is played along with the
+    ///   opponent's answer, while a wrong one is not played.
+    /// * `Err` if the puzzle is already solved.
+    /// * `Err` if the move is not legal.
+    pub fn play(&mut self, chess_move: ChessMove) -> Result<PuzzleStep, &'static str> {
+        if self.solved {
+            return Err("puzzle is already solved");
+        }
+        if !self.game.get_moves().contains(&chess_move) {
+            return Err("illegal move");
+        }
+        if chess_move != self.solution[self.next] && !self.is_mate(chess_move) {
+            self.failed = true;
+            return Ok(PuzzleStep::Wrong);
+        }
+
+        let mate = self.game.make_move(chess_move)?.check() == Some(CheckKind::Checkmate);
+        if mate || self.next + 1 == self.solution.len() {
+            self.solved = true;
+            return Ok(PuzzleStep::Solved);
+        }
+        let reply = self.solution[self.next + 1];
+        self.game.make_move(reply)?;
+        self.next += 2;
+        Ok(PuzzleStep::Correct(reply))
+    }
+
+    /// Play a move of the solver written in UCI notation.
+    pub fn play_uci(&mut self, uci: &str) -> Result<PuzzleStep, String> {
+        let chess_move = self.game.parse_uci_move(uci)?;
+        Ok(self.play(chess_move)?)
+    }
+
+    fn is_mate(&self, chess_move: ChessMove) -> bool {
+        let mut after = self.game.clone();
+        matches!(
+            after.make_move(chess_move).map(|info| info.check()),
+            Ok(Some(CheckKind::Checkmate))
+        )
+    }
+
+    /// Returns `true` if every move of the solution has been played.
+    pub fn is_solved(&self) -> bool {
+        self.solved
+    }
+
+    /// Returns `true` if a wrong move was tried.
+    pub fn is_failed(&self) -> bool {
+        self.failed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitboard::*;
+
+    const CSV: &str = "PuzzleId,FEN,Moves,Rating,RatingDeviation,Popularity,NbPlays,Themes,GameUrl,OpeningTags
+00sHx,q3k1nr/1pp1nQpp/3p4/1P2p3/4P3/B1PP1b2/B5PP/5K2 b k - 0 17,e8d7 a2e6 d7d8 f7f8,1760,80,83,72,mate mateIn2 middlegame short,https://lichess.org/yyznGmXs/black#34,Italian_Game Italian_Game_Classical_Variation
+
+00bad,q3k1nr/1pp1nQpp/3p4/1P2p3/4P3/B1PP1b2/B5PP/5K2 b k - 0 17,e8d7 a2e6,high,80,83,72,mate,
+";
+
+    fn record() -> PuzzleRecord {
+        PuzzleReader::new(CSV.as_bytes()).next().unwrap().unwrap()
+    }
+
+    #[test]
+    fn read_puzzles() {
+        let puzzles: Vec<_> = PuzzleReader::new(CSV.as_bytes()).collect();
+        assert_eq!(puzzles.len(), 2);
+        assert!(puzzles[1].is_err());
+
+        let record = record();
+        assert_eq!(record.id(), "00sHx");
+        assert_eq!(record.moves().len(), 4);
+        assert_eq!(record.rating(), 1760);
+        assert_eq!(record.popularity(), 83);
+        assert!(record.has_theme("mateIn2"));
+        assert_eq!(
+            record.game_url(),
+            Some("https://lichess.org/yyznGmXs/black#34")
+        );
+
+        assert!(PuzzleRecord::from_csv_line(
+            "00sHx,8/8/8/8/8/8/8/8 w - - 0 1,e2e4,1500,80,0,0,short"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn solve() {
+        let mut puzzle = Puzzle::new(&record()).unwrap();
+        assert_eq!(puzzle.solver(), Color::White);
+        assert_eq!(puzzle.play_uci("a2b1"), Ok(PuzzleStep::Wrong));
+        assert!(puzzle.is_failed());
+        assert_eq!(
+            puzzle.play(ChessMove::Regular { from: A2, to: A3 }),
+            Err("illegal move")
+        );
+
+        let reply = ChessMove::Regular { from: D7, to: D8 };
+        assert_eq!(puzzle.play_uci("a2e6"), Ok(PuzzleStep::Correct(reply)));
+        assert_eq!(puzzle.hint(), puzzle.game().parse_uci_move("f7f8").ok());
+        assert_eq!(puzzle.play_uci("f7f8"), Ok(PuzzleStep::Solved));
+        assert!(puzzle.is_solved());
+        assert_eq!(puzzle.hint(), None);
+        assert!(puzzle.play_uci("a3b4").is_err());
+    }
+
+    #[test]
+    fn alternative_mate() {
+        let record = PuzzleRecord::from_csv_line(
+            "00alt,6k1/5ppp/8/8/8/8/8/RR4K1 b - - 0 1,g8h8 a1a8,900,75,90,100,mate mateIn1 oneMove",
+        )
+        .unwrap();
+        let mut puzzle = Puzzle::new(&record).unwrap();
+        assert_eq!(puzzle.play_uci("b1b7"), Ok(PuzzleStep::Wrong));
+        assert_eq!(puzzle.play_uci("b1b8"), Ok(PuzzleStep::Solved));
+        assert!(puzzle.is_solved());
+        assert!(puzzle.is_failed());
+    }
+}
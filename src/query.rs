@@ -0,0 +1,402 @@
+//! Searching for positions by their material and the placement of their pieces.
+//!
+//! A query is written as terms separated by whitespace, and a position matches when it matches
+//! every term:
+//! * `material:KRPKR` matches the material signature, white's pieces first and then black's.
+//!   Each side starts with its king, and every piece letter may be followed by a count: `P2` for
+//!   exactly two pawns, `P1-3` for one to three, `P+` for at least one and `P*` for any number.
+//!   Pieces that are not listed must be absent.
+//! * `Nd5` matches a white knight on d5, and `pc6,e6` a black pawn on c6 or e6. Squares can be
+//!   given as a file, such as `Pe`, or as a rank, such as `P7`, and a term prefixed by `!`, such
+//!   as `!pe`, matches when no such piece stands on any of the squares.
+//! * `turn:white` and `turn:black` match the player to move.
+//! * `check` matches a player to move in check, and `!check` one that isn't.
+//!
+//! For example, `material:KRP*KR Pe,f,g turn:black` finds rook endings with a white pawn on one
+//! of the e, f or g files and black to move.
+
+use crate::{
+    chess_board::ChessBoard, database::GameRecord, game::Game, index::PositionHit,
+    piece::PieceType, Color, Piece,
+};
+use bitboard::*;
+use std::{fmt, str::FromStr};
+
+/// The piece types that can be counted in a material signature, in the order they are written.
+const SIGNATURE_KINDS: [PieceType; 5] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+    PieceType::Pawn,
+];
+
+/// The allowed number of pieces of each kind for both players, such as `KRP*KR`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaterialSignature {
+    /// The smallest and largest counts of white's and then black's pieces, in the order of
+    /// [`SIGNATURE_KINDS`].
+    counts: [[(u8, u8); 5]; 2],
+}
+
+impl MaterialSignature {
+    /// The exact material signature of `board`, such as `KRPKR`.
+    pub fn of(board: &ChessBoard) -> Self {
+        let mut counts = [[(0, 0); 5]; 2];
+        for (side, color) in [Color::White, Color::Black].iter().enumerate() {
+            for (i, &kind) in SIGNATURE_KINDS.iter().enumerate() {
+                let count = board.get_bitboard(*color, kind).count_ones() as u8;
+                counts[side][i] = (count, count);
+            }
+        }
+        Self { counts }
+    }
+
+    /// Returns `true` if the pieces of `board` are within the counts of the signature.
+    pub fn matches(&self, board: &ChessBoard) -> bool {
+        let exact = Self::of(board);
+        self.counts
+            .iter()
+            .flatten()
+            .zip(exact.counts.iter().flatten())
+            .all(|(&(min, max), &(count, _))| min <= count && count <= max)
+    }
+}
+
+impl FromStr for MaterialSignature {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid material signature '{}'", s);
+        let black_king = s
+            .strip_prefix('K')
+            .and_then(|rest| rest.find('K'))
+            .ok_or_else(invalid)?
+            + 1;
+        let mut counts = [[(0u8, 0u8); 5]; 2];
+        for (side, pieces) in [&s[1..black_king], &s[black_king + 1..]].iter().enumerate() {
+            let mut chars = pieces.chars().peekable();
+            while let Some(c) = chars.next() {
+                let i = SIGNATURE_KINDS
+                    .iter()
+                    .position(|&kind| Piece::white(kind).fen_char() == c)
+                    .ok_or_else(invalid)?;
+                let (min, max) = match chars.peek().copied() {
+                    Some('+') => {
+                        chars.next();
+                        (1, u8::MAX)
+                    }
+                    Some('*') => {
+                        chars.next();
+                        (0, u8::MAX)
+                    }
+                    Some(digit) if digit.is_ascii_digit() => {
+                        chars.next();
+                        let min = digit.to_digit(10).unwrap() as u8;
+                        if chars.peek() == Some(&'-') {
+                            chars.next();
+                            let max = chars
+                                .next()
+                                .and_then(|digit| digit.to_digit(10))
+                                .ok_or_else(invalid)?;
+                            (min, max as u8)
+                        } else {
+                            (min, min)
+                        }
+                    }
+                    _ => (1, 1),
+                };
+                if min > max {
+                    return Err(invalid());
+                }
+                let count = &mut counts[side][i];
+                *count = (count.0.saturating_add(min), count.1.saturating_add(max));
+            }
+        }
+        Ok(Self { counts })
+    }
+}
+
+impl fmt::Display for MaterialSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for side in &self.counts {
+            write!(f, "K")?;
+            for (&kind, &(min, max)) in SIGNATURE_KINDS.iter().zip(side) {
+                let c = Piece::white(kind).fen_char();
+                match (min, max) {
+                    (0, 0) => {}
+                    (1, 1) => write!(f, "{}", c)?,
+                    (0, u8::MAX) => write!(f, "{}*", c)?,
+                    (1, u8::MAX) => write!(f, "{}+", c)?,
+                    (min, max) if min == max => write!(f, "{}{}", c, min)?,
+                    (min, max) => write!(f, "{}{}-{}", c, min, max)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A piece that must, or must not, stand on one of a set of squares.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Placement {
+    piece: Piece,
+    squares: Bitboard,
+    forbidden: bool,
+}
+
+impl Placement {
+    fn matches(&self, board: &ChessBoard) -> bool {
+        let found =
+            !(board.get_bitboard(self.piece.color(), self.piece.kind()) & self.squares).is_empty();
+        found != self.forbidden
+    }
+}
+
+/// A search for positions, written in the query language described in the [module
+/// documentation](self).
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionQuery {
+    material: Option<MaterialSignature>,
+    placements: Vec<Placement>,
+    turn: Option<Color>,
+    in_check: Option<bool>,
+}
+
+impl PositionQuery {
+    /// Returns `true` if the material and the placement of the pieces of `board` match the
+    /// query, without looking at the player to move or whether they are in check.
+    pub fn matches_board(&self, board: &ChessBoard) -> bool {
+        self.material.iter().all(|material| material.matches(board))
+            && self
+                .placements
+                .iter()
+                .all(|placement| placement.matches(board))
+    }
+
+    /// Returns `true` if the current position of `game` matches the query.
+    pub fn matches(&self, game: &Game) -> bool {
+        self.turn.iter().all(|&turn| turn == game.current_player())
+            && self
+                .in_check
+                .iter()
+                .all(|&in_check| in_check == game.is_in_check())
+            && self.matches_board(&game.board())
+    }
+
+    /// Find the positions of `game` that match the query, from its starting position to its
+    /// current one.
+    ///
+    /// # Returns
+    /// The number of moves made before each matching position, so the starting position is at
+    /// ply 0.
+    pub fn matching_plies(&self, game: &Game) -> Vec<u16> {
+        let mut replay = game.clone();
+        while replay.undo_move().is_some() {}
+        let moves = game.move_history();
+        let mut plies = Vec::new();
+        for ply in 0..=moves.len().min(u16::MAX as usize) {
+            if ply > 0 {
+                replay.make_move(moves[ply - 1]).unwrap();
+            }
+            if self.matches(&replay) {
+                plies.push(ply as u16);
+            }
+        }
+        plies
+    }
+
+    /// Find the matching positions in the games read from a PGN file or a game database.
+    ///
+    /// Games are identified by their position in `records`, starting from 0.
+    ///
+    /// # Returns
+    /// The matching positions in order, and the ids of the games that couldn't be read, with the
+    /// reason.
+    pub fn search<I>(&self, records: I) -> (Vec<PositionHit>, Vec<(u32, String)>)
+    where
+        I: IntoIterator<Item = Result<GameRecord, String>>,
+    {
+        let mut hits = Vec::new();
+        let mut errors = Vec::new();
+        for (game_id, record) in (0..).zip(records) {
+            match record {
+                Ok(record) => hits.extend(
+                    self.matching_plies(record.game())
+                        .into_iter()
+                        .map(|ply| PositionHit::new(game_id, ply)),
+                ),
+                Err(e) => errors.push((game_id, e)),
+            }
+        }
+        (hits, errors)
+    }
+}
+
+impl FromStr for PositionQuery {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut query = Self {
+            material: None,
+            placements: Vec::new(),
+            turn: None,
+            in_check: None,
+        };
+        for term in s.split_whitespace() {
+            let invalid = || format!("invalid query term '{}'", term);
+            if let Some(signature) = term.strip_prefix("material:") {
+                query.material = Some(signature.parse()?);
+                continue;
+            }
+            match term {
+                "turn:white" => query.turn = Some(Color::White),
+                "turn:black" => query.turn = Some(Color::Black),
+                "check" => query.in_check = Some(true),
+                "!check" => query.in_check = Some(false),
+                _ => {
+                    let (forbidden, placement) = match term.strip_prefix('!') {
+                        Some(placement) => (true, placement),
+                        None => (false, term),
+                    };
+                    let mut chars = placement.chars();
+                    let piece = chars.next().and_then(parse_piece).ok_or_else(invalid)?;
+                    let squares = parse_squares(chars.as_str()).ok_or_else(invalid)?;
+                    query.placements.push(Placement {
+                        piece,
+                        squares,
+                        forbidden,
+                    });
+                }
+            }
+        }
+        Ok(query)
+    }
+}
+
+fn parse_piece(c: char) -> Option<Piece> {
+    let kind = PieceType::all_iter()
+        .find(|&kind| Piece::white(kind).fen_char() == c.to_ascii_uppercase())?;
+    Some(if c.is_ascii_uppercase() {
+        Piece::white(kind)
+    } else {
+        Piece::black(kind)
+    })
+}
+
+/// Parse a comma separated list of squares, files and ranks, such as `c6,e` or `7`.
+fn parse_squares(s: &str) -> Option<Bitboard> {
+    let mut squares = Vec::new();
+    for item in s.split(',') {
+        let matching: Vec<Position> = match item.as_bytes() {
+            [b'a'..=b'h'] | [b'1'..=b'8'] => INCREASING_A1_B1
+                .iter()
+                .copied()
+                .filter(|square| square.to_string().contains(item))
+                .collect(),
+            _ => vec![Position::from_str(item).ok()?],
+        };
+        squares.extend(matching);
+    }
+    Some(Bitboard::with_ones(squares))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn::PgnReader;
+
+    fn query(s: &str) -> PositionQuery {
+        s.parse().unwrap()
+    }
+
+    fn position(fen: &str) -> Game {
+        Game::from_fen_string(fen).unwrap()
+    }
+
+    #[test]
+    fn material() {
+        let board = position("8/8/4k3/8/3PP3/8/r7/1R2K3 w - - 0 1").board();
+        assert_eq!(MaterialSignature::of(&board).to_string(), "KRP2KR");
+        for signature in ["KRP2KR", "KRP+KR", "KRP*KR", "KRP1-3KR*", "KRPPKR"] {
+            let signature: MaterialSignature = signature.parse().unwrap();
+            assert!(signature.matches(&board), "{}", signature);
+        }
+        for signature in ["KRPKR", "KRP2K", "KRKR", "KQP2KR"] {
+            let signature: MaterialSignature = signature.parse().unwrap();
+            assert!(!signature.matches(&board), "{}", signature);
+        }
+        for signature in ["RKR", "KRP", "KRXKR", "KRP3-1KR", "KRP-2KR"] {
+            assert!(
+                signature.parse::<MaterialSignature>().is_err(),
+                "{}",
+                signature
+            );
+        }
+        assert_eq!(
+            "KRP1-3KR*"
+                .parse::<MaterialSignature>()
+                .unwrap()
+                .to_string(),
+            "KRP1-3KR*"
+        );
+    }
+
+    #[test]
+    fn placements() {
+        let game = position("4k3/8/2p1p3/3N4/8/8/4P3/4K3 w - - 0 1");
+        let board = game.board();
+        for s in [
+            "Nd5 pc6 pe6",
+            "pc6,h7",
+            "Pe",
+            "P2",
+            "!Pd",
+            "!nd5",
+            "Ke1 ke8",
+            "",
+        ] {
+            assert!(query(s).matches_board(&board), "{}", s);
+        }
+        for s in ["Nd4", "pd6", "!Pe", "Bd5", "p5"] {
+            assert!(!query(s).matches_board(&board), "{}", s);
+        }
+        for s in ["Xd5", "N", "Ni9", "Nd5,", "material:KK:", "turn:red"] {
+            assert!(s.parse::<PositionQuery>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn turn_and_check() {
+        let game = position("4k3/8/8/8/8/8/8/R3K3 b - - 0 1");
+        assert!(query("turn:black !check").matches(&game));
+        assert!(!query("turn:white").matches(&game));
+        assert!(!query("check").matches(&game));
+
+        let check = position("4k3/8/8/8/8/8/8/4RK2 b - - 0 1");
+        assert!(query("check material:KRK").matches(&check));
+    }
+
+    #[test]
+    fn search() {
+        let pgn = r#"[Round "1"]
+1. e4 d5 2. exd5 Qxd5 3. Nc3 *
+
+[Round "2"]
+1. e4 ?? *
+
+[Round "3"]
+[FEN "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"]
+1. e4 Kd7 2. e5 *
+"#;
+        let (hits, errors) = query("material:KP*K Pe4").search(PgnReader::new(pgn.as_bytes()));
+        let hits: Vec<(u32, u16)> = hits.iter().map(|hit| (hit.game_id(), hit.ply())).collect();
+        assert_eq!(hits, vec![(2, 1), (2, 2)]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, 1);
+
+        let (hits, _) = query("qd5 turn:white").search(PgnReader::new(pgn.as_bytes()));
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].game_id(), hits[0].ply()), (0, 4));
+    }
+}
@@ -0,0 +1,447 @@
+use crate::{
+    chess_board::ChessBoard,
+    chess_move::{ChessMove, MoveManager, PromotionPiece},
+    piece::PieceType,
+    Color, Piece,
+};
+use bitboard::*;
+
+/// A move that could have been the last one made before a position, together with the position
+/// it was made from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetroMove {
+    chess_move: ChessMove,
+    player: Color,
+    captured: Option<Piece>,
+    board: ChessBoard,
+    en_passant_target: Option<Position>,
+}
+
+impl RetroMove {
+    /// The move, as made in the predecessor position.
+    pub fn chess_move(&self) -> ChessMove {
+        self.chess_move
+    }
+
+    /// The player who made the move, who is to move in the predecessor position.
+    pub fn player(&self) -> Color {
+        self.player
+    }
+
+    /// The piece captured by the move, which the retro-move puts back on the board.
+    pub fn captured(&self) -> Option<Piece> {
+        self.captured
+    }
+
+    /// The board of the predecessor position.
+    ///
+    /// For un-castling, the predecessor must also have had the right to castle on that side.
+    pub fn board(&self) -> ChessBoard {
+        self.board
+    }
+
+    /// The en passant target the predecessor position must have had, which is only the case
+    /// when un-making an en passant capture.
+    pub fn en_passant_target(&self) -> Option<Position> {
+        self.en_passant_target
+    }
+}
+
+/// Generate every move that could have led to `board`, with `side_to_move` to move.
+///
+/// The moves are made by the opponent of `side_to_move`, and include un-captures of every piece
+/// type, un-promotions, un-castling and un-en-passant. A predecessor is only included if it is
+/// legal: `side_to_move` may not be in check in it, and neither side may have more than 8 pawns
+/// or 16 pieces.
+///
+/// # Returns
+/// Every retro-move, or an empty list if `board` can't be reached because the player who just
+/// moved is in check or a king is missing.
+pub fn retro_moves(board: &ChessBoard, side_to_move: Color) -> Vec<RetroMove> {
+    let mut generator = RetroMoveGenerator {
+        board,
+        player: side_to_move.opponent(),
+        move_manager: MoveManager::default(),
+        retro_moves: Vec::new(),
+    };
+    if board.get_bitboard(Color::White, PieceType::King) == Bitboard::empty()
+        || board.get_bitboard(Color::Black, PieceType::King) == Bitboard::empty()
+        || generator.move_manager.is_in_check(board, generator.player)
+    {
+        return Vec::new();
+    }
+
+    for to in board.get_occupancy_for_color(generator.player).positions() {
+        match board.get_piece(to).unwrap().kind() {
+            PieceType::Pawn => generator.pawn_retro_moves(to),
+            kind => generator.piece_retro_moves(to, kind),
+        }
+    }
+    generator.castle_retro_moves();
+    generator.retro_moves
+}
+
+struct RetroMoveGenerator<'a> {
+    board: &'a ChessBoard,
+    player: Color,
+    move_manager: MoveManager,
+    retro_moves: Vec<RetroMove>,
+}
+
+impl RetroMoveGenerator<'_> {
+    fn is_empty(&self, pos: Position) -> bool {
+        !self.board.has_piece_at(pos)
+    }
+
+    fn piece_retro_moves(&mut self, to: Position, kind: PieceType) {
+        let occupancy = self.board.full_occupancy();
+        let sources = match kind {
+            PieceType::Knight => Bitboard::knight_targets(to),
+            PieceType::Bishop => Bitboard::bishop_targets(to, occupancy),
+            PieceType::Rook => Bitboard::rook_targets(to, occupancy),
+            PieceType::Queen => Bitboard::queen_targets(to, occupancy),
+            PieceType::King => Bitboard::king_targets(to),
+            PieceType::Pawn => unreachable!("pawns are handled separately"),
+        } & !occupancy;
+        for from in sources.positions() {
+            for captured in uncaptures(to, true) {
+                self.push(ChessMove::Regular { from, to }, captured, to, None);
+            }
+        }
+
+        let promotion_rank = match self.player {
+            Color::White => Rank::Eight,
+            Color::Black => Rank::One,
+        };
+        if to.rank() != promotion_rank || kind == PieceType::King {
+            return;
+        }
+        let piece = match kind {
+            PieceType::Knight => PromotionPiece::Knight,
+            PieceType::Bishop => PromotionPiece::Bishop,
+            PieceType::Rook => PromotionPiece::Rook,
+            _ => PromotionPiece::Queen,
+        };
+        let (behind, behind_left, behind_right) = match self.player {
+            Color::White => (to.down(), to.down_left(), to.down_right()),
+            Color::Black => (to.up(), to.up_left(), to.up_right()),
+        };
+        if let Some(from) = behind.filter(|&from| self.is_empty(from)) {
+            self.push(ChessMove::Promotion { from, to, piece }, None, to, None);
+        }
+        for from in [behind_left, behind_right].iter().flatten().copied() {
+            if self.is_empty(from) {
+                // a pawn can't be captured on the last rank
+                for captured in uncaptures(to, false).into_iter().skip(1) {
+                    self.push(ChessMove::Promotion { from, to, piece }, captured, to, None);
+                }
+            }
+        }
+    }
+
+    fn pawn_retro_moves(&mut self, to: Position) {
+        let (start_rank, double_push_rank, en_passant_rank) = match self.player {
+            Color::White => (Rank::Two, Rank::Four, Rank::Six),
+            Color::Black => (Rank::Seven, Rank::Five, Rank::Three),
+        };
+        if to.rank() == start_rank {
+            return;
+        }
+        let (behind, behind_left, behind_right, ahead) = match self.player {
+            Color::White => (to.down(), to.down_left(), to.down_right(), to.up()),
+            Color::Black => (to.up(), to.up_left(), to.up_right(), to.down()),
+        };
+        let (behind, ahead) = match (behind, ahead) {
+            (Some(behind), Some(ahead)) => (behind, ahead),
+            // pawns never stand on the first or last rank
+            _ => return,
+        };
+
+        if self.is_empty(behind) {
+            self.push(ChessMove::Regular { from: behind, to }, None, to, None);
+            if to.rank() == double_push_rank {
+                let from = match self.player {
+                    Color::White => behind.down(),
+                    Color::Black => behind.up(),
+                }
+                .unwrap();
+                if self.is_empty(from) {
+                    self.push(ChessMove::Regular { from, to }, None, to, None);
+                }
+            }
+        }
+
+        for from in [behind_left, behind_right].iter().flatten().copied() {
+            if !self.is_empty(from) {
+                continue;
+            }
+            // pawns only move diagonally when capturing
+            for captured in uncaptures(to, true).into_iter().skip(1) {
+                self.push(ChessMove::Regular { from, to }, captured, to, None);
+            }
+
+            // the captured pawn passed over `to` on a double push, so the squares it came from
+            // and went to must both be empty now
+            if to.rank() == en_passant_rank && self.is_empty(ahead) && self.is_empty(behind) {
+                let en_passant = ChessMove::EnPassant {
+                    from,
+                    to,
+                    taken_original_index: ahead,
+                    taken_index: behind,
+                };
+                self.push(en_passant, Some(PieceType::Pawn), behind, Some(to));
+            }
+        }
+    }
+
+    fn castle_retro_moves(&mut self) {
+        let (rank, king) = match self.player {
+            Color::White => (Rank::One, Piece::king(Color::White)),
+            Color::Black => (Rank::Eight, Piece::king(Color::Black)),
+        };
+        let rook = Piece::rook(self.player);
+        let square = |file| Position::new(file, rank);
+
+        // the king may not castle out of or through check
+        let castles = [
+            (
+                File::H,
+                File::F,
+                File::G,
+                vec![File::E, File::F],
+                vec![File::E, File::H],
+            ),
+            (
+                File::A,
+                File::D,
+                File::C,
+                vec![File::E, File::D],
+                vec![File::E, File::A, File::B],
+            ),
+        ];
+        for (rook_from, rook_to, king_to, safe, empty) in castles.iter() {
+            if self.board.get_piece(square(*king_to)) != Some(king)
+                || self.board.get_piece(square(*rook_to)) != Some(rook)
+                || empty.iter().any(|&file| !self.is_empty(square(file)))
+            {
+                continue;
+            }
+            let castle = ChessMove::Castle {
+                rook_from: square(*rook_from),
+                rook_to: square(*rook_to),
+                king_from: square(File::E),
+                king_to: square(*king_to),
+            };
+            let previous = unmake(self.board, self.player, castle, None);
+            let attacked = safe.iter().any(|&file| {
+                self.move_manager
+                    .is_under_attack(&previous, square(file), self.player.opponent())
+            });
+            if !attacked {
+                self.push(castle, None, square(*king_to), None);
+            }
+        }
+    }
+
+    /// Add the retro-move if its predecessor position is legal.
+    fn push(
+        &mut self,
+        chess_move: ChessMove,
+        captured: Option<PieceType>,
+        captured_at: Position,
+        en_passant_target: Option<Position>,
+    ) {
+        let opponent = self.player.opponent();
+        let captured = captured.map(|kind| Piece::new(opponent, kind));
+        let previous = unmake(
+            self.board,
+            self.player,
+            chess_move,
+            captured.map(|piece| (captured_at, piece)),
+        );
+        if has_plausible_material(&previous) && !self.move_manager.is_in_check(&previous, opponent)
+        {
+            self.retro_moves.push(RetroMove {
+                chess_move,
+                player: self.player,
+                captured,
+                board: previous,
+                en_passant_target,
+            });
+        }
+    }
+}
+
+/// The piece types that can be put back on `to` by an un-capture, starting with no piece at
+/// all.
+fn uncaptures(to: Position, pawns: bool) -> Vec<Option<PieceType>> {
+    let pawns = pawns && to.rank() != Rank::One && to.rank() != Rank::Eight;
+    let mut uncaptures = vec![None];
+    uncaptures.extend(
+        PieceType::all_iter()
+            .filter(|&kind| kind != PieceType::King && (pawns || kind != PieceType::Pawn))
+            .map(Some),
+    );
+    uncaptures
+}
+
+/// Get the board before `player` made `chess_move` on `board`.
+fn unmake(
+    board: &ChessBoard,
+    player: Color,
+    chess_move: ChessMove,
+    captured: Option<(Position, Piece)>,
+) -> ChessBoard {
+    let mut previous = *board;
+    match chess_move {
+        ChessMove::Regular { from, to } | ChessMove::EnPassant { from, to, .. } => {
+            let piece = previous.take_piece(to).unwrap();
+            previous.set_piece(from, piece);
+        }
+        ChessMove::Promotion { from, to, .. } => {
+            previous.take_piece(to);
+            previous.set_piece(from, Piece::pawn(player));
+        }
+        ChessMove::Castle {
+            rook_from,
+            rook_to,
+            king_from,
+            king_to,
+        } => {
+            let king = previous.take_piece(king_to).unwrap();
+            previous.set_piece(king_from, king);
+            let rook = previous.take_piece(rook_to).unwrap();
+            previous.set_piece(rook_from, rook);
+        }
+    }
+    if let Some((pos, piece)) = captured {
+        previous.set_piece(pos, piece);
+    }
+    previous
+}
+
+fn has_plausible_material(board: &ChessBoard) -> bool {
+    [Color::White, Color::Black].iter().all(|&color| {
+        board.get_occupancy_for_color(color).positions().len() <= 16
+            && board.get_bitboard(color, PieceType::Pawn).positions().len() <= 8
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Game;
+
+    fn retro_moves_of(fen: &str) -> Vec<RetroMove> {
+        let game = Game::from_fen_string(fen).unwrap();
+        retro_moves(&game.board(), game.current_player())
+    }
+
+    #[test]
+    fn start_position() {
+        let retro_moves =
+            retro_moves_of("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        let mut moves: Vec<String> = retro_moves
+            .iter()
+            .map(|retro_move| {
+                assert_eq!(retro_move.player(), Color::Black);
+                assert_eq!(retro_move.captured(), None);
+                retro_move.chess_move().to_uci_string()
+            })
+            .collect();
+        moves.sort();
+        assert_eq!(moves, vec!["a6b8", "c6b8", "f6g8", "h6g8"]);
+    }
+
+    #[test]
+    fn un_en_passant() {
+        let retro_moves = retro_moves_of("4k3/8/3P4/8/8/8/8/4K3 b - - 0 1");
+        let en_passant = retro_moves
+            .iter()
+            .find(|retro_move| {
+                retro_move.chess_move()
+                    == ChessMove::EnPassant {
+                        from: E5,
+                        to: D6,
+                        taken_original_index: D7,
+                        taken_index: D5,
+                    }
+            })
+            .unwrap();
+        assert_eq!(en_passant.en_passant_target(), Some(D6));
+        assert_eq!(en_passant.captured(), Some(Piece::pawn(Color::Black)));
+        assert_eq!(
+            en_passant.board().get_piece(D5),
+            Some(Piece::pawn(Color::Black))
+        );
+        assert_eq!(
+            en_passant.board().get_piece(E5),
+            Some(Piece::pawn(Color::White))
+        );
+        assert!(!en_passant.board().has_piece_at(D6));
+
+        // a push, two ordinary captures of each piece type, and two en passant captures
+        let pawn_moves = retro_moves
+            .iter()
+            .filter(|retro_move| retro_move.chess_move().to() == D6)
+            .count();
+        assert_eq!(pawn_moves, 1 + 2 * 5 + 2);
+    }
+
+    #[test]
+    fn un_promotion() {
+        let retro_moves = retro_moves_of("3Q3k/8/8/8/8/8/8/K7 b - - 0 1");
+        let promotions: Vec<&RetroMove> = retro_moves
+            .iter()
+            .filter(|retro_move| retro_move.chess_move().is_promotion())
+            .collect();
+        // a push, and captures of a knight, bishop, rook or queen from either side
+        assert_eq!(promotions.len(), 1 + 2 * 4);
+        assert!(promotions.iter().all(|retro_move| {
+            retro_move.board().get_piece(retro_move.chess_move().from())
+                == Some(Piece::pawn(Color::White))
+        }));
+    }
+
+    #[test]
+    fn un_castling() {
+        let castle = ChessMove::Castle {
+            rook_from: H1,
+            rook_to: F1,
+            king_from: E1,
+            king_to: G1,
+        };
+        let retro_moves = retro_moves_of("4k3/8/8/8/8/8/8/5RK1 b - - 0 1");
+        let un_castle = retro_moves
+            .iter()
+            .find(|retro_move| retro_move.chess_move() == castle)
+            .unwrap();
+        assert_eq!(
+            un_castle.board().get_piece(E1),
+            Some(Piece::king(Color::White))
+        );
+        assert_eq!(
+            un_castle.board().get_piece(H1),
+            Some(Piece::rook(Color::White))
+        );
+
+        // the king would have castled out of check
+        let retro_moves = retro_moves_of("4r1k1/8/8/8/8/8/8/5RK1 b - - 0 1");
+        assert!(retro_moves
+            .iter()
+            .all(|retro_move| retro_move.chess_move() != castle));
+    }
+
+    #[test]
+    fn predecessors_are_legal() {
+        // the white king can't have moved into check
+        assert!(retro_moves_of("4k3/8/8/8/8/8/8/4K2r b - - 0 1").is_empty());
+
+        let retro_moves = retro_moves_of("4k3/8/8/8/8/8/3Pn3/4K3 b - - 0 1");
+        assert!(!retro_moves.is_empty());
+        let move_manager = MoveManager::default();
+        for retro_move in retro_moves {
+            assert!(!move_manager.is_in_check(&retro_move.board(), Color::Black));
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use crate::{
+    chess_move::{ChessMove, PromotionPiece},
+    game::Game,
+    move_info::CheckKind,
+    piece::PieceType,
+    Color, Piece,
+};
+use bitboard::*;
+use std::str::FromStr;
+
+/// Write a legal move of the current player of `game` in standard algebraic notation.
+pub(crate) fn to_san(game: &Game, chess_move: ChessMove) -> Result<String, &'static str> {
+    if !game.get_moves().contains(&chess_move) {
+        return Err("illegal move");
+    }
+    let board = game.board();
+    let (from, to) = (chess_move.from(), chess_move.to());
+    let mut san = String::new();
+    if let ChessMove::Castle { king_to, .. } = chess_move {
+        san.push_str(if king_to.file() == File::G {
+            "O-O"
+        } else {
+            "O-O-O"
+        });
+    } else {
+        let kind = board.get_piece(from).unwrap().kind();
+        let capture = chess_move.is_en_passant() || board.get_piece(to).is_some();
+        let from_square = from.to_string();
+        if kind == PieceType::Pawn {
+            if capture {
+                san.push_str(&from_square[..1]);
+            }
+        } else {
+            san.push(Piece::white(kind).fen_char());
+            // the other pieces of the same kind that can move to the same square
+            let others: Vec<Position> = game
+                .get_moves()
+                .iter()
+                .filter(|other| {
+                    !other.is_castle()
+                        && other.to() == to
+                        && other.from() != from
+                        && board.get_piece(other.from()).unwrap().kind() == kind
+                })
+                .map(|other| other.from())
+                .collect();
+            if others.iter().all(|other| other.file() != from.file()) {
+                if !others.is_empty() {
+                    san.push_str(&from_square[..1]);
+                }
+            } else if others.iter().all(|other| other.rank() != from.rank()) {
+                san.push_str(&from_square[1..]);
+            } else {
+                san.push_str(&from_square);
+            }
+        }
+        if capture {
+            san.push('x');
+        }
+        san.push_str(&to.to_string());
+        if let ChessMove::Promotion { piece, .. } = chess_move {
+            san.push('=');
+            san.push(piece.create_piece(Color::White).fen_char());
+        }
+    }
+
+    let mut after = game.clone();
+    match after.make_move(chess_move)?.check() {
+        Some(CheckKind::Checkmate) => san.push('#'),
+        Some(_) => san.push('+'),
+        None => {}
+    }
+    Ok(san)
+}
+
+/// Find the legal move of the current player of `game` written in standard algebraic notation.
+///
+/// Check and annotation suffixes are ignored, the `=` before a promotion piece may be left out,
+/// and castling may be written with zeros.
+pub(crate) fn parse_san(game: &Game, san: &str) -> Result<ChessMove, String> {
+    let invalid = || format!("invalid move '{}'", san);
+    let illegal = || format!("illegal move '{}'", san);
+    let trimmed = san.trim_end_matches(|c| "+#!?".contains(c));
+    if !trimmed.is_ascii() {
+        return Err(invalid());
+    }
+
+    let castle = match trimmed {
+        "O-O" | "0-0" => Some(File::G),
+        "O-O-O" | "0-0-0" => Some(File::C),
+        _ => None,
+    };
+    if let Some(file) = castle {
+        return game
+            .get_moves()
+            .iter()
+            .find(|chess_move| {
+                matches!(chess_move, ChessMove::Castle { king_to, .. } if king_to.file() == file)
+            })
+            .copied()
+            .ok_or_else(illegal);
+    }
+
+    let (kind, rest) = match trimmed.chars().next() {
+        Some('N') => (PieceType::Knight, &trimmed[1..]),
+        Some('B') => (PieceType::Bishop, &trimmed[1..]),
+        Some('R') => (PieceType::Rook, &trimmed[1..]),
+        Some('Q') => (PieceType::Queen, &trimmed[1..]),
+        Some('K') => (PieceType::King, &trimmed[1..]),
+        _ => (PieceType::Pawn, trimmed),
+    };
+    let promotion_piece = |c| match c {
+        'N' => Some(PromotionPiece::Knight),
+        'B' => Some(PromotionPiece::Bishop),
+        'R' => Some(PromotionPiece::Rook),
+        'Q' => Some(PromotionPiece::Queen),
+        _ => None,
+    };
+    let (rest, promotion) = match rest.chars().last().and_then(promotion_piece) {
+        Some(piece) if kind == PieceType::Pawn => {
+            let rest = &rest[..rest.len() - 1];
+            (rest.strip_suffix('=').unwrap_or(rest), Some(piece))
+        }
+        _ => (rest, None),
+    };
+    let rest: String = rest.chars().filter(|&c| c != 'x').collect();
+    if rest.len() < 2 {
+        return Err(invalid());
+    }
+    let (hint, to) = rest.split_at(rest.len() - 2);
+    let to = Position::from_str(to).map_err(|_| invalid())?;
+    let (mut from_file, mut from_rank) = (None, None);
+    for c in hint.chars() {
+        match c {
+            'a'..='h' if from_file.is_none() && from_rank.is_none() => from_file = Some(c),
+            '1'..='8' if from_rank.is_none() => from_rank = Some(c),
+            _ => return Err(invalid()),
+        }
+    }
+
+    let board = game.board();
+    let mut candidates = game.get_moves().iter().filter(|chess_move| {
+        let from = chess_move.from().to_string();
+        let move_promotion = match chess_move {
+            ChessMove::Promotion { piece, .. } => Some(*piece),
+            _ => None,
+        };
+        !chess_move.is_castle()
+            && chess_move.to() == to
+            && board.get_piece(chess_move.from()).unwrap().kind() == kind
+            && move_promotion == promotion
+            && from_file.iter().all(|&file| from.starts_with(file))
+            && from_rank.iter().all(|&rank| from.ends_with(rank))
+    });
+    match (candidates.next(), candidates.next()) {
+        (Some(&chess_move), None) => Ok(chess_move),
+        (Some(_), Some(_)) => Err(format!("ambiguous move '{}'", san)),
+        (None, _) => Err(illegal()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn san_moves(game: &Game) -> Vec<String> {
+        let mut moves: Vec<String> = game
+            .get_moves()
+            .iter()
+            .map(|&chess_move| to_san(game, chess_move).unwrap())
+            .collect();
+        moves.sort();
+        moves
+    }
+
+    #[test]
+    fn write_san() {
+        let game = Game::from_fen_string("1n2k3/P7/8/8/8/2N3N1/8/R3K2R w KQ - 0 1").unwrap();
+        let moves = san_moves(&game);
+        for san in [
+            "O-O", "O-O-O", "Nce4", "Nge4", "a8=Q", "axb8=N", "Rh8+", "Kf1", "Rd1",
+        ] {
+            assert!(
+                moves.contains(&san.to_string()),
+                "{} not in {:?}",
+                san,
+                moves
+            );
+        }
+
+        let game = Game::from_fen_string("6k1/5ppp/8/8/8/R7/8/R5K1 w - - 0 1").unwrap();
+        let moves = san_moves(&game);
+        assert!(moves.contains(&"R3a2".to_string()));
+        assert!(moves.contains(&"Ra8#".to_string()));
+        assert!(to_san(&game, ChessMove::Regular { from: A3, to: H3 }).is_ok());
+        assert!(to_san(&game, ChessMove::Regular { from: A3, to: A1 }).is_err());
+    }
+
+    #[test]
+    fn read_san() {
+        let game = Game::from_fen_string("1n2k3/P7/8/8/8/2N3N1/8/R3K2R w KQ - 0 1").unwrap();
+        for chess_move in game.get_moves() {
+            let san = to_san(&game, *chess_move).unwrap();
+            assert_eq!(parse_san(&game, &san), Ok(*chess_move));
+        }
+        assert_eq!(parse_san(&game, "0-0-0"), parse_san(&game, "O-O-O"));
+        assert_eq!(parse_san(&game, "axb8Q"), parse_san(&game, "axb8=Q"));
+        assert_eq!(parse_san(&game, "Nc3e4!?"), parse_san(&game, "Nce4"));
+        assert_eq!(
+            parse_san(&game, "Ne4"),
+            Err("ambiguous move 'Ne4'".to_string())
+        );
+        assert_eq!(
+            parse_san(&game, "Nb6"),
+            Err("illegal move 'Nb6'".to_string())
+        );
+        assert!(parse_san(&game, "a8").is_err());
+        assert!(parse_san(&game, "Zz9").is_err());
+    }
+}
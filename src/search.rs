@@ -0,0 +1,473 @@
+use crate::{chess_move::ChessMove, game::Game, piece::PieceType, Color};
+use bitboard::Position;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// Score of a position where the side to move has been checkmated.
+///
+/// Mates found closer to the root are given scores further from zero.
+pub const MATE_SCORE: i32 = 100_000;
+
+const INFINITY: i32 = MATE_SCORE + 1;
+
+/// Any score with an absolute value above this is a forced mate.
+const MATE_THRESHOLD: i32 = MATE_SCORE - 1_000;
+
+/// Safety margin used when deciding if a capture can possibly raise alpha in quiescence search.
+const DELTA_MARGIN: i32 = 200;
+
+/// How many nodes to visit between checks of the stop signal and deadline.
+///
+/// Must be a power of two, so the check can mask the node count.
+const STOP_CHECK_INTERVAL: u64 = 256;
+
+/// Number of moves assumed to be left in the game when the time control doesn't say.
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+
+/// Time kept in reserve on the clock, to account for communication overhead.
+const MOVE_OVERHEAD: Duration = Duration::from_millis(50);
+
+/// Statically evaluate a position from the perspective of the current player, in centipawns.
+pub fn evaluate(game: &Game) -> i32 {
+    let board = game.board();
+    let player = game.current_player();
+    let mut score = 0;
+    for kind in PieceType::all_iter().filter(|&kind| kind != PieceType::King) {
+        let own = board.get_bitboard(player, kind).count_ones() as i32;
+        let opponent = board.get_bitboard(player.opponent(), kind).count_ones() as i32;
+        score += (own - opponent) * kind.value();
+    }
+    score
+}
+
+/// Returns `true` if `score` means that one of the players can force mate.
+pub fn is_mate_score(score: i32) -> bool {
+    score.abs() > MATE_THRESHOLD
+}
+
+/// Decide how long to search for a move, given the time left on the clock, the increment
+/// gained after the move and, if known, the number of moves left until the next time control.
+pub(crate) fn allocate_time(
+    time_left: Duration,
+    increment: Duration,
+    moves_to_go: Option<u32>,
+) -> Duration {
+    let moves_to_go = moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+    let budget = (time_left / moves_to_go).saturating_add(increment / 2);
+    let available = time_left
+        .checked_sub(MOVE_OVERHEAD)
+        .unwrap_or_else(|| Duration::from_millis(1));
+    budget.min(available)
+}
+
+/// The outcome of a search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchResult {
+    best_move: Option<ChessMove>,
+    score: i32,
+    depth: u32,
+    nodes: u64,
+}
+
+impl SearchResult {
+    /// The best move found, or `None` if the current player has no legal moves.
+    pub fn best_move(&self) -> Option<ChessMove> {
+        self.best_move
+    }
+
+    /// The score of the best move from the perspective of the current player, in centipawns.
+    pub fn score(&self) -> i32 {
+        self.score
+    }
+
+    /// The deepest completed iteration.
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// The number of positions visited, including positions visited by quiescence search.
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+}
+
+/// An iterative deepening alpha-beta search with a quiescence search at the horizon.
+///
+/// A search can be cut short by a stop signal or a deadline. When that happens the result of
+/// the deepest completed iteration is returned.
+#[derive(Debug, Clone)]
+pub struct Search {
+    max_depth: u32,
+    quiescence_checks: bool,
+    stop_signal: Option<Arc<AtomicBool>>,
+    deadline: Option<Instant>,
+    nodes: u64,
+    aborted: bool,
+}
+
+impl Search {
+    /// Create a search that looks `max_depth` plies ahead before resolving captures.
+    pub fn new(max_depth: u32) -> Self {
+        Self {
+            max_depth,
+            quiescence_checks: false,
+            stop_signal: None,
+            deadline: None,
+            nodes: 0,
+            aborted: false,
+        }
+    }
+
+    /// Also search quiet checking moves in the first ply of quiescence search.
+    pub fn with_quiescence_checks(mut self, quiescence_checks: bool) -> Self {
+        self.quiescence_checks = quiescence_checks;
+        self
+    }
+
+    /// Stop the search as soon as possible after `stop_signal` is set to `true`.
+    pub fn with_stop_signal(mut self, stop_signal: Arc<AtomicBool>) -> Self {
+        self.stop_signal = Some(stop_signal);
+        self
+    }
+
+    /// Stop the search as soon as possible after `deadline` has passed.
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Search for the best move for the current player of `game`.
+    pub fn run(&mut self, game: &Game) -> SearchResult {
+        self.run_with_callback(game, |_| {})
+    }
+
+    /// Search for the best move for the current player of `game`, calling `on_iteration` with
+    /// the result of every completed iteration.
+    pub fn run_with_callback<F>(&mut self, game: &Game, mut on_iteration: F) -> SearchResult
+    where
+        F: FnMut(&SearchResult),
+    {
+        self.nodes = 0;
+        self.aborted = false;
+        let mut result = SearchResult {
+            best_move: None,
+            score: self.terminal_score(game, 0),
+            depth: 0,
+            nodes: 0,
+        };
+        if game.is_over() {
+            return result;
+        }
+
+        for depth in 1..=self.max_depth.max(1) {
+            if depth > 1 && self.check_stop() {
+                break;
+            }
+            let (best_move, score) = self.root(game, depth, result.best_move);
+            if self.aborted {
+                if result.best_move.is_none() {
+                    // the first iteration was cut short, but some move must be returned
+                    result.best_move = Some(best_move);
+                    result.nodes = self.nodes;
+                }
+                break;
+            }
+            result = SearchResult {
+                best_move: Some(best_move),
+                score,
+                depth,
+                nodes: self.nodes,
+            };
+            on_iteration(&result);
+            if is_mate_score(score) {
+                break;
+            }
+        }
+
+        result
+    }
+
+    /// Resolve captures and promotions until the position is quiet, and return the score of
+    /// the position from the perspective of the current player.
+    ///
+    /// The current player may "stand pat" and decline to capture, and captures that can't
+    /// raise the score above `alpha`, even with a margin, are not searched.
+    pub fn quiescence(&mut self, game: &Game, alpha: i32, beta: i32) -> i32 {
+        self.quiescence_from(game, alpha, beta, 0, 0)
+    }
+
+    fn root(
+        &mut self,
+        game: &Game,
+        depth: u32,
+        previous_best: Option<ChessMove>,
+    ) -> (ChessMove, i32) {
+        let mut moves = ordered_moves(game, game.get_moves().iter().copied().collect());
+        if let Some(previous_best) = previous_best {
+            if let Some(index) = moves.iter().position(|&m| m == previous_best) {
+                let chess_move = moves.remove(index);
+                moves.insert(0, chess_move);
+            }
+        }
+
+        let mut alpha = -INFINITY;
+        let mut best_move = moves[0];
+        for chess_move in moves {
+            let mut child = game.clone();
+            child.make_move(chess_move).unwrap();
+            let score = -self.negamax(&child, depth - 1, 1, -INFINITY, -alpha);
+            if self.aborted {
+                break;
+            }
+            if score > alpha {
+                alpha = score;
+                best_move = chess_move;
+            }
+        }
+
+        (best_move, alpha)
+    }
+
+    fn negamax(&mut self, game: &Game, depth: u32, ply: u32, mut alpha: i32, beta: i32) -> i32 {
+        if game.is_over() || game.move_manager().half_moves() >= 100 {
+            self.nodes += 1;
+            return self.terminal_score(game, ply);
+        }
+        if depth == 0 {
+            return self.quiescence_from(game, alpha, beta, ply, 0);
+        }
+        self.nodes += 1;
+        if self.should_stop() {
+            return 0;
+        }
+
+        let moves = ordered_moves(game, game.get_moves().iter().copied().collect());
+        for chess_move in moves {
+            let mut child = game.clone();
+            child.make_move(chess_move).unwrap();
+            let score = -self.negamax(&child, depth - 1, ply + 1, -beta, -alpha);
+            if score >= beta {
+                return score;
+            }
+            if score > alpha {
+                alpha = score;
+            }
+        }
+
+        alpha
+    }
+
+    fn quiescence_from(
+        &mut self,
+        game: &Game,
+        mut alpha: i32,
+        beta: i32,
+        ply: u32,
+        quiescence_ply: u32,
+    ) -> i32 {
+        self.nodes += 1;
+        if self.should_stop() {
+            return 0;
+        }
+
+        if game.is_in_check() {
+            // standing pat is not an option when in check, so every evasion is searched
+            let mut game = game.clone();
+            game.evaluate_legal_moves();
+            if game.is_over() {
+                return -MATE_SCORE + ply as i32;
+            }
+            let evasions = ordered_moves(&game, game.get_moves().iter().copied().collect());
+            for chess_move in evasions {
+                let mut child = game.clone();
+                child.make_move_unevaluated(chess_move);
+                let score =
+                    -self.quiescence_from(&child, -beta, -alpha, ply + 1, quiescence_ply + 1);
+                if score >= beta {
+                    return score;
+                }
+                if score > alpha {
+                    alpha = score;
+                }
+            }
+            return alpha;
+        }
+
+        let stand_pat = evaluate(game);
+        if stand_pat >= beta {
+            return stand_pat;
+        }
+        if stand_pat + PieceType::Queen.value() + DELTA_MARGIN < alpha {
+            // not even winning a queen would be enough
+            return alpha;
+        }
+        if stand_pat > alpha {
+            alpha = stand_pat;
+        }
+
+        let include_checks = self.quiescence_checks && quiescence_ply == 0;
+        let moves = ordered_moves(game, game.get_tactical_moves(include_checks));
+        let board = game.board();
+        for chess_move in moves {
+            if !chess_move.is_promotion() {
+                let taken_value = match chess_move {
+                    ChessMove::EnPassant { .. } => PieceType::Pawn.value(),
+                    _ => board
+                        .get_piece(chess_move.to())
+                        .map(|p| p.kind().value())
+                        .unwrap_or(0),
+                };
+                if taken_value > 0 && stand_pat + taken_value + DELTA_MARGIN <= alpha {
+                    continue;
+                }
+                if game.move_manager().static_exchange_evaluation(
+                    &board,
+                    game.current_player(),
+                    chess_move,
+                ) < 0
+                {
+                    continue;
+                }
+            }
+
+            let mut child = game.clone();
+            child.make_move_unevaluated(chess_move);
+            let score = -self.quiescence_from(&child, -beta, -alpha, ply + 1, quiescence_ply + 1);
+            if score >= beta {
+                return score;
+            }
+            if score > alpha {
+                alpha = score;
+            }
+        }
+
+        alpha
+    }
+
+    /// Check the stop signal and the deadline every few nodes.
+    fn should_stop(&mut self) -> bool {
+        if !self.aborted && self.nodes & (STOP_CHECK_INTERVAL - 1) == 0 {
+            self.check_stop();
+        }
+        self.aborted
+    }
+
+    fn check_stop(&mut self) -> bool {
+        let stopped = self
+            .stop_signal
+            .as_ref()
+            .map(|stop_signal| stop_signal.load(Ordering::Relaxed))
+            .unwrap_or(false);
+        let timed_out = self
+            .deadline
+            .map(|deadline| Instant::now() >= deadline)
+            .unwrap_or(false);
+        self.aborted = self.aborted || stopped || timed_out;
+        self.aborted
+    }
+
+    fn terminal_score(&self, game: &Game, ply: u32) -> i32 {
+        match game.game_result() {
+            Some(_) if game.is_in_check() => -MATE_SCORE + ply as i32,
+            _ => 0,
+        }
+    }
+}
+
+/// Order moves so that promotions and captures of valuable pieces by cheap pieces come first.
+fn ordered_moves(game: &Game, mut moves: Vec<ChessMove>) -> Vec<ChessMove> {
+    let board = game.board();
+    let square = |pos: Position| u8::from(pos.rank()) * 8 + u8::from(pos.file());
+    let key = |chess_move: &ChessMove| -> (i32, u8, u8, u8) {
+        let attacker = board
+            .get_piece(chess_move.from())
+            .map(|p| p.kind().value())
+            .unwrap_or(0);
+        let score = match *chess_move {
+            ChessMove::Regular { to, .. } => board
+                .get_piece(to)
+                .map(|victim| 10 * victim.kind().value() - attacker)
+                .unwrap_or(0),
+            ChessMove::EnPassant { .. } => 10 * PieceType::Pawn.value() - attacker,
+            ChessMove::Promotion { to, piece, .. } => {
+                10 * piece.create_piece(Color::White).kind().value()
+                    + board
+                        .get_piece(to)
+                        .map(|victim| 10 * victim.kind().value())
+                        .unwrap_or(0)
+            }
+            ChessMove::Castle { .. } => 0,
+        };
+        // break ties by the squares involved, so that searches are deterministic
+        let promotion = match chess_move {
+            ChessMove::Promotion { piece, .. } => *piece as u8 + 1,
+            _ => 0,
+        };
+        (
+            -score,
+            square(chess_move.from()),
+            square(chess_move.to()),
+            promotion,
+        )
+    };
+    moves.sort_by_cached_key(key);
+    moves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitboard::*;
+
+    #[test]
+    fn finds_mate_in_one() {
+        // back rank mate
+        let game = Game::from_fen_string("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let result = Search::new(2).run(&game);
+        assert_eq!(
+            result.best_move(),
+            Some(ChessMove::Regular { from: A1, to: A8 })
+        );
+        assert_eq!(result.score(), MATE_SCORE - 1);
+    }
+
+    #[test]
+    fn quiescence_resolves_captures() {
+        // the pawn on d5 is defended by the pawn on e6, so taking it loses the queen
+        let game = Game::from_fen_string("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        let mut search = Search::new(1);
+        assert_eq!(evaluate(&game), 700);
+        assert_eq!(search.quiescence(&game, -INFINITY, INFINITY), 700);
+
+        // here the capture is free
+        let game = Game::from_fen_string("4k3/8/8/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        assert_eq!(search.quiescence(&game, -INFINITY, INFINITY), 900);
+    }
+
+    #[test]
+    fn stop_signal() {
+        let stop_signal = Arc::new(AtomicBool::new(true));
+        let game = Game::default();
+        let result = Search::new(u32::MAX)
+            .with_stop_signal(stop_signal)
+            .run(&game);
+        assert!(game.get_moves().contains(&result.best_move().unwrap()));
+        assert!(result.depth() <= 1);
+    }
+
+    #[test]
+    fn horizon() {
+        // a depth one search without quiescence would grab the defended pawn on d5
+        let game = Game::from_fen_string("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        let result = Search::new(1).run(&game);
+        assert_ne!(
+            result.best_move(),
+            Some(ChessMove::Regular { from: D1, to: D5 })
+        );
+    }
+}
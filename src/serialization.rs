@@ -0,0 +1,302 @@
+//! `Serialize` and `Deserialize` implementations for types that are written in chess notation
+//! rather than derived: pieces as FEN letters, moves in UCI notation, boards as the piece
+//! placement field of FEN, and games as their starting position and moves.
+
+use crate::{
+    chess_board::ChessBoard,
+    chess_move::{ChessMove, PromotionPiece},
+    fen,
+    game::{Game, GameOverReason},
+    piece::PieceType,
+    Color, Piece,
+};
+use bitboard::*;
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+use std::str::FromStr;
+
+impl Serialize for Piece {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_char(self.fen_char())
+    }
+}
+
+impl<'de> Deserialize<'de> for Piece {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let c = char::deserialize(deserializer)?;
+        [Color::White, Color::Black]
+            .iter()
+            .flat_map(|&color| PieceType::all_iter().map(move |kind| Piece::new(color, kind)))
+            .find(|piece| piece.fen_char() == c)
+            .ok_or_else(|| D::Error::custom(format!("invalid piece '{}'", c)))
+    }
+}
+
+/// A move written in UCI notation, tagged with its kind since the notation alone doesn't tell
+/// castling and en passant apart from regular moves.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum TaggedMove {
+    Regular(String),
+    EnPassant(String),
+    Promotion(String),
+    Castle(String),
+}
+
+impl Serialize for ChessMove {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let uci = self.to_uci_string();
+        match self {
+            ChessMove::Regular { .. } => TaggedMove::Regular(uci),
+            ChessMove::EnPassant { .. } => TaggedMove::EnPassant(uci),
+            ChessMove::Promotion { .. } => TaggedMove::Promotion(uci),
+            ChessMove::Castle { .. } => TaggedMove::Castle(uci),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ChessMove {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let parse = |uci: &str, promotion| parse_uci(uci, promotion).map_err(D::Error::custom);
+        match TaggedMove::deserialize(deserializer)? {
+            TaggedMove::Regular(uci) => {
+                let (from, to, _) = parse(&uci, false)?;
+                Ok(ChessMove::Regular { from, to })
+            }
+            TaggedMove::EnPassant(uci) => {
+                let (from, to, _) = parse(&uci, false)?;
+                let original_rank = match from.rank() {
+                    Rank::Five => Rank::Seven,
+                    Rank::Four => Rank::Two,
+                    _ => return Err(D::Error::custom(format!("invalid en passant '{}'", uci))),
+                };
+                Ok(ChessMove::EnPassant {
+                    from,
+                    to,
+                    taken_original_index: Position::new(to.file(), original_rank),
+                    taken_index: Position::new(to.file(), from.rank()),
+                })
+            }
+            TaggedMove::Promotion(uci) => {
+                let (from, to, piece) = parse(&uci, true)?;
+                Ok(ChessMove::Promotion {
+                    from,
+                    to,
+                    piece: piece.unwrap(),
+                })
+            }
+            TaggedMove::Castle(uci) => {
+                let (king_from, king_to, _) = parse(&uci, false)?;
+                let (rook_from, rook_to) = match (king_from, king_to) {
+                    (E1, G1) => (H1, F1),
+                    (E1, C1) => (A1, D1),
+                    (E8, G8) => (H8, F8),
+                    (E8, C8) => (A8, D8),
+                    _ => return Err(D::Error::custom(format!("invalid castle '{}'", uci))),
+                };
+                Ok(ChessMove::Castle {
+                    rook_from,
+                    rook_to,
+                    king_from,
+                    king_to,
+                })
+            }
+        }
+    }
+}
+
+/// Split a move in UCI notation into its squares and promotion piece, which must be given
+/// exactly when `promotion` is `true`.
+fn parse_uci(
+    uci: &str,
+    promotion: bool,
+) -> Result<(Position, Position, Option<PromotionPiece>), String> {
+    let invalid = || format!("invalid move '{}'", uci);
+    let square = |range| {
+        uci.get(range)
+            .and_then(|square| Position::from_str(square).ok())
+            .ok_or_else(invalid)
+    };
+    let (from, to) = (square(0..2)?, square(2..4)?);
+    let piece = match uci.get(4..) {
+        Some("") if !promotion => None,
+        Some("n") if promotion => Some(PromotionPiece::Knight),
+        Some("b") if promotion => Some(PromotionPiece::Bishop),
+        Some("r") if promotion => Some(PromotionPiece::Rook),
+        Some("q") if promotion => Some(PromotionPiece::Queen),
+        _ => return Err(invalid()),
+    };
+    Ok((from, to, piece))
+}
+
+impl Serialize for ChessBoard {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_fen_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for ChessBoard {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let placement = String::deserialize(deserializer)?;
+        fen::board_from_fen_part_0(&placement).map_err(D::Error::custom)
+    }
+}
+
+/// The serialized form of a [`Game`].
+///
+/// The position is given both as the moves from the starting position, which is needed to undo
+/// them, and as the FEN string of the current position, which is checked against the moves when
+/// deserializing. An attached clock is not serialized.
+#[derive(Serialize, Deserialize)]
+struct GameSnapshot {
+    start_fen: String,
+    moves: Vec<String>,
+    fen: String,
+    #[serde(default)]
+    draw_offer: Option<Color>,
+    #[serde(default)]
+    drawn_by_agreement: bool,
+}
+
+impl Serialize for Game {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        GameSnapshot {
+            start_fen: self.start_fen_string(),
+            moves: self
+                .move_history()
+                .iter()
+                .map(ChessMove::to_uci_string)
+                .collect(),
+            fen: self.to_fen_string(),
+            draw_offer: self.draw_offer(),
+            drawn_by_agreement: self.game_over_reason() == Some(GameOverReason::Agreement),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Game {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let snapshot = GameSnapshot::deserialize(deserializer)?;
+        let mut game = Game::from_fen_string(&snapshot.start_fen).map_err(D::Error::custom)?;
+        for uci in &snapshot.moves {
+            let chess_move = game.parse_uci_move(uci).map_err(D::Error::custom)?;
+            game.make_move(chess_move).map_err(D::Error::custom)?;
+        }
+        if game.to_fen_string() != snapshot.fen {
+            return Err(D::Error::custom(format!(
+                "the moves lead to '{}', not '{}'",
+                game.to_fen_string(),
+                snapshot.fen
+            )));
+        }
+        if let Some(color) = snapshot.draw_offer {
+            game.offer_draw(color).map_err(D::Error::custom)?;
+        }
+        if snapshot.drawn_by_agreement {
+            game.offer_draw(game.current_player().opponent())
+                .and_then(|_| game.accept_draw())
+                .map_err(D::Error::custom)?;
+        }
+        Ok(game)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_move::CastlingRights;
+    use serde_json::{from_str, json, to_string, to_value};
+
+    #[test]
+    fn pieces_and_colors() {
+        assert_eq!(to_string(&Piece::knight(Color::Black)).unwrap(), "\"n\"");
+        assert_eq!(
+            from_str::<Piece>("\"Q\"").unwrap(),
+            Piece::queen(Color::White)
+        );
+        assert!(from_str::<Piece>("\"x\"").is_err());
+        assert_eq!(to_string(&Color::White).unwrap(), "\"white\"");
+        assert_eq!(to_string(&PieceType::Rook).unwrap(), "\"rook\"");
+        assert_eq!(
+            from_str::<PromotionPiece>("\"queen\"").unwrap(),
+            PromotionPiece::Queen
+        );
+        let rights = CastlingRights::new(true, false, false, true);
+        assert_eq!(
+            from_str::<CastlingRights>(&to_string(&rights).unwrap()).unwrap(),
+            rights
+        );
+    }
+
+    #[test]
+    fn moves() {
+        let moves = [
+            ChessMove::Regular { from: E2, to: E4 },
+            ChessMove::EnPassant {
+                from: E4,
+                to: D3,
+                taken_original_index: D2,
+                taken_index: D4,
+            },
+            ChessMove::Promotion {
+                from: B7,
+                to: A8,
+                piece: PromotionPiece::Knight,
+            },
+            ChessMove::Castle {
+                rook_from: A8,
+                rook_to: D8,
+                king_from: E8,
+                king_to: C8,
+            },
+        ];
+        assert_eq!(
+            to_value(moves).unwrap(),
+            json!([
+                {"regular": "e2e4"},
+                {"en_passant": "e4d3"},
+                {"promotion": "b7a8n"},
+                {"castle": "e8c8"},
+            ])
+        );
+        for chess_move in moves {
+            let json = to_string(&chess_move).unwrap();
+            assert_eq!(from_str::<ChessMove>(&json).unwrap(), chess_move);
+        }
+        assert!(from_str::<ChessMove>(r#"{"regular": "e7e8q"}"#).is_err());
+        assert!(from_str::<ChessMove>(r#"{"castle": "e1f1"}"#).is_err());
+    }
+
+    #[test]
+    fn board() {
+        let board = ChessBoard::default();
+        let json = to_string(&board).unwrap();
+        assert_eq!(json, "\"rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR\"");
+        assert_eq!(from_str::<ChessBoard>(&json).unwrap(), board);
+    }
+
+    #[test]
+    fn game() {
+        let mut game = Game::default();
+        for uci in ["e2e4", "e7e5", "g1f3"] {
+            game.make_move(game.parse_uci_move(uci).unwrap()).unwrap();
+        }
+        game.offer_draw(Color::White).unwrap();
+        let value = to_value(&game).unwrap();
+        assert_eq!(value["moves"], json!(["e2e4", "e7e5", "g1f3"]));
+        assert_eq!(value["fen"], json!(game.to_fen_string()));
+
+        let mut restored: Game = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(restored.to_fen_string(), game.to_fen_string());
+        assert_eq!(restored.move_history(), game.move_history());
+        assert_eq!(restored.draw_offer(), Some(Color::White));
+        restored.accept_draw().unwrap();
+        let restored: Game = from_str(&to_string(&restored).unwrap()).unwrap();
+        assert_eq!(restored.game_over_reason(), Some(GameOverReason::Agreement));
+
+        let mut tampered = value;
+        tampered["moves"] = json!(["e2e4", "e7e5"]);
+        assert!(serde_json::from_value::<Game>(tampered).is_err());
+    }
+}
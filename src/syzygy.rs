@@ -0,0 +1,1480 @@
+//! Probing of Syzygy endgame tablebases.
+//!
+//! Syzygy tables come in two files per material signature, such as `KRvK.rtbw` and
+//! `KRvK.rtbz`. The `.rtbw` file tells whether a position is won, drawn or lost (WDL), and the
+//! `.rtbz` file gives the distance to zeroing (DTZ): the number of plies until the next capture
+//! or pawn move, which is what matters under the fifty-move rule. Tables exist for positions
+//! with up to [`MAX_PIECES`] pieces, kings included.
+//!
+//! The tables do not store positions where castling is possible, and they may store any value
+//! for positions where the side to move has a winning capture, since those are resolved by a
+//! small search over captures while probing.
+//!
+//! The decoding is written from the description of the format, and has only been tested against
+//! the tables in `tests/syzygy`, which are written by `tests/syzygy/generate.py` from the same
+//! description. It has not been checked against the published tables.
+
+use crate::{
+    chess_board::ChessBoard,
+    chess_move::ChessMove,
+    game::{Game, GameOver},
+    piece::PieceType,
+    Color, Piece,
+};
+use bitboard::INCREASING_A1_B1;
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    ops::Neg,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// The largest number of pieces, kings included, that Syzygy tables exist for.
+pub const MAX_PIECES: usize = 7;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+// flags stored for every subtable
+const FLAG_STM: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+/// The tablebase result of a position, from the perspective of the side to move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Wdl {
+    /// The side to move loses.
+    Loss,
+    /// The side to move loses with best play, but the fifty-move rule saves the draw.
+    BlessedLoss,
+    /// The position is drawn.
+    Draw,
+    /// The side to move wins with best play, but not before the fifty-move rule applies.
+    CursedWin,
+    /// The side to move wins.
+    Win,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Result<Self, String> {
+        match value {
+            -2 => Ok(Wdl::Loss),
+            -1 => Ok(Wdl::BlessedLoss),
+            0 => Ok(Wdl::Draw),
+            1 => Ok(Wdl::CursedWin),
+            2 => Ok(Wdl::Win),
+            _ => Err(format!("invalid WDL value {}", value)),
+        }
+    }
+
+    fn signum(self) -> i32 {
+        match self {
+            Wdl::Loss | Wdl::BlessedLoss => -1,
+            Wdl::Draw => 0,
+            Wdl::CursedWin | Wdl::Win => 1,
+        }
+    }
+
+    /// Classify a distance to zeroing, given the number of half moves that have already been
+    /// played since the last capture or pawn move.
+    ///
+    /// A DTZ value may be one ply shorter than the real distance, so a win is only reported
+    /// when it is certain to be converted in time.
+    fn from_dtz(dtz: i32, half_moves: u32) -> Self {
+        let half_moves = half_moves as i32;
+        if dtz > 0 {
+            if dtz + half_moves <= 99 {
+                Wdl::Win
+            } else {
+                Wdl::CursedWin
+            }
+        } else if dtz < 0 {
+            if -dtz + half_moves <= 100 {
+                Wdl::Loss
+            } else {
+                Wdl::BlessedLoss
+            }
+        } else {
+            Wdl::Draw
+        }
+    }
+}
+
+impl Neg for Wdl {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        match self {
+            Wdl::Loss => Wdl::Win,
+            Wdl::BlessedLoss => Wdl::CursedWin,
+            Wdl::Draw => Wdl::Draw,
+            Wdl::CursedWin => Wdl::BlessedLoss,
+            Wdl::Win => Wdl::Loss,
+        }
+    }
+}
+
+/// A legal move in the probed position, with the tablebase result after playing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RankedMove {
+    chess_move: ChessMove,
+    wdl: Wdl,
+    dtz: i32,
+}
+
+impl RankedMove {
+    pub fn chess_move(&self) -> ChessMove {
+        self.chess_move
+    }
+
+    /// The result for the player making the move, taking the half moves already played into
+    /// account.
+    pub fn wdl(&self) -> Wdl {
+        self.wdl
+    }
+
+    /// The distance to zeroing in plies, counted from before the move. Positive for wins and
+    /// negative for losses.
+    pub fn dtz(&self) -> i32 {
+        self.dtz
+    }
+}
+
+/// A collection of Syzygy tables on disk.
+///
+/// Directories are scanned with [`Tablebase::add_directory`], and each table is opened the
+/// first time a position needs it.
+pub struct Tablebase {
+    paths: HashMap<String, PathBuf>,
+    tables: Mutex<HashMap<String, Arc<Table>>>,
+    indices: Indices,
+}
+
+impl Tablebase {
+    /// Create a tablebase without any tables.
+    pub fn new() -> Self {
+        Self {
+            paths: HashMap::new(),
+            tables: Mutex::new(HashMap::new()),
+            indices: Indices::new(),
+        }
+    }
+
+    /// Add every `.rtbw` and `.rtbz` file in `directory`.
+    ///
+    /// # Returns
+    /// * `Ok` with the number of files found.
+    /// * `Err` if the directory could not be read.
+    pub fn add_directory<P: AsRef<Path>>(&mut self, directory: P) -> Result<usize, String> {
+        let directory = directory.as_ref();
+        let entries = fs::read_dir(directory)
+            .map_err(|e| format!("could not read {}: {}", directory.display(), e))?;
+
+        let mut found = 0;
+        for entry in entries {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            let file_name = match path.file_name().and_then(|name| name.to_str()) {
+                Some(file_name) => file_name.to_string(),
+                None => continue,
+            };
+            if file_name.ends_with(".rtbw") || file_name.ends_with(".rtbz") {
+                self.paths.insert(file_name, path);
+                found += 1;
+            }
+        }
+        Ok(found)
+    }
+
+    /// Probe the win/draw/loss result of a position for the current player.
+    ///
+    /// The result assumes that the half-move clock was just reset. Use
+    /// [`Tablebase::probe_outcome`] to take the half moves already played into account.
+    ///
+    /// # Returns
+    /// * `Ok` with the result.
+    /// * `Err` if the position is not covered by the tables that were added.
+    pub fn probe_wdl(&self, game: &Game) -> Result<Wdl, String> {
+        check_supported(game)?;
+        Ok(self.search(game, false)?.0)
+    }
+
+    /// Probe the distance to zeroing of a position for the current player, in plies.
+    ///
+    /// The value is positive if the current player wins and negative if they lose, and is
+    /// beyond 100 in magnitude if the result is a cursed win or a blessed loss. It is `0` for
+    /// draws and `-1` if the current player is mated. A value may be one ply shorter than the
+    /// real distance.
+    ///
+    /// # Returns
+    /// * `Ok` with the distance to zeroing.
+    /// * `Err` if the position is not covered by the tables that were added.
+    pub fn probe_dtz(&self, game: &Game) -> Result<i32, String> {
+        check_supported(game)?;
+        self.dtz(game)
+    }
+
+    /// Probe the result of a position for the current player, taking the half moves played
+    /// since the last capture or pawn move into account.
+    ///
+    /// A win that can no longer be forced before the fifty-move rule applies is reported as a
+    /// [`Wdl::CursedWin`], and a loss that the fifty-move rule saves as a [`Wdl::BlessedLoss`].
+    ///
+    /// # Returns
+    /// * `Ok` with the result.
+    /// * `Err` if the position is not covered by the tables that were added.
+    pub fn probe_outcome(&self, game: &Game) -> Result<Wdl, String> {
+        check_supported(game)?;
+        Ok(Wdl::from_dtz(self.dtz(game)?, game.half_moves()))
+    }
+
+    /// Rank the legal moves of the current player by their tablebase result.
+    ///
+    /// Moves are sorted best first: wins before draws before losses. Among winning moves,
+    /// those that reach the next capture or pawn move soonest come first, and among losing
+    /// moves, those that delay it the longest.
+    ///
+    /// # Returns
+    /// * `Ok` with every legal move and its result.
+    /// * `Err` if a position after one of the moves is not covered by the tables that were
+    ///   added.
+    pub fn rank_root_moves(&self, game: &Game) -> Result<Vec<RankedMove>, String> {
+        check_supported(game)?;
+
+        let mut ranked = Vec::new();
+        for &chess_move in game.get_moves() {
+            let mut after_move = game.clone();
+            after_move.make_move(chess_move)?;
+
+            let mut dtz = if after_move.half_moves() == 0 {
+                dtz_before_zeroing(-self.search(&after_move, false)?.0)
+            } else {
+                let dtz = -self.dtz(&after_move)?;
+                dtz + dtz.signum()
+            };
+            if dtz == 2 && matches!(after_move.game_result(), Some(GameOver::Winner(_))) {
+                // a mating move
+                dtz = 1;
+            }
+
+            ranked.push(RankedMove {
+                chess_move,
+                wdl: Wdl::from_dtz(dtz, game.half_moves()),
+                dtz,
+            });
+        }
+
+        ranked.sort_by_cached_key(|ranked_move| {
+            (
+                std::cmp::Reverse((ranked_move.wdl, -ranked_move.dtz)),
+                ranked_move.chess_move.to_uci_string(),
+            )
+        });
+        Ok(ranked)
+    }
+
+    /// Find the result of a position by probing it and searching the captures (and pawn moves,
+    /// if `check_zeroing` is `true`) of the current player, since the tables may store any
+    /// value for positions where such a move is best.
+    ///
+    /// # Returns
+    /// The result, and whether a capture or pawn move is the best move.
+    fn search(&self, game: &Game, check_zeroing: bool) -> Result<(Wdl, bool), String> {
+        let board = game.board();
+        let mut best = Wdl::Loss;
+        let mut searched = 0;
+        for &chess_move in game.get_moves() {
+            let zeroing = is_capture(&board, chess_move)
+                || (check_zeroing && is_pawn_move(&board, chess_move));
+            if !zeroing {
+                continue;
+            }
+            searched += 1;
+
+            let mut after_move = game.clone();
+            after_move.make_move(chess_move)?;
+            let value = -self.search(&after_move, false)?.0;
+            if value > best {
+                best = value;
+                if value == Wdl::Win {
+                    return Ok((value, true));
+                }
+            }
+        }
+
+        // the stored value can't be trusted if every move has been searched, for instance if
+        // the only legal move is an en passant capture
+        let no_more_moves = searched > 0 && searched == game.get_moves().len();
+        let value = if no_more_moves {
+            best
+        } else {
+            let value = self
+                .probe_table(game, false, Wdl::Draw)?
+                .ok_or_else(|| "WDL table does not store the side to move".to_string())?;
+            Wdl::from_value(value)?
+        };
+
+        if best >= value {
+            Ok((best, best > Wdl::Draw || no_more_moves))
+        } else {
+            Ok((value, false))
+        }
+    }
+
+    fn dtz(&self, game: &Game) -> Result<i32, String> {
+        let (wdl, zeroing) = self.search(game, true)?;
+        if wdl == Wdl::Draw {
+            return Ok(0);
+        }
+        if zeroing {
+            // the table may store any value if a capture or pawn move is best
+            return Ok(dtz_before_zeroing(wdl));
+        }
+
+        if let Some(dtz) = self.probe_table(game, true, wdl)? {
+            let cursed = wdl == Wdl::CursedWin || wdl == Wdl::BlessedLoss;
+            return Ok((dtz + if cursed { 100 } else { 0 }) * wdl.signum());
+        }
+
+        // the table only stores the other side to move, so look one ply ahead
+        let board = game.board();
+        let mut min_dtz = i32::MAX;
+        for &chess_move in game.get_moves() {
+            let zeroing = is_capture(&board, chess_move) || is_pawn_move(&board, chess_move);
+            let mut after_move = game.clone();
+            after_move.make_move(chess_move)?;
+
+            let mut dtz = if zeroing {
+                -dtz_before_zeroing(self.search(&after_move, false)?.0)
+            } else {
+                -self.dtz(&after_move)?
+            };
+            if dtz == 1 && matches!(after_move.game_result(), Some(GameOver::Winner(_))) {
+                min_dtz = 1;
+            }
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+            if dtz < min_dtz && dtz.signum() == wdl.signum() {
+                min_dtz = dtz;
+            }
+        }
+
+        // without legal moves the current player is mated
+        Ok(if min_dtz == i32::MAX { -1 } else { min_dtz })
+    }
+
+    /// Look up the value stored for a position.
+    ///
+    /// # Returns
+    /// * `Ok(Some)` with the WDL value, or the DTZ value for a position with result `wdl`.
+    /// * `Ok(None)` if the DTZ table only stores the other side to move.
+    /// * `Err` if the table is missing or could not be read.
+    fn probe_table(&self, game: &Game, dtz: bool, wdl: Wdl) -> Result<Option<i32>, String> {
+        let board = game.board();
+        let white = material_name(&board, Color::White);
+        let black = material_name(&board, Color::Black);
+        if white.len() + black.len() == 2 {
+            // only kings left
+            return Ok(Some(0));
+        }
+
+        // tables are only stored with one side as white, usually the stronger one
+        let extension = if dtz { "rtbz" } else { "rtbw" };
+        let name = format!("{}v{}", white, black);
+        let mirrored_name = format!("{}v{}", black, white);
+        let (name, mirrored) = if self.paths.contains_key(&format!("{}.{}", name, extension)) {
+            (name, false)
+        } else if self
+            .paths
+            .contains_key(&format!("{}.{}", mirrored_name, extension))
+        {
+            (mirrored_name, true)
+        } else {
+            return Err(format!("missing table {}.{}", name, extension));
+        };
+
+        let table = self.table(&name, dtz)?;
+        table.probe(&self.indices, &board, game.current_player(), mirrored, wdl)
+    }
+
+    fn table(&self, name: &str, dtz: bool) -> Result<Arc<Table>, String> {
+        let file_name = format!("{}.{}", name, if dtz { "rtbz" } else { "rtbw" });
+        let mut tables = self.tables.lock().unwrap();
+        if let Some(table) = tables.get(&file_name) {
+            return Ok(Arc::clone(table));
+        }
+
+        let table = Arc::new(Table::open(
+            &self.paths[&file_name],
+            name,
+            dtz,
+            &self.indices,
+        )?);
+        tables.insert(file_name, Arc::clone(&table));
+        Ok(table)
+    }
+}
+
+impl Default for Tablebase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn check_supported(game: &Game) -> Result<(), String> {
+    let pieces = game.board().full_occupancy().count_ones() as usize;
+    if pieces > MAX_PIECES {
+        return Err(format!("too many pieces for the tablebase ({})", pieces));
+    }
+    if game
+        .get_moves()
+        .iter()
+        .any(|chess_move| chess_move.is_castle())
+    {
+        return Err("positions with castling are not in the tablebase".to_string());
+    }
+    Ok(())
+}
+
+fn is_capture(board: &ChessBoard, chess_move: ChessMove) -> bool {
+    match chess_move {
+        ChessMove::EnPassant { .. } => true,
+        ChessMove::Castle { .. } => false,
+        _ => board.has_piece_at(chess_move.to()),
+    }
+}
+
+fn is_pawn_move(board: &ChessBoard, chess_move: ChessMove) -> bool {
+    board
+        .get_piece(chess_move.from())
+        .is_some_and(|piece| piece.kind() == PieceType::Pawn)
+}
+
+/// The distance to zeroing before a capture or pawn move into a position with result `wdl`,
+/// from the perspective of the player making the move.
+fn dtz_before_zeroing(wdl: Wdl) -> i32 {
+    match wdl {
+        Wdl::Win => 1,
+        Wdl::CursedWin => 101,
+        Wdl::Draw => 0,
+        Wdl::BlessedLoss => -101,
+        Wdl::Loss => -1,
+    }
+}
+
+/// The pieces of one player as written in table names, such as `KRP`.
+fn material_name(board: &ChessBoard, color: Color) -> String {
+    use PieceType::*;
+
+    let mut name = String::new();
+    for &(kind, letter) in &[
+        (King, 'K'),
+        (Queen, 'Q'),
+        (Rook, 'R'),
+        (Bishop, 'B'),
+        (Knight, 'N'),
+        (Pawn, 'P'),
+    ] {
+        for _ in board.get_bitboard(color, kind).positions() {
+            name.push(letter);
+        }
+    }
+    name
+}
+
+/// The code used for a piece in table files: 1 to 6 for white pawn to king, and 9 to 14 for
+/// black.
+fn piece_code(piece: Piece) -> u8 {
+    let kind = match piece.kind() {
+        PieceType::Pawn => 1,
+        PieceType::Knight => 2,
+        PieceType::Bishop => 3,
+        PieceType::Rook => 4,
+        PieceType::Queen => 5,
+        PieceType::King => 6,
+    };
+    match piece.color() {
+        Color::White => kind,
+        Color::Black => kind | 8,
+    }
+}
+
+// Squares are numbered from 0 for a1 to 63 for h8.
+
+fn file_of(square: usize) -> usize {
+    square & 7
+}
+
+fn rank_of(square: usize) -> usize {
+    square >> 3
+}
+
+/// How far above the a1-h8 diagonal a square is, negative for squares below it.
+fn off_diagonal(square: usize) -> i32 {
+    rank_of(square) as i32 - file_of(square) as i32
+}
+
+/// Tables used to turn a position into an index into a table.
+struct Indices {
+    /// `binomial[k][n]` is the number of ways to choose `k` squares out of `n`.
+    binomial: [[u64; 64]; 6],
+    /// Maps the squares a2 to h7 to 0..48, such that the leading pawn has the highest value.
+    map_pawns: [usize; 64],
+    /// Maps the squares below the a1-h8 diagonal to 0..28.
+    map_b1h1h7: [usize; 64],
+    /// Maps the a1-d1-d4 triangle to 0..10, with the diagonal last.
+    map_a1d1d4: [usize; 64],
+    /// Maps the 462 legal placements of two kings, with the first in the a1-d1-d4 triangle.
+    map_kk: [[u64; 64]; 10],
+    lead_pawn_idx: [[u64; 64]; 6],
+    lead_pawns_size: [[u64; 4]; 6],
+}
+
+impl Indices {
+    fn new() -> Self {
+        let mut binomial = [[0; 64]; 6];
+        for n in 0..64 {
+            for k in 0..6 {
+                binomial[k][n] = match (k, n) {
+                    (0, _) => 1,
+                    (_, 0) => 0,
+                    _ => binomial[k - 1][n - 1] + binomial[k][n - 1],
+                };
+            }
+        }
+
+        let mut map_b1h1h7 = [0; 64];
+        let below_diagonal = (0..64).filter(|&square| off_diagonal(square) < 0);
+        for (code, square) in below_diagonal.enumerate() {
+            map_b1h1h7[square] = code;
+        }
+
+        let mut map_a1d1d4 = [0; 64];
+        let triangle = (0..64).filter(|&square| file_of(square) <= 3 && rank_of(square) <= 3);
+        let below = triangle.clone().filter(|&square| off_diagonal(square) < 0);
+        let diagonal = triangle.filter(|&square| off_diagonal(square) == 0);
+        for (code, square) in below.chain(diagonal).enumerate() {
+            map_a1d1d4[square] = code;
+        }
+
+        // placements with the first king on the diagonal and the second above it are mirrors
+        // of others, and placements with both kings on the diagonal are numbered last
+        let mut map_kk = [[0; 64]; 10];
+        let mut both_on_diagonal = Vec::new();
+        let mut code = 0;
+        for (idx, kings) in map_kk.iter_mut().enumerate() {
+            for first in (0..64).filter(|&square| {
+                file_of(square) <= 3
+                    && rank_of(square) <= 3
+                    && off_diagonal(square) <= 0
+                    && map_a1d1d4[square] == idx
+            }) {
+                for (second, entry) in kings.iter_mut().enumerate() {
+                    let touching = (file_of(first) as i32 - file_of(second) as i32).abs() <= 1
+                        && (rank_of(first) as i32 - rank_of(second) as i32).abs() <= 1;
+                    if touching || (off_diagonal(first) == 0 && off_diagonal(second) > 0) {
+                        continue;
+                    }
+                    if off_diagonal(first) == 0 && off_diagonal(second) == 0 {
+                        both_on_diagonal.push((idx, second));
+                    } else {
+                        *entry = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (idx, second) in both_on_diagonal {
+            map_kk[idx][second] = code;
+            code += 1;
+        }
+
+        let mut map_pawns = [0; 64];
+        let mut available: usize = 47;
+        for file in 0..4 {
+            for rank in 1..7 {
+                let square = rank * 8 + file;
+                map_pawns[square] = available;
+                map_pawns[square ^ 7] = available - 1;
+                available = available.saturating_sub(2);
+            }
+        }
+
+        let mut lead_pawn_idx = [[0; 64]; 6];
+        let mut lead_pawns_size = [[0; 4]; 6];
+        for lead_pawns in 1..6 {
+            for (file, size) in lead_pawns_size[lead_pawns].iter_mut().enumerate() {
+                let mut idx = 0;
+                for rank in 1..7 {
+                    let square = rank * 8 + file;
+                    lead_pawn_idx[lead_pawns][square] = idx;
+                    idx += binomial[lead_pawns - 1][map_pawns[square]];
+                }
+                *size = idx;
+            }
+        }
+
+        Self {
+            binomial,
+            map_pawns,
+            map_b1h1h7,
+            map_a1d1d4,
+            map_kk,
+            lead_pawn_idx,
+            lead_pawns_size,
+        }
+    }
+}
+
+/// A table file, read on demand.
+struct TableFile {
+    file: Mutex<File>,
+    len: u64,
+}
+
+impl TableFile {
+    fn open(path: &Path) -> Result<Self, String> {
+        let file =
+            File::open(path).map_err(|e| format!("could not open {}: {}", path.display(), e))?;
+        let len = file.metadata().map_err(|e| e.to_string())?.len();
+        Ok(Self {
+            file: Mutex::new(file),
+            len,
+        })
+    }
+
+    fn read(&self, offset: u64, buf: &mut [u8]) -> Result<(), String> {
+        if offset + buf.len() as u64 > self.len {
+            return Err("unexpected end of table".to_string());
+        }
+        let mut file = self.file.lock().unwrap();
+        file.seek(SeekFrom::Start(offset))
+            .and_then(|_| file.read_exact(buf))
+            .map_err(|e| e.to_string())
+    }
+
+    fn u8(&self, offset: u64) -> Result<u8, String> {
+        let mut buf = [0; 1];
+        self.read(offset, &mut buf)?;
+        Ok(buf[0])
+    }
+
+    fn u16_le(&self, offset: u64) -> Result<u16, String> {
+        let mut buf = [0; 2];
+        self.read(offset, &mut buf)?;
+        Ok(u16::from_le_bytes(buf))
+    }
+
+    fn u32_le(&self, offset: u64) -> Result<u32, String> {
+        let mut buf = [0; 4];
+        self.read(offset, &mut buf)?;
+        Ok(u32::from_le_bytes(buf))
+    }
+}
+
+/// The layout and Huffman code of one subtable: one per side to move, and one per file of the
+/// leading pawn for tables with pawns.
+#[derive(Debug, Default)]
+struct PairsData {
+    flags: u8,
+    min_sym_len: u8,
+    block_size: u64,
+    /// Roughly every `span` values there is an entry in the sparse index.
+    span: u64,
+    num_blocks: u64,
+    /// The lowest symbol of each code length, starting at the shortest.
+    lowest_sym: Vec<u16>,
+    /// The lowest code of each length, left aligned to 64 bits.
+    base64: Vec<u64>,
+    /// The number of values each symbol expands to, minus one.
+    symlen: Vec<u8>,
+    /// The left and right symbols that each symbol expands to, 12 bits each.
+    btree: Vec<[u8; 3]>,
+    sparse_index: u64,
+    sparse_index_size: u64,
+    block_lengths: u64,
+    block_lengths_size: u64,
+    data: u64,
+    /// The pieces of the table, in the order they are encoded.
+    pieces: [u8; MAX_PIECES],
+    group_idx: [u64; MAX_PIECES + 1],
+    /// The sizes of the groups of pieces that are encoded together, terminated by 0.
+    group_len: [usize; MAX_PIECES + 1],
+    /// Offsets into the DTZ value map, for wins, losses, cursed wins and blessed losses.
+    map_idx: [u64; 4],
+}
+
+impl PairsData {
+    fn left(&self, sym: usize) -> usize {
+        let lr = self.btree[sym];
+        (usize::from(lr[1] & 0xf) << 8) | usize::from(lr[0])
+    }
+
+    fn right(&self, sym: usize) -> usize {
+        let lr = self.btree[sym];
+        (usize::from(lr[2]) << 4) | usize::from(lr[1] >> 4)
+    }
+
+    /// Compute the number of values each symbol expands to.
+    fn set_symlen(&mut self, sym: usize, visited: &mut [bool]) -> u8 {
+        visited[sym] = true;
+        let right = self.right(sym);
+        if right == 0xfff {
+            return 0;
+        }
+        let left = self.left(sym);
+        if !visited[left] {
+            self.symlen[left] = self.set_symlen(left, visited);
+        }
+        if !visited[right] {
+            self.symlen[right] = self.set_symlen(right, visited);
+        }
+        self.symlen[left]
+            .wrapping_add(self.symlen[right])
+            .wrapping_add(1)
+    }
+}
+
+struct Table {
+    file: TableFile,
+    dtz: bool,
+    piece_count: usize,
+    has_pawns: bool,
+    /// Whether any player has a piece other than the king that is the only one of its kind.
+    has_unique_pieces: bool,
+    /// The number of pawns of the leading color, which has the fewest pawns, and of the other.
+    pawn_count: [usize; 2],
+    /// Whether both players have the same pieces.
+    symmetric: bool,
+    /// Indexed by side to move and file of the leading pawn.
+    items: Vec<PairsData>,
+    /// The offset of the map from stored values to DTZ values.
+    map_offset: u64,
+}
+
+impl Table {
+    fn open(path: &Path, name: &str, dtz: bool, indices: &Indices) -> Result<Self, String> {
+        let (white, black) = name
+            .split_once('v')
+            .ok_or_else(|| format!("invalid table name {}", name))?;
+        let file = TableFile::open(path)?;
+
+        let mut magic = [0; 4];
+        file.read(0, &mut magic)?;
+        if magic != if dtz { DTZ_MAGIC } else { WDL_MAGIC } {
+            return Err(format!("{} is not a Syzygy table", path.display()));
+        }
+
+        let count = |side: &str, letter: char| side.chars().filter(|&c| c == letter).count();
+        let white_pawns = count(white, 'P');
+        let black_pawns = count(black, 'P');
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        let mut table = Self {
+            file,
+            dtz,
+            piece_count: white.len() + black.len(),
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces: [white, black]
+                .iter()
+                .any(|side| "QRBNP".chars().any(|letter| count(side, letter) == 1)),
+            pawn_count: if white_leads {
+                [white_pawns, black_pawns]
+            } else {
+                [black_pawns, white_pawns]
+            },
+            symmetric: white == black,
+            items: (0..8).map(|_| PairsData::default()).collect(),
+            map_offset: 0,
+        };
+        if table.piece_count > MAX_PIECES {
+            return Err(format!("invalid table name {}", name));
+        }
+        table.read_header(indices)?;
+        Ok(table)
+    }
+
+    fn item_index(&self, side: usize, file: usize) -> usize {
+        let side = if self.dtz { 0 } else { side };
+        let file = if self.has_pawns { file } else { 0 };
+        side * 4 + file
+    }
+
+    fn read_header(&mut self, indices: &Indices) -> Result<(), String> {
+        let flags = self.file.u8(4)?;
+        if (flags & 1 != 0) == self.symmetric || (flags & 2 != 0) != self.has_pawns {
+            return Err("table does not match its name".to_string());
+        }
+        let mut offset = 5;
+
+        let sides = if !self.dtz && !self.symmetric { 2 } else { 1 };
+        let files = if self.has_pawns { 4 } else { 1 };
+        let both_have_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        for file in 0..files {
+            let order = self.file.u8(offset)?;
+            let pawn_order = if both_have_pawns {
+                self.file.u8(offset + 1)?
+            } else {
+                0xff
+            };
+            let orders = [
+                [order & 0xf, pawn_order & 0xf],
+                [order >> 4, pawn_order >> 4],
+            ];
+            offset += if both_have_pawns { 2 } else { 1 };
+
+            for k in 0..self.piece_count {
+                let pieces = self.file.u8(offset)?;
+                for side in 0..sides {
+                    let index = self.item_index(side, file);
+                    self.items[index].pieces[k] =
+                        if side == 0 { pieces & 0xf } else { pieces >> 4 };
+                }
+                offset += 1;
+            }
+
+            for (side, order) in orders.iter().enumerate().take(sides) {
+                let index = self.item_index(side, file);
+                self.set_groups(index, *order, file, indices);
+            }
+        }
+        offset += offset & 1;
+
+        for file in 0..files {
+            for side in 0..sides {
+                let index = self.item_index(side, file);
+                offset = self.read_sizes(index, offset)?;
+            }
+        }
+
+        if self.dtz {
+            offset = self.read_dtz_map(offset, files)?;
+        }
+
+        for file in 0..files {
+            for side in 0..sides {
+                let d = &mut self.items[side * 4 + file];
+                d.sparse_index = offset;
+                offset += d.sparse_index_size * 6;
+            }
+        }
+        for file in 0..files {
+            for side in 0..sides {
+                let d = &mut self.items[side * 4 + file];
+                d.block_lengths = offset;
+                offset += d.block_lengths_size * 2;
+            }
+        }
+        for file in 0..files {
+            for side in 0..sides {
+                let d = &mut self.items[side * 4 + file];
+                offset = (offset + 0x3f) & !0x3f;
+                d.data = offset;
+                offset += d.num_blocks * d.block_size;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Group the pieces that are encoded together, and compute the factor of each group in
+    /// the index.
+    ///
+    /// Pieces of the same kind and color form a group. Without pawns, the first group is made
+    /// up of three unique pieces, or of the two kings if there aren't any. With pawns, the
+    /// first group is the pawns of the leading color.
+    fn set_groups(&mut self, index: usize, order: [u8; 2], file: usize, indices: &Indices) {
+        let piece_count = self.piece_count;
+        let has_pawns = self.has_pawns;
+        let has_unique_pieces = self.has_unique_pieces;
+        let both_have_pawns = has_pawns && self.pawn_count[1] > 0;
+        let d = &mut self.items[index];
+
+        let mut n = 0;
+        let mut first_len: i32 = if has_pawns {
+            0
+        } else if has_unique_pieces {
+            3
+        } else {
+            2
+        };
+        d.group_len[0] = 1;
+        for i in 1..piece_count {
+            first_len -= 1;
+            if first_len > 0 || d.pieces[i] == d.pieces[i - 1] {
+                d.group_len[n] += 1;
+            } else {
+                n += 1;
+                d.group_len[n] = 1;
+            }
+        }
+        n += 1;
+        d.group_len[n] = 0;
+
+        // the groups are not necessarily encoded in the order they appear in
+        let mut next = if both_have_pawns { 2 } else { 1 };
+        let mut free_squares =
+            64 - d.group_len[0] - if both_have_pawns { d.group_len[1] } else { 0 };
+        let mut idx = 1;
+        let mut k = 0;
+        while next < n || k == order[0] || k == order[1] {
+            if k == order[0] {
+                d.group_idx[0] = idx;
+                idx *= if has_pawns {
+                    indices.lead_pawns_size[d.group_len[0]][file]
+                } else if has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] {
+                d.group_idx[1] = idx;
+                idx *= indices.binomial[d.group_len[1]][48 - d.group_len[0]];
+            } else {
+                d.group_idx[next] = idx;
+                idx *= indices.binomial[d.group_len[next]][free_squares];
+                free_squares -= d.group_len[next];
+                next += 1;
+            }
+            k += 1;
+        }
+        d.group_idx[n] = idx;
+    }
+
+    fn read_sizes(&mut self, index: usize, mut offset: u64) -> Result<u64, String> {
+        let file = &self.file;
+        let d = &mut self.items[index];
+
+        d.flags = file.u8(offset)?;
+        offset += 1;
+        if d.flags & FLAG_SINGLE_VALUE != 0 {
+            // every position has the same value, stored in place of the symbol length
+            d.min_sym_len = file.u8(offset)?;
+            return Ok(offset + 1);
+        }
+
+        let groups = d.group_len.iter().position(|&len| len == 0).unwrap();
+        let table_size = d.group_idx[groups];
+
+        d.block_size = 1 << file.u8(offset)?;
+        d.span = 1 << file.u8(offset + 1)?;
+        d.sparse_index_size = table_size.div_ceil(d.span);
+        let padding = u64::from(file.u8(offset + 2)?);
+        d.num_blocks = u64::from(file.u32_le(offset + 3)?);
+        d.block_lengths_size = d.num_blocks + padding;
+        let max_sym_len = file.u8(offset + 7)?;
+        d.min_sym_len = file.u8(offset + 8)?;
+        offset += 9;
+        if max_sym_len < d.min_sym_len || max_sym_len > 32 {
+            return Err("invalid symbol lengths in table".to_string());
+        }
+
+        let lengths = usize::from(max_sym_len - d.min_sym_len) + 1;
+        d.lowest_sym = (0..lengths)
+            .map(|i| file.u16_le(offset + 2 * i as u64))
+            .collect::<Result<_, _>>()?;
+        offset += 2 * lengths as u64;
+
+        // canonical Huffman codes: longer codes have lower values, so the lowest code of each
+        // length is found from the lowest symbols, starting with the longest
+        d.base64 = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            d.base64[i] = (d.base64[i + 1]
+                .wrapping_add(u64::from(d.lowest_sym[i]))
+                .wrapping_sub(u64::from(d.lowest_sym[i + 1])))
+                / 2;
+        }
+        for (i, base) in d.base64.iter_mut().enumerate() {
+            let shift = 64 - i as u32 - u32::from(d.min_sym_len);
+            *base = base.checked_shl(shift).unwrap_or(0);
+        }
+
+        let symbols = usize::from(file.u16_le(offset)?);
+        offset += 2;
+        let mut btree = vec![0; symbols * 3];
+        file.read(offset, &mut btree)?;
+        d.btree = btree
+            .chunks_exact(3)
+            .map(|lr| [lr[0], lr[1], lr[2]])
+            .collect();
+        offset += symbols as u64 * 3 + (symbols as u64 & 1);
+
+        // symbols are built by recursively pairing other symbols
+        d.symlen = vec![0; symbols];
+        let mut visited = vec![false; symbols];
+        for sym in 0..symbols {
+            if !visited[sym] {
+                d.symlen[sym] = d.set_symlen(sym, &mut visited);
+            }
+        }
+
+        Ok(offset)
+    }
+
+    fn read_dtz_map(&mut self, mut offset: u64, files: usize) -> Result<u64, String> {
+        self.map_offset = offset;
+        for file in 0..files {
+            let d = &mut self.items[file];
+            if d.flags & FLAG_MAPPED == 0 {
+                continue;
+            }
+            if d.flags & FLAG_WIDE != 0 {
+                offset += offset & 1;
+                for i in 0..4 {
+                    d.map_idx[i] = (offset - self.map_offset) / 2 + 1;
+                    offset += 2 * u64::from(self.file.u16_le(offset)?) + 2;
+                }
+            } else {
+                for i in 0..4 {
+                    d.map_idx[i] = offset - self.map_offset + 1;
+                    offset += u64::from(self.file.u8(offset)?) + 1;
+                }
+            }
+        }
+        Ok(offset + (offset & 1))
+    }
+
+    /// Look up the value stored for a position.
+    ///
+    /// `mirrored` tells whether the pieces listed first in the table name belong to black.
+    fn probe(
+        &self,
+        indices: &Indices,
+        board: &ChessBoard,
+        player: Color,
+        mirrored: bool,
+        wdl: Wdl,
+    ) -> Result<Option<i32>, String> {
+        let mut codes = [0; 64];
+        for (square, code) in codes.iter_mut().enumerate() {
+            if let Some(piece) = board.get_piece(INCREASING_A1_B1[square]) {
+                *code = piece_code(piece);
+            }
+        }
+
+        // swap the colors and mirror the board so that the position matches the table, in
+        // which symmetric positions are only stored with white to move
+        let flip = mirrored || (self.symmetric && player == Color::Black);
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let side = usize::from(flip != (player == Color::Black));
+
+        let mut squares = Vec::with_capacity(MAX_PIECES);
+        let mut pieces = Vec::with_capacity(MAX_PIECES);
+        let mut tb_file = 0;
+        if self.has_pawns {
+            // the pawns of the leading color come first, led by the one closest to the a-file
+            // or h-file and with the lowest rank
+            let lead_pawn = self.items[0].pieces[0];
+            for (square, &code) in codes.iter().enumerate() {
+                if code != 0 && code ^ flip_color == lead_pawn {
+                    squares.push(square ^ flip_squares);
+                    pieces.push(lead_pawn);
+                }
+            }
+            let mut lead = 0;
+            for i in 1..squares.len() {
+                if indices.map_pawns[squares[i]] > indices.map_pawns[squares[lead]] {
+                    lead = i;
+                }
+            }
+            if squares.is_empty() {
+                return Err("position does not match the table".to_string());
+            }
+            squares.swap(0, lead);
+            tb_file = file_of(squares[0]).min(7 - file_of(squares[0]));
+        }
+        let lead_pawns = squares.len();
+
+        if self.dtz {
+            let flags = self.items[self.item_index(0, tb_file)].flags;
+            if (!self.symmetric || self.has_pawns) && usize::from(flags & FLAG_STM) != side {
+                return Ok(None);
+            }
+        }
+
+        for (square, &code) in codes.iter().enumerate() {
+            let is_lead_pawn = self.has_pawns && code ^ flip_color == self.items[0].pieces[0];
+            if code != 0 && !is_lead_pawn {
+                squares.push(square ^ flip_squares);
+                pieces.push(code ^ flip_color);
+            }
+        }
+        if squares.len() != self.piece_count {
+            return Err("position does not match the table".to_string());
+        }
+
+        let d = &self.items[self.item_index(side, tb_file)];
+
+        // order the pieces like the table does
+        for i in lead_pawns..squares.len().saturating_sub(1) {
+            for j in i + 1..squares.len() {
+                if d.pieces[i] == pieces[j] {
+                    pieces.swap(i, j);
+                    squares.swap(i, j);
+                    break;
+                }
+            }
+        }
+
+        // mirror the board so that the leading piece is on the a-file to the d-file
+        if file_of(squares[0]) > 3 {
+            for square in squares.iter_mut() {
+                *square ^= 7;
+            }
+        }
+
+        let mut idx = if self.has_pawns {
+            let mut idx = indices.lead_pawn_idx[lead_pawns][squares[0]];
+            squares[1..lead_pawns].sort_by_key(|&square| indices.map_pawns[square]);
+            for (i, &square) in squares[1..lead_pawns].iter().enumerate() {
+                idx += indices.binomial[i + 1][indices.map_pawns[square]];
+            }
+            idx
+        } else {
+            // without pawns, the board is also mirrored so that the leading piece is on the
+            // first four ranks, and then below the a1-h8 diagonal
+            if rank_of(squares[0]) > 3 {
+                for square in squares.iter_mut() {
+                    *square ^= 56;
+                }
+            }
+            for i in 0..d.group_len[0] {
+                if off_diagonal(squares[i]) == 0 {
+                    continue;
+                }
+                if off_diagonal(squares[i]) > 0 {
+                    for square in squares[i..].iter_mut() {
+                        *square = ((*square >> 3) | (*square << 3)) & 63;
+                    }
+                }
+                break;
+            }
+
+            if self.has_unique_pieces {
+                let (s0, s1, s2) = (squares[0], squares[1], squares[2]);
+                let adjust1 = usize::from(s1 > s0);
+                let adjust2 = usize::from(s2 > s0) + usize::from(s2 > s1);
+                let idx = if off_diagonal(s0) != 0 {
+                    (indices.map_a1d1d4[s0] * 63 + (s1 - adjust1)) * 62 + s2 - adjust2
+                } else if off_diagonal(s1) != 0 {
+                    (6 * 63 + rank_of(s0) * 28 + indices.map_b1h1h7[s1]) * 62 + s2 - adjust2
+                } else if off_diagonal(s2) != 0 {
+                    6 * 63 * 62
+                        + 4 * 28 * 62
+                        + rank_of(s0) * 7 * 28
+                        + (rank_of(s1) - adjust1) * 28
+                        + indices.map_b1h1h7[s2]
+                } else {
+                    6 * 63 * 62
+                        + 4 * 28 * 62
+                        + 4 * 7 * 28
+                        + rank_of(s0) * 7 * 6
+                        + (rank_of(s1) - adjust1) * 6
+                        + (rank_of(s2) - adjust2)
+                };
+                idx as u64
+            } else {
+                indices.map_kk[indices.map_a1d1d4[squares[0]]][squares[1]]
+            }
+        };
+
+        // the remaining groups are encoded by their squares, skipping the squares taken by
+        // earlier groups, and the ranks pawns can't stand on
+        idx *= d.group_idx[0];
+        let mut group_start = d.group_len[0];
+        let mut remaining_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = 1;
+        while d.group_len[next] != 0 {
+            let len = d.group_len[next];
+            squares[group_start..group_start + len].sort_unstable();
+            let mut n = 0;
+            for i in 0..len {
+                let square = squares[group_start + i];
+                let adjust = squares[..group_start]
+                    .iter()
+                    .filter(|&&other| square > other)
+                    .count();
+                let skipped = if remaining_pawns { 8 } else { 0 };
+                n += indices.binomial[i + 1][square - adjust - skipped];
+            }
+            remaining_pawns = false;
+            idx += n * d.group_idx[next];
+            group_start += len;
+            next += 1;
+        }
+
+        let value = self.decompress_pairs(d, idx)?;
+        if self.dtz {
+            self.map_score(tb_file, value, wdl).map(Some)
+        } else {
+            Ok(Some(value as i32 - 2))
+        }
+    }
+
+    /// Turn a value stored in a DTZ table into a distance to zeroing in plies.
+    fn map_score(&self, tb_file: usize, value: u16, wdl: Wdl) -> Result<i32, String> {
+        let d = &self.items[self.item_index(0, tb_file)];
+        let mut value = u64::from(value);
+        if d.flags & FLAG_MAPPED != 0 {
+            let map_idx = d.map_idx[match wdl {
+                Wdl::Win => 0,
+                Wdl::Loss => 1,
+                Wdl::CursedWin => 2,
+                Wdl::BlessedLoss => 3,
+                Wdl::Draw => 0,
+            }];
+            value = if d.flags & FLAG_WIDE != 0 {
+                u64::from(self.file.u16_le(self.map_offset + 2 * (map_idx + value))?)
+            } else {
+                u64::from(self.file.u8(self.map_offset + map_idx + value)?)
+            };
+        }
+
+        // values are stored in moves rather than plies when that is exact enough
+        let in_moves = match wdl {
+            Wdl::Win => d.flags & FLAG_WIN_PLIES == 0,
+            Wdl::Loss => d.flags & FLAG_LOSS_PLIES == 0,
+            Wdl::CursedWin | Wdl::BlessedLoss => true,
+            Wdl::Draw => false,
+        };
+        if in_moves {
+            value *= 2;
+        }
+        Ok(value as i32 + 1)
+    }
+
+    /// Decode the value stored at index `idx`.
+    ///
+    /// Values are compressed by recursive pairing, where each symbol stands for a pair of
+    /// other symbols, and the symbols are then Huffman coded in blocks. A sparse index points
+    /// into the list of block lengths to find the block holding a value.
+    fn decompress_pairs(&self, d: &PairsData, idx: u64) -> Result<u16, String> {
+        if d.flags & FLAG_SINGLE_VALUE != 0 {
+            return Ok(u16::from(d.min_sym_len));
+        }
+
+        let k = idx / d.span;
+        let mut block = u64::from(self.file.u32_le(d.sparse_index + 6 * k)?);
+        let mut offset = i64::from(self.file.u16_le(d.sparse_index + 6 * k + 4)?);
+        offset += (idx % d.span) as i64 - (d.span / 2) as i64;
+
+        let block_length = |block: u64| -> Result<i64, String> {
+            Ok(i64::from(self.file.u16_le(d.block_lengths + 2 * block)?))
+        };
+        while offset < 0 {
+            block = block
+                .checked_sub(1)
+                .ok_or_else(|| "invalid sparse index in table".to_string())?;
+            offset += block_length(block)? + 1;
+        }
+        loop {
+            let length = block_length(block)?;
+            if offset <= length {
+                break;
+            }
+            offset -= length + 1;
+            block += 1;
+        }
+
+        let start = d.data + block * d.block_size;
+        let mut data = vec![0; d.block_size.min(self.file.len.saturating_sub(start)) as usize];
+        self.file.read(start, &mut data)?;
+        let read_u32 = |pos: usize| -> u64 {
+            let mut bytes = [0; 4];
+            for (i, byte) in bytes.iter_mut().enumerate() {
+                *byte = data.get(pos + i).copied().unwrap_or(0);
+            }
+            u64::from(u32::from_be_bytes(bytes))
+        };
+
+        let mut buf = (read_u32(0) << 32) | read_u32(4);
+        let mut pos = 8;
+        let mut buf_size = 64;
+        let mut sym;
+        loop {
+            let mut len = 0;
+            while len + 1 < d.base64.len() && buf < d.base64[len] {
+                len += 1;
+            }
+            let shift = 64 - len as u32 - u32::from(d.min_sym_len);
+            sym = buf
+                .checked_sub(d.base64[len])
+                .map(|code| (code >> shift) as usize + usize::from(d.lowest_sym[len]))
+                .filter(|&sym| sym < d.symlen.len())
+                .ok_or_else(|| "invalid symbol in table".to_string())?;
+
+            let values = i64::from(d.symlen[sym]) + 1;
+            if offset < values {
+                break;
+            }
+            offset -= values;
+            let len = len as u32 + u32::from(d.min_sym_len);
+            buf = buf.checked_shl(len).unwrap_or(0);
+            buf_size -= len;
+            if buf_size <= 32 {
+                buf_size += 32;
+                buf |= read_u32(pos) << (64 - buf_size);
+                pos += 4;
+            }
+        }
+
+        // expand the symbol until reaching the single value at the offset
+        while d.symlen[sym] != 0 {
+            let left = d.left(sym);
+            let values = i64::from(d.symlen[left]) + 1;
+            if offset < values {
+                sym = left;
+            } else {
+                offset -= values;
+                sym = d.right(sym);
+            }
+        }
+        Ok(d.left(sym) as u16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indices() {
+        let indices = Indices::new();
+        assert_eq!(indices.binomial[2][5], 10);
+        assert_eq!(indices.binomial[0][0], 1);
+        assert_eq!(indices.binomial[3][2], 0);
+
+        // the triangle is numbered below the diagonal first
+        assert_eq!(indices.map_a1d1d4[1], 0);
+        assert_eq!(indices.map_a1d1d4[19], 5);
+        assert_eq!(indices.map_a1d1d4[0], 6);
+        assert_eq!(indices.map_a1d1d4[27], 9);
+
+        let kings = indices.map_kk.iter().flat_map(|kings| kings.iter());
+        assert_eq!(kings.max(), Some(&461));
+
+        assert_eq!(indices.map_pawns[8], 47);
+        assert_eq!(indices.map_pawns[15], 46);
+        assert_eq!(indices.lead_pawns_size[1], [6, 6, 6, 6]);
+    }
+
+    #[test]
+    fn wdl_after_half_moves() {
+        assert_eq!(Wdl::from_dtz(10, 0), Wdl::Win);
+        assert_eq!(Wdl::from_dtz(10, 95), Wdl::CursedWin);
+        assert_eq!(Wdl::from_dtz(101, 0), Wdl::CursedWin);
+        assert_eq!(Wdl::from_dtz(0, 50), Wdl::Draw);
+        assert_eq!(Wdl::from_dtz(-10, 90), Wdl::Loss);
+        assert_eq!(Wdl::from_dtz(-10, 95), Wdl::BlessedLoss);
+        assert_eq!(-Wdl::CursedWin, Wdl::BlessedLoss);
+        assert!(Wdl::CursedWin > Wdl::Draw);
+    }
+
+    #[test]
+    fn bare_kings() {
+        let tablebase = Tablebase::new();
+        let game = Game::from_fen_string("8/8/3k4/8/8/3K4/8/8 w - - 0 1").unwrap();
+        assert_eq!(tablebase.probe_wdl(&game), Ok(Wdl::Draw));
+        assert_eq!(tablebase.probe_dtz(&game), Ok(0));
+        assert_eq!(tablebase.probe_outcome(&game), Ok(Wdl::Draw));
+
+        let ranked = tablebase.rank_root_moves(&game).unwrap();
+        assert_eq!(ranked.len(), game.get_moves().len());
+        assert!(ranked
+            .iter()
+            .all(|ranked_move| ranked_move.wdl() == Wdl::Draw));
+    }
+
+    /// Tables for KQvK and KRvK, written by `tests/syzygy/generate.py`.
+    fn fixtures() -> Tablebase {
+        let mut tablebase = Tablebase::new();
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/syzygy");
+        assert_eq!(tablebase.add_directory(directory), Ok(4));
+        tablebase
+    }
+
+    fn probe(tablebase: &Tablebase, fen: &str) -> (Wdl, i32) {
+        let game = Game::from_fen_string(fen).unwrap();
+        (
+            tablebase.probe_wdl(&game).unwrap(),
+            tablebase.probe_dtz(&game).unwrap(),
+        )
+    }
+
+    #[test]
+    fn queen_against_king() {
+        let tablebase = fixtures();
+        let probe = |fen| probe(&tablebase, fen);
+        assert_eq!(probe("7k/8/6K1/8/8/8/Q7/8 w - - 0 1"), (Wdl::Win, 1));
+        assert_eq!(probe("Q6k/8/6K1/8/8/8/8/8 b - - 0 1"), (Wdl::Loss, -1));
+        // stalemate, and a queen that can be taken
+        assert_eq!(probe("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1"), (Wdl::Draw, 0));
+        assert_eq!(probe("8/8/8/8/8/8/6Qk/4K3 b - - 0 1"), (Wdl::Draw, 0));
+
+        // the longest win is a mate in 10
+        assert_eq!(probe("8/8/8/5k2/8/8/1Q6/K7 w - - 0 1"), (Wdl::Win, 19));
+        assert_eq!(probe("8/8/8/8/4k3/8/1Q6/K7 b - - 0 1"), (Wdl::Loss, -20));
+        assert_eq!(probe("k7/1q6/8/8/5K2/8/8/8 b - - 0 1"), (Wdl::Win, 19));
+
+        assert_eq!(probe("8/8/3k4/8/8/3K4/8/3Q4 w - - 0 1"), (Wdl::Win, 13));
+        assert_eq!(probe("7k/8/8/8/3Q4/8/1K6/8 b - - 0 1"), (Wdl::Loss, -12));
+        assert_eq!(probe("8/8/5Q2/8/8/8/K7/7k b - - 0 1"), (Wdl::Loss, -14));
+        assert_eq!(probe("k6Q/8/8/8/8/8/K7/8 b - - 0 1"), (Wdl::Loss, -14));
+
+        let game = Game::from_fen_string("7k/8/6K1/8/8/8/Q7/8 w - - 0 1").unwrap();
+        let ranked = tablebase.rank_root_moves(&game).unwrap();
+        assert_eq!(ranked.len(), game.get_moves().len());
+        assert_eq!(ranked[0].chess_move(), game.parse_uci_move("a2a8").unwrap());
+        assert_eq!((ranked[0].wdl(), ranked[0].dtz()), (Wdl::Win, 1));
+    }
+
+    #[test]
+    fn rook_against_king() {
+        let tablebase = fixtures();
+        let probe = |fen| probe(&tablebase, fen);
+        assert_eq!(probe("7k/8/6K1/8/8/8/8/R7 w - - 0 1"), (Wdl::Win, 1));
+        assert_eq!(probe("k7/2R5/1K6/8/8/8/8/8 b - - 0 1"), (Wdl::Loss, -4));
+        assert_eq!(probe("8/8/8/8/8/8/6Rk/4K3 b - - 0 1"), (Wdl::Draw, 0));
+
+        // the longest win is a mate in 16
+        assert_eq!(probe("8/8/8/8/8/2k5/1R6/K7 w - - 0 1"), (Wdl::Win, 31));
+        assert_eq!(probe("8/8/8/8/8/8/1Rk5/K7 b - - 0 1"), (Wdl::Loss, -32));
+
+        assert_eq!(probe("8/6k1/8/4R3/8/2K5/8/8 w - - 0 1"), (Wdl::Win, 19));
+        assert_eq!(probe("8/8/3k4/8/8/3K4/8/3R4 w - - 0 1"), (Wdl::Win, 23));
+        assert_eq!(probe("8/1R6/5K2/8/8/8/6k1/8 b - - 0 1"), (Wdl::Loss, -20));
+        assert_eq!(probe("8/5R2/2K5/8/8/1k6/8/8 w - - 0 1"), (Wdl::Win, 17));
+        assert_eq!(probe("8/8/8/k7/8/2R5/8/4K3 w - - 0 1"), (Wdl::Win, 17));
+
+        // too late to mate before the fifty-move rule
+        let game = Game::from_fen_string("8/8/8/8/8/2k5/1R6/K7 w - - 75 100").unwrap();
+        assert_eq!(tablebase.probe_outcome(&game), Ok(Wdl::CursedWin));
+    }
+
+    #[test]
+    fn missing_tables() {
+        let mut tablebase = Tablebase::new();
+        let game = Game::from_fen_string("8/8/3k4/8/8/3K4/8/7Q b - - 0 1").unwrap();
+        assert_eq!(
+            tablebase.probe_wdl(&game),
+            Err("missing table KQvK.rtbw".to_string())
+        );
+        assert!(tablebase.probe_wdl(&Game::default()).is_err());
+
+        let directory = std::env::temp_dir().join(format!("syzygy-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("KQvK.rtbw"), b"not a table").unwrap();
+        fs::write(directory.join("README"), b"").unwrap();
+        assert_eq!(tablebase.add_directory(&directory), Ok(1));
+        assert!(tablebase
+            .probe_wdl(&game)
+            .unwrap_err()
+            .ends_with("is not a Syzygy table"));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
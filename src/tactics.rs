@@ -0,0 +1,276 @@
+use crate::{chess_board::ChessBoard, chess_move::ChessMove, game::Game, piece::PieceType, Color};
+use bitboard::*;
+
+/// Whether a pinned piece is pinned to its king or to another piece.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinKind {
+    /// The piece is pinned to its king, so it may only move along the pin ray.
+    Absolute,
+    /// The piece is pinned to a more valuable piece, which would be attacked if it moved.
+    Relative,
+}
+
+/// A piece that can't leave the line between a sliding piece and a piece behind it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pin {
+    pinned: Position,
+    pinner: Position,
+    pinned_to: Position,
+    ray: Bitboard,
+    kind: PinKind,
+}
+
+impl Pin {
+    pub fn pinned(&self) -> Position {
+        self.pinned
+    }
+
+    /// The sliding piece holding the pin.
+    pub fn pinner(&self) -> Position {
+        self.pinner
+    }
+
+    /// The king or more valuable piece behind the pinned piece.
+    pub fn pinned_to(&self) -> Position {
+        self.pinned_to
+    }
+
+    /// The squares from the pinner up to the piece pinned to, which the pinned piece can move
+    /// to without breaking the pin, including the pinner itself.
+    pub fn ray(&self) -> Bitboard {
+        self.ray
+    }
+
+    pub fn kind(&self) -> PinKind {
+        self.kind
+    }
+}
+
+/// An attack by a sliding piece through exactly one other piece.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XRay {
+    attacker: Position,
+    blocker: Position,
+    target: Position,
+    ray: Bitboard,
+}
+
+impl XRay {
+    /// The sliding piece.
+    pub fn attacker(&self) -> Position {
+        self.attacker
+    }
+
+    /// The piece in between, of either color.
+    pub fn blocker(&self) -> Position {
+        self.blocker
+    }
+
+    /// The piece behind the blocker, of either color. A piece of the attacker's own color is
+    /// defended through the blocker.
+    pub fn target(&self) -> Position {
+        self.target
+    }
+
+    /// The squares between the attacker and the target, including the blocker.
+    pub fn ray(&self) -> Bitboard {
+        self.ray
+    }
+}
+
+/// A move that uncovers an attack by a sliding piece behind the moved piece.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiscoveredAttack {
+    chess_move: ChessMove,
+    attacker: Position,
+    target: Position,
+    is_check: bool,
+}
+
+impl DiscoveredAttack {
+    pub fn chess_move(&self) -> ChessMove {
+        self.chess_move
+    }
+
+    /// The sliding piece whose attack is uncovered.
+    pub fn attacker(&self) -> Position {
+        self.attacker
+    }
+
+    /// The opponent's piece that is attacked after the move.
+    pub fn target(&self) -> Position {
+        self.target
+    }
+
+    /// Returns `true` if the uncovered attack is on the opponent's king.
+    pub fn is_check(&self) -> bool {
+        self.is_check
+    }
+}
+
+/// Find every piece of `color` that is pinned by a sliding piece of the opponent.
+///
+/// A piece is absolutely pinned if its king is behind it, and relatively pinned if a more
+/// valuable piece is.
+pub fn pins(board: &ChessBoard, color: Color) -> Vec<Pin> {
+    let mut pins = Vec::new();
+    for x_ray in x_rays(board, color.opponent()) {
+        let pinned = board.get_piece(x_ray.blocker).unwrap();
+        let pinned_to = board.get_piece(x_ray.target).unwrap();
+        if pinned.color() != color || pinned_to.color() != color {
+            continue;
+        }
+        let kind = if pinned_to.kind() == PieceType::King {
+            PinKind::Absolute
+        } else if pinned_to.kind().value() > pinned.kind().value() {
+            PinKind::Relative
+        } else {
+            continue;
+        };
+        pins.push(Pin {
+            pinned: x_ray.blocker,
+            pinner: x_ray.attacker,
+            pinned_to: x_ray.target,
+            ray: x_ray.ray | Bitboard::with_one(x_ray.attacker),
+            kind,
+        });
+    }
+    pins
+}
+
+/// Find every attack by a sliding piece of `color` through exactly one other piece.
+pub fn x_rays(board: &ChessBoard, color: Color) -> Vec<XRay> {
+    let occupancy = board.full_occupancy();
+    let mut x_rays = Vec::new();
+    for attacker in board.get_occupancy_for_color(color).positions() {
+        let lines: &[bool] = match board.get_piece(attacker).unwrap().kind() {
+            PieceType::Bishop => &[true],
+            PieceType::Rook => &[false],
+            PieceType::Queen => &[true, false],
+            _ => &[],
+        };
+        for &diagonal in lines {
+            let targets = line_targets(attacker, occupancy, diagonal);
+            for blocker in (targets & occupancy).positions() {
+                let without_blocker = occupancy & !Bitboard::with_one(blocker);
+                let x_ray_targets = line_targets(attacker, without_blocker, diagonal);
+                // only the line through the blocker changes
+                let target = match (x_ray_targets & !targets & occupancy).first_position() {
+                    Some(target) => target,
+                    None => continue,
+                };
+                x_rays.push(XRay {
+                    attacker,
+                    blocker,
+                    target,
+                    ray: x_ray_targets & line_targets(target, without_blocker, diagonal),
+                });
+            }
+        }
+    }
+    x_rays
+}
+
+/// Find every legal move of the current player that uncovers an attack on an opponent's piece
+/// by moving a piece out of the way of a sliding piece.
+pub fn discovered_attacks(game: &Game) -> Vec<DiscoveredAttack> {
+    let board = game.board();
+    let player = game.current_player();
+    let mut moves: Vec<ChessMove> = game.get_moves().iter().copied().collect();
+    moves.sort_by_cached_key(|chess_move| chess_move.to_uci_string());
+
+    let mut discovered_attacks = Vec::new();
+    for x_ray in x_rays(&board, player) {
+        if !board.has_piece_of_color_at(player, x_ray.blocker)
+            || !board.has_piece_of_color_at(player.opponent(), x_ray.target)
+        {
+            continue;
+        }
+        let line = x_ray.ray | Bitboard::with_one(x_ray.target);
+        for &chess_move in &moves {
+            if chess_move.from() == x_ray.blocker && line & chess_move.to() == 0 {
+                discovered_attacks.push(DiscoveredAttack {
+                    chess_move,
+                    attacker: x_ray.attacker,
+                    target: x_ray.target,
+                    is_check: board.get_piece(x_ray.target).unwrap().kind() == PieceType::King,
+                });
+            }
+        }
+    }
+    discovered_attacks
+}
+
+/// The squares a sliding piece on `pos` attacks along diagonals, or along ranks and files.
+fn line_targets(pos: Position, occupancy: Bitboard, diagonal: bool) -> Bitboard {
+    if diagonal {
+        Bitboard::bishop_targets(pos, occupancy)
+    } else {
+        Bitboard::rook_targets(pos, occupancy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn absolute_pin() {
+        let game = Game::from_fen_string("4k3/4r3/8/8/8/8/4N3/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            pins(&game.board(), Color::White),
+            vec![Pin {
+                pinned: E2,
+                pinner: E7,
+                pinned_to: E1,
+                ray: Bitboard::with_ones([E2, E3, E4, E5, E6, E7]),
+                kind: PinKind::Absolute,
+            }]
+        );
+        assert!(game
+            .get_moves()
+            .iter()
+            .all(|chess_move| chess_move.from() != E2));
+        assert!(pins(&game.board(), Color::Black).is_empty());
+    }
+
+    #[test]
+    fn relative_pin_and_x_ray() {
+        let game = Game::from_fen_string("7k/8/8/b7/8/2N5/8/4Q1K1 w - - 0 1").unwrap();
+        assert_eq!(
+            pins(&game.board(), Color::White),
+            vec![Pin {
+                pinned: C3,
+                pinner: A5,
+                pinned_to: E1,
+                ray: Bitboard::with_ones([A5, B4, C3, D2]),
+                kind: PinKind::Relative,
+            }]
+        );
+        assert_eq!(
+            x_rays(&game.board(), Color::White),
+            vec![XRay {
+                attacker: E1,
+                blocker: C3,
+                target: A5,
+                ray: Bitboard::with_ones([B4, C3, D2]),
+            }]
+        );
+    }
+
+    #[test]
+    fn discovered_check() {
+        let game = Game::from_fen_string("4k3/8/8/8/8/8/4N3/4R1K1 w - - 0 1").unwrap();
+        let discovered = discovered_attacks(&game);
+        let moves: Vec<String> = discovered
+            .iter()
+            .map(|attack| {
+                assert_eq!(attack.attacker(), E1);
+                assert_eq!(attack.target(), E8);
+                assert!(attack.is_check());
+                attack.chess_move().to_uci_string()
+            })
+            .collect();
+        assert_eq!(moves, vec!["e2c1", "e2c3", "e2d4", "e2f4", "e2g3"]);
+    }
+}
@@ -0,0 +1,390 @@
+//! An engine frontend for the Universal Chess Interface (UCI) protocol.
+//!
+//! The protocol is line based: the GUI writes commands to the engine's standard input, and the
+//! engine answers on its standard output. [`run_uci`] reads commands from any reader and writes
+//! answers to any writer, so the engine can be driven by tests as well as by a GUI.
+
+use crate::{
+    game::Game,
+    search::{allocate_time, is_mate_score, Search, SearchResult, MATE_SCORE},
+    Color,
+};
+use std::{
+    io::{self, BufRead, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+const ENGINE_NAME: &str = "chess";
+const ENGINE_AUTHOR: &str = "Isak Jägberg";
+
+/// Depth used when `go` does not limit the depth of the search.
+const MAX_DEPTH: u32 = 64;
+
+/// Run the UCI protocol, reading commands from `input` and writing answers to `output`, until
+/// `quit` is received or `input` is exhausted.
+///
+/// When `input` is exhausted, a search that was started with a depth or time limit is allowed
+/// to finish before returning.
+pub fn run_uci<R, W>(input: R, output: W) -> io::Result<()>
+where
+    R: BufRead,
+    W: Write + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel::<String>();
+    let writer = thread::spawn(move || -> io::Result<()> {
+        let mut output = output;
+        for line in receiver {
+            writeln!(output, "{}", line)?;
+            output.flush()?;
+        }
+        Ok(())
+    });
+
+    let mut engine = Engine::new(sender);
+    for line in input.lines() {
+        if !engine.handle_command(&line?) {
+            break;
+        }
+    }
+    engine.finish();
+    drop(engine);
+
+    writer.join().expect("writer thread panicked")
+}
+
+/// Limits given to the `go` command.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct GoLimits {
+    depth: Option<u32>,
+    movetime: Option<Duration>,
+    wtime: Option<Duration>,
+    btime: Option<Duration>,
+    winc: Option<Duration>,
+    binc: Option<Duration>,
+    movestogo: Option<u32>,
+    infinite: bool,
+}
+
+impl GoLimits {
+    fn parse(args: &[&str]) -> Self {
+        let mut limits = Self::default();
+        let mut args = args.iter();
+        while let Some(&arg) = args.next() {
+            let mut next_number = || args.next().and_then(|value| value.parse::<u64>().ok());
+            match arg {
+                "depth" => limits.depth = next_number().map(|depth| depth as u32),
+                "movetime" => limits.movetime = next_number().map(Duration::from_millis),
+                "wtime" => limits.wtime = next_number().map(Duration::from_millis),
+                "btime" => limits.btime = next_number().map(Duration::from_millis),
+                "winc" => limits.winc = next_number().map(Duration::from_millis),
+                "binc" => limits.binc = next_number().map(Duration::from_millis),
+                "movestogo" => limits.movestogo = next_number().map(|moves| moves as u32),
+                "infinite" => limits.infinite = true,
+                _ => {}
+            }
+        }
+        limits
+    }
+
+    /// How long to search for a move by `player`, or `None` if there is no time limit.
+    fn time_budget(&self, player: Color) -> Option<Duration> {
+        if self.infinite {
+            return None;
+        }
+        if let Some(movetime) = self.movetime {
+            return Some(movetime);
+        }
+
+        let (time, increment) = match player {
+            Color::White => (self.wtime?, self.winc.unwrap_or_default()),
+            Color::Black => (self.btime?, self.binc.unwrap_or_default()),
+        };
+        Some(allocate_time(time, increment, self.movestogo))
+    }
+}
+
+/// The state of the engine between commands.
+struct Engine {
+    game: Game,
+    quiescence_checks: bool,
+    sender: Sender<String>,
+    search: Option<(Arc<AtomicBool>, JoinHandle<()>, bool)>,
+}
+
+impl Engine {
+    fn new(sender: Sender<String>) -> Self {
+        Self {
+            game: Game::default(),
+            quiescence_checks: false,
+            sender,
+            search: None,
+        }
+    }
+
+    fn send(&self, line: impl Into<String>) {
+        // the writer only hangs up once the engine is done
+        let _ = self.sender.send(line.into());
+    }
+
+    /// Handle a single command.
+    ///
+    /// # Returns
+    /// `false` if the engine should quit.
+    fn handle_command(&mut self, line: &str) -> bool {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let (&command, args) = match parts.split_first() {
+            Some(split) => split,
+            None => return true,
+        };
+
+        match command {
+            "uci" => {
+                self.send(format!("id name {}", ENGINE_NAME));
+                self.send(format!("id author {}", ENGINE_AUTHOR));
+                self.send("option name QuiescenceChecks type check default false");
+                self.send("uciok");
+            }
+            "isready" => self.send("readyok"),
+            "ucinewgame" => {
+                self.stop_search();
+                self.game = Game::default();
+            }
+            "setoption" => self.set_option(args),
+            "position" => {
+                self.stop_search();
+                if let Err(e) = self.set_position(args) {
+                    self.send(format!("info string {}", e));
+                }
+            }
+            "go" => {
+                self.stop_search();
+                self.go(GoLimits::parse(args));
+            }
+            "stop" => self.stop_search(),
+            "quit" => {
+                self.stop_search();
+                return false;
+            }
+            "debug" | "register" | "ponderhit" => {}
+            _ => self.send(format!("info string unknown command '{}'", command)),
+        }
+
+        true
+    }
+
+    fn set_option(&mut self, args: &[&str]) {
+        // setoption name <id> [value <x>]
+        let value_index = args.iter().position(|&arg| arg == "value");
+        let name = args[..value_index.unwrap_or(args.len())]
+            .iter()
+            .copied()
+            .skip_while(|&arg| arg == "name")
+            .collect::<Vec<_>>()
+            .join(" ");
+        let value = value_index
+            .map(|index| args[index + 1..].join(" "))
+            .unwrap_or_default();
+
+        match name.to_lowercase().as_str() {
+            "quiescencechecks" => self.quiescence_checks = value == "true",
+            _ => self.send(format!("info string unknown option '{}'", name)),
+        }
+    }
+
+    fn set_position(&mut self, args: &[&str]) -> Result<(), String> {
+        let moves_index = args.iter().position(|&arg| arg == "moves");
+        let (position, moves) = match moves_index {
+            Some(index) => (&args[..index], &args[index + 1..]),
+            None => (args, &[][..]),
+        };
+
+        let mut game = match position.split_first() {
+            Some((&"startpos", _)) => Game::default(),
+            Some((&"fen", fen)) => Game::from_fen_string(&fen.join(" "))?,
+            _ => return Err("expected 'startpos' or 'fen'".to_string()),
+        };
+        for uci in moves {
+            let chess_move = game.parse_uci_move(uci)?;
+            game.make_move(chess_move)?;
+        }
+
+        self.game = game;
+        Ok(())
+    }
+
+    fn go(&mut self, limits: GoLimits) {
+        let stop_signal = Arc::new(AtomicBool::new(false));
+        let mut search = Search::new(limits.depth.unwrap_or(MAX_DEPTH))
+            .with_quiescence_checks(self.quiescence_checks)
+            .with_stop_signal(Arc::clone(&stop_signal));
+        let start = Instant::now();
+        // a budget too far in the future to represent means there is no deadline
+        if let Some(deadline) = limits
+            .time_budget(self.game.current_player())
+            .and_then(|budget| start.checked_add(budget))
+        {
+            search = search.with_deadline(deadline);
+        }
+
+        let game = self.game.clone();
+        let sender = self.sender.clone();
+        let thread_stop_signal = Arc::clone(&stop_signal);
+        let handle = thread::spawn(move || {
+            let result = search.run_with_callback(&game, |result| {
+                let _ = sender.send(info_line(result, start.elapsed()));
+            });
+            if limits.infinite {
+                // in infinite mode the best move may only be sent after `stop`
+                while !thread_stop_signal.load(Ordering::Relaxed) {
+                    thread::sleep(Duration::from_millis(5));
+                }
+            }
+            let best_move = result
+                .best_move()
+                .map(|chess_move| chess_move.to_uci_string())
+                .unwrap_or_else(|| "0000".to_string());
+            let _ = sender.send(format!("bestmove {}", best_move));
+        });
+
+        self.search = Some((stop_signal, handle, limits.infinite));
+    }
+
+    /// Stop the current search, if any, and wait for it to send its best move.
+    fn stop_search(&mut self) {
+        if let Some((stop_signal, handle, _)) = self.search.take() {
+            stop_signal.store(true, Ordering::Relaxed);
+            handle.join().expect("search thread panicked");
+        }
+    }
+
+    /// Wait for a limited search to finish, and stop an infinite one.
+    fn finish(&mut self) {
+        if let Some((stop_signal, handle, infinite)) = self.search.take() {
+            if infinite {
+                stop_signal.store(true, Ordering::Relaxed);
+            }
+            handle.join().expect("search thread panicked");
+        }
+    }
+}
+
+fn info_line(result: &SearchResult, elapsed: Duration) -> String {
+    let score = if is_mate_score(result.score()) {
+        let plies = MATE_SCORE - result.score().abs();
+        let moves = (plies + 1) / 2;
+        format!("mate {}", if result.score() > 0 { moves } else { -moves })
+    } else {
+        format!("cp {}", result.score())
+    };
+    let mut line = format!(
+        "info depth {} score {} nodes {} time {}",
+        result.depth(),
+        score,
+        result.nodes(),
+        elapsed.as_millis()
+    );
+    if let Some(best_move) = result.best_move() {
+        line.push_str(&format!(" pv {}", best_move.to_uci_string()));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::Cursor,
+        sync::{Arc, Mutex},
+    };
+
+    /// A writer that can be inspected after it has been moved into [`run`].
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn run_commands(commands: &str) -> Vec<String> {
+        let output = SharedBuffer::default();
+        run_uci(Cursor::new(commands.to_string()), output.clone()).unwrap();
+        let bytes = output.0.lock().unwrap().clone();
+        String::from_utf8(bytes)
+            .unwrap()
+            .lines()
+            .map(|line| line.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn handshake() {
+        let output = run_commands("uci\nisready\nquit\n");
+        assert_eq!(output[0], "id name chess");
+        assert!(output.contains(&"uciok".to_string()));
+        assert_eq!(output.last().unwrap(), "readyok");
+    }
+
+    #[test]
+    fn mate_in_one() {
+        let output = run_commands(
+            "position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\n\
+             go depth 3\n",
+        );
+        assert!(output
+            .iter()
+            .any(|line| line.starts_with("info depth 1 score mate 1")));
+        assert_eq!(output.last().unwrap(), "bestmove a1a8");
+    }
+
+    #[test]
+    fn position_with_moves() {
+        let output = run_commands(
+            "position startpos moves f2f3 e7e5 g2g4\n\
+             go depth 2\n",
+        );
+        assert_eq!(output.last().unwrap(), "bestmove d8h4");
+
+        let output = run_commands("position startpos moves e2e5\n");
+        assert_eq!(output, vec!["info string illegal move 'e2e5'"]);
+    }
+
+    #[test]
+    fn stop_infinite_search() {
+        let output = run_commands("position startpos\ngo infinite\nstop\nquit\n");
+        let best_move = output.last().unwrap().strip_prefix("bestmove ").unwrap();
+        assert!(Game::default().parse_uci_move(best_move).is_ok());
+    }
+
+    #[test]
+    fn time_budget() {
+        let limits = GoLimits::parse(&["wtime", "60000", "btime", "1500", "winc", "1000"]);
+        assert_eq!(
+            limits.time_budget(Color::White),
+            Some(Duration::from_millis(2500))
+        );
+        assert_eq!(
+            limits.time_budget(Color::Black),
+            Some(Duration::from_millis(50))
+        );
+        assert_eq!(
+            GoLimits::parse(&["infinite"]).time_budget(Color::White),
+            None
+        );
+        assert_eq!(
+            GoLimits::parse(&["movetime", "100"]).time_budget(Color::Black),
+            Some(Duration::from_millis(100))
+        );
+    }
+}
@@ -0,0 +1,548 @@
+//! A client for driving external chess engines over the Universal Chess Interface (UCI)
+//! protocol.
+//!
+//! The engine is started as a child process, and positions are sent to it as the starting
+//! position of a [`Game`] followed by the moves made since. Moves in the engine's answers are
+//! checked against the game and returned as [`ChessMove`]s.
+
+use crate::{chess_move::ChessMove, game::Game};
+use std::{
+    ffi::OsStr,
+    io::{self, BufRead, BufReader, Write},
+    process::{Child, ChildStdin, ChildStdout, Command, ExitStatus, Stdio},
+    time::Duration,
+};
+
+/// A running UCI engine.
+#[derive(Debug)]
+pub struct UciClient {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    name: Option<String>,
+    author: Option<String>,
+    options: Vec<EngineOption>,
+}
+
+/// An option declared by an engine during the handshake.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineOption {
+    name: String,
+    kind: String,
+    default: Option<String>,
+}
+
+impl EngineOption {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The type of the option, such as `check`, `spin`, `combo`, `button` or `string`.
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    pub fn default(&self) -> Option<&str> {
+        self.default.as_deref()
+    }
+}
+
+/// How long an engine may search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchLimit {
+    Depth(u32),
+    Nodes(u64),
+    MoveTime(Duration),
+    /// Let the engine manage the time left on both players' clocks.
+    Clock {
+        white_time: Duration,
+        black_time: Duration,
+        white_increment: Duration,
+        black_increment: Duration,
+    },
+}
+
+impl SearchLimit {
+    fn to_uci_string(self) -> String {
+        match self {
+            SearchLimit::Depth(depth) => format!("depth {}", depth),
+            SearchLimit::Nodes(nodes) => format!("nodes {}", nodes),
+            SearchLimit::MoveTime(time) => format!("movetime {}", time.as_millis()),
+            SearchLimit::Clock {
+                white_time,
+                black_time,
+                white_increment,
+                black_increment,
+            } => format!(
+                "wtime {} btime {} winc {} binc {}",
+                white_time.as_millis(),
+                black_time.as_millis(),
+                white_increment.as_millis(),
+                black_increment.as_millis()
+            ),
+        }
+    }
+}
+
+/// A score reported by an engine, from the perspective of the player to move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Score {
+    Centipawns(i32),
+    /// Mate in the given number of moves. Negative if the engine is getting mated.
+    Mate(i32),
+}
+
+/// A line of `info` output from an engine.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EngineInfo {
+    depth: Option<u32>,
+    selective_depth: Option<u32>,
+    multi_pv: Option<u32>,
+    score: Option<Score>,
+    lower_bound: bool,
+    upper_bound: bool,
+    nodes: Option<u64>,
+    nodes_per_second: Option<u64>,
+    time: Option<Duration>,
+    pv: Vec<ChessMove>,
+    string: Option<String>,
+}
+
+impl EngineInfo {
+    pub fn depth(&self) -> Option<u32> {
+        self.depth
+    }
+
+    pub fn selective_depth(&self) -> Option<u32> {
+        self.selective_depth
+    }
+
+    pub fn multi_pv(&self) -> Option<u32> {
+        self.multi_pv
+    }
+
+    pub fn score(&self) -> Option<Score> {
+        self.score
+    }
+
+    /// Returns `true` if the score is only a lower bound.
+    pub fn is_lower_bound(&self) -> bool {
+        self.lower_bound
+    }
+
+    /// Returns `true` if the score is only an upper bound.
+    pub fn is_upper_bound(&self) -> bool {
+        self.upper_bound
+    }
+
+    pub fn nodes(&self) -> Option<u64> {
+        self.nodes
+    }
+
+    pub fn nodes_per_second(&self) -> Option<u64> {
+        self.nodes_per_second
+    }
+
+    pub fn time(&self) -> Option<Duration> {
+        self.time
+    }
+
+    /// The principal variation, up to the first move that is not legal in the game.
+    pub fn pv(&self) -> &[ChessMove] {
+        &self.pv
+    }
+
+    /// Free-form text sent with `info string`.
+    pub fn string(&self) -> Option<&str> {
+        self.string.as_deref()
+    }
+
+    /// Parse the arguments of an `info` line, checking the moves of the principal variation
+    /// against `game`.
+    fn parse(args: &[&str], game: &Game) -> Self {
+        let mut info = Self::default();
+        let mut index = 0;
+        while index < args.len() {
+            let next = args.get(index + 1).copied().unwrap_or_default();
+            match args[index] {
+                "depth" => info.depth = next.parse().ok(),
+                "seldepth" => info.selective_depth = next.parse().ok(),
+                "multipv" => info.multi_pv = next.parse().ok(),
+                "nodes" => info.nodes = next.parse().ok(),
+                "nps" => info.nodes_per_second = next.parse().ok(),
+                "time" => info.time = next.parse().ok().map(Duration::from_millis),
+                "score" => {
+                    let value = args.get(index + 2).and_then(|value| value.parse().ok());
+                    info.score = match (next, value) {
+                        ("cp", Some(value)) => Some(Score::Centipawns(value)),
+                        ("mate", Some(value)) => Some(Score::Mate(value)),
+                        _ => None,
+                    };
+                    index += 1;
+                }
+                "lowerbound" | "upperbound" => {
+                    info.lower_bound |= args[index] == "lowerbound";
+                    info.upper_bound |= args[index] == "upperbound";
+                    index += 1;
+                    continue;
+                }
+                "pv" => {
+                    let mut game = game.clone();
+                    for uci in &args[index + 1..] {
+                        match game.parse_uci_move(uci) {
+                            Ok(chess_move) => {
+                                game.make_move(chess_move).unwrap();
+                                info.pv.push(chess_move);
+                            }
+                            Err(_) => break,
+                        }
+                    }
+                    // the principal variation is always last
+                    break;
+                }
+                "string" => {
+                    info.string = Some(args[index + 1..].join(" "));
+                    break;
+                }
+                // unknown keys are skipped, along with anything that follows them
+                _ => {
+                    index += 1;
+                    continue;
+                }
+            }
+            index += 2;
+        }
+        info
+    }
+}
+
+/// The answer of an engine to a `go` command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BestMove {
+    best_move: Option<ChessMove>,
+    ponder: Option<ChessMove>,
+    info: Vec<EngineInfo>,
+}
+
+impl BestMove {
+    /// The move chosen by the engine, or `None` if the engine had no legal moves.
+    pub fn best_move(&self) -> Option<ChessMove> {
+        self.best_move
+    }
+
+    /// The reply the engine expects to the best move.
+    pub fn ponder(&self) -> Option<ChessMove> {
+        self.ponder
+    }
+
+    /// Every `info` line sent during the search, in the order they were sent.
+    pub fn info(&self) -> &[EngineInfo] {
+        &self.info
+    }
+}
+
+impl UciClient {
+    /// Start the engine at `program` and perform the `uci` handshake.
+    pub fn start<P: AsRef<OsStr>>(program: P) -> io::Result<Self> {
+        Self::start_command(Command::new(program))
+    }
+
+    /// Start the engine with `command`, which may set arguments, environment variables or a
+    /// working directory, and perform the `uci` handshake.
+    pub fn start_command(mut command: Command) -> io::Result<Self> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let mut client = Self {
+            child,
+            stdin,
+            stdout,
+            name: None,
+            author: None,
+            options: Vec::new(),
+        };
+
+        client.send("uci")?;
+        loop {
+            let line = client.read_line()?;
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            match parts.as_slice() {
+                ["uciok"] => break,
+                ["id", "name", name @ ..] => client.name = Some(name.join(" ")),
+                ["id", "author", author @ ..] => client.author = Some(author.join(" ")),
+                ["option", "name", rest @ ..] => client.options.push(parse_option(rest)),
+                _ => {}
+            }
+        }
+
+        Ok(client)
+    }
+
+    /// The name the engine identified itself with.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// The author the engine identified itself with.
+    pub fn author(&self) -> Option<&str> {
+        self.author.as_deref()
+    }
+
+    /// The options declared by the engine.
+    pub fn options(&self) -> &[EngineOption] {
+        &self.options
+    }
+
+    /// Set an option, and wait for the engine to be ready again.
+    pub fn set_option(&mut self, name: &str, value: &str) -> io::Result<()> {
+        self.send(&format!("setoption name {} value {}", name, value))?;
+        self.wait_until_ready()
+    }
+
+    /// Tell the engine that the next position is from a different game.
+    pub fn new_game(&mut self) -> io::Result<()> {
+        self.send("ucinewgame")?;
+        self.wait_until_ready()
+    }
+
+    /// Send `isready` and wait for `readyok`.
+    pub fn wait_until_ready(&mut self) -> io::Result<()> {
+        self.send("isready")?;
+        while self.read_line()?.trim() != "readyok" {}
+        Ok(())
+    }
+
+    /// Send the current position of `game` to the engine, as its starting position followed by
+    /// the moves made since.
+    pub fn set_position(&mut self, game: &Game) -> io::Result<()> {
+        self.send(&position_command(game))
+    }
+
+    /// Let the engine search the current position of `game`, and wait for its best move.
+    pub fn go(&mut self, game: &Game, limit: SearchLimit) -> io::Result<BestMove> {
+        self.set_position(game)?;
+        self.send(&format!("go {}", limit.to_uci_string()))?;
+
+        let mut info = Vec::new();
+        loop {
+            let line = self.read_line()?;
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            match parts.split_first() {
+                Some((&"info", args)) => info.push(EngineInfo::parse(args, game)),
+                Some((&"bestmove", args)) => {
+                    let (best_move, ponder) = parse_best_move(args, game)?;
+                    return Ok(BestMove {
+                        best_move,
+                        ponder,
+                        info,
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Tell the engine to quit, and wait for it to exit.
+    pub fn quit(mut self) -> io::Result<ExitStatus> {
+        self.send("quit")?;
+        self.child.wait()
+    }
+
+    fn send(&mut self, line: &str) -> io::Result<()> {
+        writeln!(self.stdin, "{}", line)?;
+        self.stdin.flush()
+    }
+
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut line = String::new();
+        if self.stdout.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "engine closed its output",
+            ));
+        }
+        Ok(line)
+    }
+}
+
+impl Drop for UciClient {
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            let _ = self.send("quit");
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+fn position_command(game: &Game) -> String {
+    let mut command = format!("position fen {}", game.start_fen_string());
+    if !game.move_history().is_empty() {
+        command.push_str(" moves");
+        for chess_move in game.move_history() {
+            command.push(' ');
+            command.push_str(&chess_move.to_uci_string());
+        }
+    }
+    command
+}
+
+/// Parse the part of an `option` line after `option name`.
+fn parse_option(args: &[&str]) -> EngineOption {
+    let type_index = args
+        .iter()
+        .position(|&arg| arg == "type")
+        .unwrap_or(args.len());
+    let name = args[..type_index].join(" ");
+    let kind = args.get(type_index + 1).copied().unwrap_or_default();
+    let default = args.iter().position(|&arg| arg == "default").map(|index| {
+        args[index + 1..]
+            .iter()
+            .copied()
+            .take_while(|&arg| !["min", "max", "var"].contains(&arg))
+            .collect::<Vec<_>>()
+            .join(" ")
+    });
+    EngineOption {
+        name,
+        kind: kind.to_string(),
+        default,
+    }
+}
+
+/// Parse the arguments of `bestmove <move> [ponder <move>]`.
+fn parse_best_move(
+    args: &[&str],
+    game: &Game,
+) -> io::Result<(Option<ChessMove>, Option<ChessMove>)> {
+    let uci = args.first().copied().unwrap_or("(none)");
+    if uci == "(none)" || uci == "0000" {
+        return Ok((None, None));
+    }
+    let best_move = game
+        .parse_uci_move(uci)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let ponder = match args {
+        [_, "ponder", ponder, ..] => {
+            let mut game = game.clone();
+            game.make_move(best_move).unwrap();
+            game.parse_uci_move(ponder).ok()
+        }
+        _ => None,
+    };
+    Ok((Some(best_move), ponder))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use bitboard::*;
+    use std::{fs, path::PathBuf};
+
+    /// Write a shell script that answers UCI commands with canned output, and echoes the
+    /// position it was given as an `info string`.
+    fn stub_engine(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{}-{}.sh", name, std::process::id()));
+        fs::write(
+            &path,
+            r#"#!/bin/sh
+while read -r line; do
+    case "$line" in
+        uci)
+            echo "id name Stub Engine"
+            echo "id author The Tests"
+            echo "option name Hash type spin default 16 min 1 max 1024"
+            echo "option name Ponder type check default false"
+            echo "uciok"
+            ;;
+        isready) echo "readyok" ;;
+        position*) echo "info string $line" ;;
+        go*)
+            echo "info depth 1 seldepth 2 score cp 20 nodes 30 nps 1000 time 3 pv e7e5 g1f3"
+            echo "info depth 2 score cp -15 upperbound nodes 200 pv e7e5 e7e5"
+            echo "info depth 3 score mate -2 pv e7e5"
+            echo "bestmove e7e5 ponder g1f3"
+            ;;
+        quit) exit 0 ;;
+    esac
+done
+"#,
+        )
+        .unwrap();
+        path
+    }
+
+    fn start_stub_engine(path: &PathBuf) -> UciClient {
+        let mut command = Command::new("sh");
+        command.arg(path);
+        UciClient::start_command(command).unwrap()
+    }
+
+    #[test]
+    fn handshake() {
+        let path = stub_engine("handshake");
+        let mut client = start_stub_engine(&path);
+        assert_eq!(client.name(), Some("Stub Engine"));
+        assert_eq!(client.author(), Some("The Tests"));
+        assert_eq!(client.options().len(), 2);
+        assert_eq!(client.options()[0].name(), "Hash");
+        assert_eq!(client.options()[0].kind(), "spin");
+        assert_eq!(client.options()[0].default(), Some("16"));
+        client.set_option("Hash", "32").unwrap();
+        client.new_game().unwrap();
+        assert!(client.quit().unwrap().success());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn go() {
+        let path = stub_engine("go");
+        let mut client = start_stub_engine(&path);
+        let mut game = Game::default();
+        game.make_move(ChessMove::Regular { from: E2, to: E4 })
+            .unwrap();
+
+        let answer = client.go(&game, SearchLimit::Depth(3)).unwrap();
+        assert_eq!(
+            answer.best_move(),
+            Some(ChessMove::Regular { from: E7, to: E5 })
+        );
+        assert_eq!(
+            answer.ponder(),
+            Some(ChessMove::Regular { from: G1, to: F3 })
+        );
+
+        let info = answer.info();
+        assert_eq!(info.len(), 4);
+        assert_eq!(
+            info[0].string(),
+            Some(
+                "position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 moves e2e4"
+            )
+        );
+        assert_eq!(info[1].depth(), Some(1));
+        assert_eq!(info[1].selective_depth(), Some(2));
+        assert_eq!(info[1].score(), Some(Score::Centipawns(20)));
+        assert_eq!(info[1].nodes_per_second(), Some(1000));
+        assert_eq!(info[1].time(), Some(Duration::from_millis(3)));
+        assert_eq!(
+            info[1].pv(),
+            &[
+                ChessMove::Regular { from: E7, to: E5 },
+                ChessMove::Regular { from: G1, to: F3 }
+            ]
+        );
+        assert!(info[2].is_upper_bound());
+        assert_eq!(info[2].score(), Some(Score::Centipawns(-15)));
+        // the second move of this principal variation is illegal
+        assert_eq!(info[2].pv(), &[ChessMove::Regular { from: E7, to: E5 }]);
+        assert_eq!(info[3].score(), Some(Score::Mate(-2)));
+
+        drop(client);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,468 @@
+//! An engine frontend for the Chess Engine Communication Protocol, also known as the xboard
+//! protocol.
+//!
+//! Like the UCI frontend, the protocol is line based. [`run_xboard`] reads commands from any reader
+//! and writes answers to any writer. Moves are sent and received in coordinate notation, such
+//! as `e2e4` or `e7e8q`.
+
+use crate::{
+    game::{Game, GameOver, GameOverReason},
+    search::{allocate_time, is_mate_score, Search, SearchResult, MATE_SCORE},
+    Color,
+};
+use std::{
+    io::{self, BufRead, Write},
+    time::{Duration, Instant},
+};
+
+const ENGINE_NAME: &str = "chess";
+
+/// Depth used when `sd` has not limited the depth of the search.
+const MAX_DEPTH: u32 = 64;
+
+/// Time used for a move when no time control has been given.
+const DEFAULT_MOVE_TIME: Duration = Duration::from_secs(5);
+
+/// Run the xboard protocol, reading commands from `input` and writing answers to `output`,
+/// until `quit` is received or `input` is exhausted.
+///
+/// The engine thinks in the foreground, so commands are not read while it is searching.
+pub fn run_xboard<R, W>(input: R, output: W) -> io::Result<()>
+where
+    R: BufRead,
+    W: Write,
+{
+    let mut engine = Engine::new(output);
+    for line in input.lines() {
+        if !engine.handle_command(&line?)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// A conventional clock time control, as given by the `level` command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Level {
+    /// Moves per time control, or 0 if the whole game is played in one time control.
+    moves_per_control: u32,
+    base: Duration,
+    increment: Duration,
+}
+
+impl Level {
+    /// Parse the arguments of `level MPS BASE INC`, where `BASE` is given in minutes or as
+    /// `minutes:seconds`, and `INC` is given in seconds.
+    fn parse(args: &[&str]) -> Option<Self> {
+        if args.len() != 3 {
+            return None;
+        }
+        let moves_per_control = args[0].parse().ok()?;
+        let base = match args[1].split_once(':') {
+            Some((minutes, seconds)) => Duration::from_secs(
+                minutes
+                    .parse::<u64>()
+                    .ok()?
+                    .checked_mul(60)?
+                    .checked_add(seconds.parse().ok()?)?,
+            ),
+            None => Duration::from_secs(args[1].parse::<u64>().ok()?.checked_mul(60)?),
+        };
+        // rejects negative, infinite and NaN increments, as well as ones too large for a duration
+        let increment = Duration::try_from_secs_f64(args[2].parse().ok()?).ok()?;
+        Some(Self {
+            moves_per_control,
+            base,
+            increment,
+        })
+    }
+}
+
+struct Engine<W> {
+    output: W,
+    game: Game,
+    force_mode: bool,
+    post: bool,
+    max_depth: Option<u32>,
+    time_per_move: Option<Duration>,
+    level: Option<Level>,
+    time_left: Option<Duration>,
+}
+
+impl<W: Write> Engine<W> {
+    fn new(output: W) -> Self {
+        Self {
+            output,
+            game: Game::default(),
+            force_mode: false,
+            post: false,
+            max_depth: None,
+            time_per_move: None,
+            level: None,
+            time_left: None,
+        }
+    }
+
+    fn send(&mut self, line: &str) -> io::Result<()> {
+        writeln!(self.output, "{}", line)?;
+        self.output.flush()
+    }
+
+    /// Handle a single command.
+    ///
+    /// # Returns
+    /// `Ok(false)` if the engine should quit.
+    fn handle_command(&mut self, line: &str) -> io::Result<bool> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let (&command, args) = match parts.split_first() {
+            Some(split) => split,
+            None => return Ok(true),
+        };
+
+        match command {
+            "xboard" | "accepted" | "rejected" | "random" | "hard" | "easy" | "computer"
+            | "otim" | "name" | "rating" => {}
+            "protover" => self.send(&format!(
+                "feature myname=\"{}\" setboard=1 usermove=1 ping=1 playother=0 san=0 \
+                 colors=0 sigint=0 sigterm=0 reuse=1 analyze=0 done=1",
+                ENGINE_NAME
+            ))?,
+            "new" => {
+                self.game = Game::default();
+                self.force_mode = false;
+                self.max_depth = None;
+                self.time_per_move = None;
+            }
+            "force" => self.force_mode = true,
+            "go" => {
+                self.force_mode = false;
+                self.think()?;
+            }
+            "usermove" => match args.first() {
+                Some(&uci) => self.user_move(uci)?,
+                None => self.send("Error (missing move): usermove")?,
+            },
+            "setboard" => match Game::from_fen_string(&args.join(" ")) {
+                Ok(game) => self.game = game,
+                Err(_) => self.send("tellusererror Illegal position")?,
+            },
+            "undo" => {
+                self.game.undo_move();
+            }
+            "remove" => {
+                self.game.undo_move();
+                self.game.undo_move();
+            }
+            "level" => match Level::parse(args) {
+                Some(level) => {
+                    self.level = Some(level);
+                    self.time_per_move = None;
+                }
+                None => self.send(&format!("Error (invalid arguments): {}", line))?,
+            },
+            "st" => match args
+                .first()
+                .and_then(|seconds| seconds.parse().ok())
+                .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+            {
+                Some(time_per_move) if time_per_move > Duration::ZERO => {
+                    self.time_per_move = Some(time_per_move)
+                }
+                _ => self.send(&format!("Error (invalid arguments): {}", line))?,
+            },
+            "sd" => match args.first().and_then(|depth| depth.parse::<u32>().ok()) {
+                Some(depth) if depth > 0 => self.max_depth = Some(depth),
+                _ => self.send(&format!("Error (invalid arguments): {}", line))?,
+            },
+            "time" => {
+                // the engine's clock, in centiseconds
+                self.time_left = args
+                    .first()
+                    .and_then(|centiseconds| centiseconds.parse::<u64>().ok())
+                    .and_then(|centiseconds| centiseconds.checked_mul(10))
+                    .map(Duration::from_millis);
+            }
+            "post" => self.post = true,
+            "nopost" => self.post = false,
+            "ping" => self.send(&format!("pong {}", args.join(" ")))?,
+            "result" => self.force_mode = true,
+            "quit" => return Ok(false),
+            _ => self.send(&format!("Error (unknown command): {}", command))?,
+        }
+
+        Ok(true)
+    }
+
+    fn user_move(&mut self, uci: &str) -> io::Result<()> {
+        if self.game.is_over() {
+            return self.send(&format!("Illegal move (game is over): {}", uci));
+        }
+        let chess_move = match self.game.parse_uci_move(uci) {
+            Ok(chess_move) => chess_move,
+            Err(_) => return self.send(&format!("Illegal move: {}", uci)),
+        };
+        self.game.make_move(chess_move).unwrap();
+
+        if self.report_result()? {
+            return Ok(());
+        }
+        if !self.force_mode {
+            self.think()?;
+        }
+        Ok(())
+    }
+
+    /// Search for a move for the current player, play it and announce it.
+    fn think(&mut self) -> io::Result<()> {
+        if self.report_result()? {
+            return Ok(());
+        }
+
+        let start = Instant::now();
+        let mut search = Search::new(self.max_depth.unwrap_or(MAX_DEPTH));
+        // a budget too far in the future to represent means there is no deadline
+        if let Some(deadline) = start.checked_add(self.time_budget()) {
+            search = search.with_deadline(deadline);
+        }
+        let post = self.post;
+        let mut thinking = Vec::new();
+        let result = search.run_with_callback(&self.game, |result| {
+            if post {
+                thinking.push(thinking_line(result, start.elapsed()));
+            }
+        });
+        for line in thinking {
+            self.send(&line)?;
+        }
+
+        let chess_move = result
+            .best_move()
+            .expect("the game is not over, so there is a legal move");
+        self.game.make_move(chess_move).unwrap();
+        self.send(&format!("move {}", chess_move.to_uci_string()))?;
+        self.report_result()?;
+        Ok(())
+    }
+
+    fn time_budget(&self) -> Duration {
+        if let Some(time_per_move) = self.time_per_move {
+            return time_per_move;
+        }
+        let level = match self.level {
+            Some(level) => level,
+            None => return DEFAULT_MOVE_TIME,
+        };
+
+        let moves_to_go = if level.moves_per_control == 0 {
+            None
+        } else {
+            let moves_played = self.game.full_moves().saturating_sub(1);
+            Some(level.moves_per_control - moves_played % level.moves_per_control)
+        };
+        allocate_time(
+            self.time_left.unwrap_or(level.base),
+            level.increment,
+            moves_to_go,
+        )
+    }
+
+    /// Announce the result if the game is over.
+    ///
+    /// # Returns
+    /// `true` if the game is over.
+    fn report_result(&mut self) -> io::Result<bool> {
+        let (result, reason) = match (self.game.game_result(), self.game.game_over_reason()) {
+            (Some(result), Some(reason)) => (result, reason),
+            _ => return Ok(false),
+        };
+        let line = match (result, reason) {
+            (GameOver::Winner(Color::White), GameOverReason::FlagFall) => {
+                "1-0 {Black forfeits on time}"
+            }
+            (GameOver::Winner(Color::Black), GameOverReason::FlagFall) => {
+                "0-1 {White forfeits on time}"
+            }
+            (GameOver::Winner(Color::White), _) => "1-0 {White mates}",
+            (GameOver::Winner(Color::Black), _) => "0-1 {Black mates}",
+            (GameOver::Draw, GameOverReason::FlagFall) => {
+                "1/2-1/2 {Time forfeit, but no mating material}"
+            }
+            (GameOver::Draw, GameOverReason::Agreement) => "1/2-1/2 {Draw by agreement}",
+            (GameOver::Draw, _) => "1/2-1/2 {Stalemate}",
+        };
+        self.send(line)?;
+        Ok(true)
+    }
+}
+
+/// Format a completed search iteration as `ply score time nodes pv`, with the score in
+/// centipawns and the time in centiseconds.
+fn thinking_line(result: &SearchResult, elapsed: Duration) -> String {
+    let score = if is_mate_score(result.score()) {
+        // mates are conventionally reported as 100000 + moves to mate
+        let moves = (MATE_SCORE - result.score().abs() + 1) / 2;
+        if result.score() > 0 {
+            100_000 + moves
+        } else {
+            -100_000 - moves
+        }
+    } else {
+        result.score()
+    };
+    format!(
+        "{} {} {} {} {}",
+        result.depth(),
+        score,
+        elapsed.as_millis() / 10,
+        result.nodes(),
+        result
+            .best_move()
+            .map(|chess_move| chess_move.to_uci_string())
+            .unwrap_or_default()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn run_commands(commands: &str) -> Vec<String> {
+        let mut output = Vec::new();
+        run_xboard(Cursor::new(commands.to_string()), &mut output).unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| line.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn feature_handshake() {
+        let output = run_commands("xboard\nprotover 2\n");
+        assert_eq!(output.len(), 1);
+        assert!(output[0].starts_with("feature myname=\"chess\""));
+        assert!(output[0].contains("setboard=1"));
+        assert!(output[0].ends_with("done=1"));
+    }
+
+    #[test]
+    fn illegal_move() {
+        let output = run_commands("new\nforce\nusermove e2e5\nusermove e2e4\nping 1\n");
+        assert_eq!(output, vec!["Illegal move: e2e5", "pong 1"]);
+    }
+
+    #[test]
+    fn engine_mates() {
+        let output = run_commands(
+            "new\n\
+             force\n\
+             setboard 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\n\
+             sd 2\n\
+             go\n\
+             usermove g8h8\n",
+        );
+        assert_eq!(
+            output,
+            vec![
+                "move a1a8",
+                "1-0 {White mates}",
+                "Illegal move (game is over): g8h8"
+            ]
+        );
+    }
+
+    #[test]
+    fn stalemate() {
+        let output = run_commands(
+            "new\n\
+             force\n\
+             setboard k7/8/1QK5/8/8/8/8/8 b - - 0 1\n\
+             go\n",
+        );
+        assert_eq!(output, vec!["1/2-1/2 {Stalemate}"]);
+    }
+
+    #[test]
+    fn engine_replies_to_user_moves() {
+        let output = run_commands("new\nsd 1\nusermove e2e4\n");
+        assert_eq!(output.len(), 1);
+        assert!(output[0].starts_with("move "));
+
+        let output = run_commands(
+            "new\n\
+             force\n\
+             usermove f2f3\n\
+             usermove e7e5\n\
+             usermove g2g4\n\
+             sd 2\n\
+             go\n",
+        );
+        assert_eq!(output, vec!["move d8h4", "0-1 {Black mates}"]);
+    }
+
+    #[test]
+    fn undo_and_setboard() {
+        let output = run_commands(
+            "new\n\
+             force\n\
+             usermove e2e4\n\
+             undo\n\
+             usermove d2d4\n\
+             remove\n\
+             usermove e2e5\n\
+             setboard not a fen\n",
+        );
+        assert_eq!(
+            output,
+            vec!["Illegal move: e2e5", "tellusererror Illegal position"]
+        );
+    }
+
+    #[test]
+    fn invalid_time_controls() {
+        let output = run_commands("st 0\nst -5\nst inf\nst NaN\nst 1e300\nlevel 40 5 inf\n");
+        assert_eq!(
+            output,
+            vec![
+                "Error (invalid arguments): st 0",
+                "Error (invalid arguments): st -5",
+                "Error (invalid arguments): st inf",
+                "Error (invalid arguments): st NaN",
+                "Error (invalid arguments): st 1e300",
+                "Error (invalid arguments): level 40 5 inf",
+            ]
+        );
+
+        // a clock too large to represent doesn't crash the engine
+        let output = run_commands("new\nsd 1\ntime 18446744073709551615\nusermove e2e4\n");
+        assert_eq!(output.len(), 1);
+        assert!(output[0].starts_with("move "));
+    }
+
+    #[test]
+    fn level() {
+        assert_eq!(
+            Level::parse(&["40", "0:30", "0"]),
+            Some(Level {
+                moves_per_control: 40,
+                base: Duration::from_secs(30),
+                increment: Duration::from_secs(0),
+            })
+        );
+        assert_eq!(
+            Level::parse(&["0", "2", "12"]),
+            Some(Level {
+                moves_per_control: 0,
+                base: Duration::from_secs(120),
+                increment: Duration::from_secs(12),
+            })
+        );
+        assert_eq!(Level::parse(&["40", "5"]), None);
+        assert_eq!(Level::parse(&["40", "5", "-1"]), None);
+        assert_eq!(Level::parse(&["40", "5", "inf"]), None);
+        assert_eq!(Level::parse(&["40", "5", "NaN"]), None);
+        assert_eq!(Level::parse(&["40", "5", "1e300"]), None);
+        assert_eq!(Level::parse(&["40", "18446744073709551615", "0"]), None);
+    }
+}
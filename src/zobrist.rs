@@ -0,0 +1,96 @@
+use crate::{game::Game, piece::PieceType};
+use bitboard::*;
+
+const SIDE_TO_MOVE: u64 = 12 * 64;
+const CASTLING: u64 = SIDE_TO_MOVE + 1;
+const EN_PASSANT: u64 = CASTLING + 4;
+
+/// The Zobrist key with the given number, derived with the SplitMix64 generator so that hashes
+/// stay the same between runs and versions.
+fn key(index: u64) -> u64 {
+    let mut z = index.wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Hash the current position of `game`: the pieces, the player to move, the castling rights,
+/// and the en passant target if an en passant capture is possible.
+pub(crate) fn hash(game: &Game) -> u64 {
+    let board = game.board();
+    let mut hash = 0;
+    for (square, &position) in INCREASING_A1_B1.iter().enumerate() {
+        if let Some(piece) = board.get_piece(position) {
+            let kind = PieceType::all_iter()
+                .position(|kind| kind == piece.kind())
+                .unwrap();
+            let color = if piece.color().is_white() { 0 } else { 6 };
+            hash ^= key(((color + kind) * 64 + square) as u64);
+        }
+    }
+    if game.current_player().is_black() {
+        hash ^= key(SIDE_TO_MOVE);
+    }
+    let rights = game.castling_rights();
+    let flags = [
+        rights.white_kingside(),
+        rights.white_queenside(),
+        rights.black_kingside(),
+        rights.black_queenside(),
+    ];
+    for (index, _) in flags.iter().enumerate().filter(|(_, &flag)| flag) {
+        hash ^= key(CASTLING + index as u64);
+    }
+    if let Some(en_passant) = game.get_moves().iter().find(|m| m.is_en_passant()) {
+        hash ^= key(EN_PASSANT + u8::from(en_passant.to().file()) as u64);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(game: &mut Game, moves: &[&str]) {
+        for uci in moves {
+            game.make_move(game.parse_uci_move(uci).unwrap()).unwrap();
+        }
+    }
+
+    #[test]
+    fn transpositions() {
+        let mut first = Game::default();
+        play(&mut first, &["g1f3", "g8f6", "b1c3"]);
+        let mut second = Game::default();
+        play(&mut second, &["b1c3", "g8f6", "g1f3"]);
+        assert_eq!(hash(&first), hash(&second));
+
+        let mut third = Game::default();
+        play(
+            &mut third,
+            &["b1c3", "g8f6", "g1f3", "f6g8", "f3g1", "g8f6"],
+        );
+        assert_ne!(hash(&third), hash(&first));
+        play(&mut third, &["g1f3"]);
+        assert_eq!(hash(&third), hash(&first));
+    }
+
+    #[test]
+    fn castling_and_en_passant() {
+        let game = Game::from_fen_string("4k3/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        let without_rights = Game::from_fen_string("4k3/8/8/8/8/8/8/4K2R w - - 0 1").unwrap();
+        assert_ne!(hash(&game), hash(&without_rights));
+
+        // en passant is only part of the position if the capture is possible
+        let mut possible = Game::default();
+        play(&mut possible, &["e2e4", "a7a6", "e4e5", "d7d5"]);
+        let mut transposed = Game::default();
+        play(&mut transposed, &["e2e4", "d7d5", "e4e5", "a7a6"]);
+        assert_ne!(hash(&possible), hash(&transposed));
+        let mut impossible = Game::default();
+        play(&mut impossible, &["e2e4", "a7a6", "e4e5", "h7h5"]);
+        let mut transposed = Game::default();
+        play(&mut transposed, &["e2e4", "h7h5", "e4e5", "a7a6"]);
+        assert_eq!(hash(&impossible), hash(&transposed));
+    }
+}
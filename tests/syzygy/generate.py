@@ -0,0 +1,400 @@
+#!/usr/bin/env python3
+"""Generate the KQvK and KRvK Syzygy tables used by the tests of `src/syzygy.rs`.
+
+The values are found by retrograde analysis, and written in the Syzygy file format: the
+positions are indexed as the format describes, and the values are compressed by
+recursive pairing followed by canonical Huffman coding. The WDL tables store both sides to
+move, while the DTZ tables only store white to move, in moves rather than plies, through a
+value map.
+
+Run it from this directory to rewrite the tables:
+
+    python3 generate.py
+"""
+
+import heapq
+import struct
+from collections import Counter
+
+WDL_MAGIC = bytes([0x71, 0xE8, 0x23, 0x5D])
+DTZ_MAGIC = bytes([0xD7, 0x66, 0x0C, 0xA5])
+
+KING_STEPS = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)]
+ROOK_LINES = [(-1, 0), (1, 0), (0, -1), (0, 1)]
+QUEEN_LINES = ROOK_LINES + [(-1, -1), (-1, 1), (1, -1), (1, 1)]
+
+
+def file_of(square):
+    return square & 7
+
+
+def rank_of(square):
+    return square >> 3
+
+
+def step(square, df, dr):
+    f, r = file_of(square) + df, rank_of(square) + dr
+    return r * 8 + f if 0 <= f < 8 and 0 <= r < 8 else None
+
+
+def king_targets(square):
+    return [t for t in (step(square, df, dr) for df, dr in KING_STEPS) if t is not None]
+
+
+def slider_targets(square, lines, occupied):
+    targets = []
+    for df, dr in lines:
+        t = step(square, df, dr)
+        while t is not None:
+            targets.append(t)
+            if t in occupied:
+                break
+            t = step(t, df, dr)
+    return targets
+
+
+def adjacent(a, b):
+    return abs(file_of(a) - file_of(b)) <= 1 and abs(rank_of(a) - rank_of(b)) <= 1
+
+
+# Retrograde analysis of king and piece against king. A state is (white king, white piece,
+# black king), and the result is the number of plies until mate for white to move, or the
+# number of plies until being mated for black to move, or None for draws.
+
+
+def solve(lines):
+    def state(wk, p, bk):
+        return (wk * 64 + p) * 64 + bk
+
+    def attacked(square, wk, p, bk):
+        return square in slider_targets(p, lines, {wk, bk} - {square})
+
+    legal_w, legal_b = set(), set()
+    for wk in range(64):
+        for p in range(64):
+            for bk in range(64):
+                if len({wk, p, bk}) < 3 or adjacent(wk, bk):
+                    continue
+                legal_b.add(state(wk, p, bk))
+                if not attacked(bk, wk, p, bk):
+                    legal_w.add(state(wk, p, bk))
+
+    def split(s):
+        return s // 4096, (s // 64) % 64, s % 64
+
+    # moves of black, and whether black can take the piece for a draw
+    black_moves, black_draws = {}, set()
+    for s in legal_b:
+        wk, p, bk = split(s)
+        moves = []
+        for t in king_targets(bk):
+            if t == wk or adjacent(t, wk):
+                continue
+            if t == p:
+                black_draws.add(s)
+                continue
+            if t in slider_targets(p, lines, {wk}):
+                continue
+            moves.append(state(wk, p, t))
+        black_moves[s] = moves
+
+    white_moves = {}
+    for s in legal_w:
+        wk, p, bk = split(s)
+        moves = [state(t, p, bk) for t in king_targets(wk) if t != p and not adjacent(t, bk)]
+        moves += [state(wk, t, bk) for t in slider_targets(p, lines, {wk, bk}) if t not in (wk, bk)]
+        white_moves[s] = moves
+
+    predecessors = {s: [] for s in legal_b}
+    for s, moves in white_moves.items():
+        for t in moves:
+            predecessors[t].append(s)
+
+    white, black = {}, {}
+    layer = []
+    for s in legal_b:
+        wk, p, bk = split(s)
+        if not black_moves[s] and s not in black_draws and attacked(bk, wk, p, bk):
+            black[s] = 0
+            layer.append(s)
+    remaining = {s: len(moves) for s, moves in black_moves.items()}
+    black_predecessors = {s: [] for s in legal_w}
+    for s, moves in black_moves.items():
+        for t in moves:
+            black_predecessors[t].append(s)
+
+    while layer:
+        won = []
+        for s in layer:
+            for w in predecessors[s]:
+                if w not in white:
+                    white[w] = black[s] + 1
+                    won.append(w)
+        layer = []
+        for w in won:
+            for b in black_predecessors[w]:
+                remaining[b] -= 1
+                if remaining[b] == 0 and b not in black_draws and b not in black:
+                    black[b] = white[w] + 1
+                    layer.append(b)
+
+    return legal_w, legal_b, white, black, split
+
+
+# Indexing of three unique pieces, as the format describes it.
+
+TRIANGLE = [0] * 64
+for code, square in enumerate([1, 2, 3, 10, 11, 19, 0, 9, 18, 27]):
+    TRIANGLE[square] = code
+LOWER = [0] * 64
+for code, square in enumerate(s for s in range(64) if rank_of(s) < file_of(s)):
+    LOWER[square] = code
+
+
+def off_diagonal(square):
+    return rank_of(square) - file_of(square)
+
+
+def flip_diagonal(square):
+    return ((square >> 3) | (square << 3)) & 63
+
+
+def encode(squares):
+    p = list(squares)
+    if p[0] & 4:
+        p = [s ^ 7 for s in p]
+    if p[0] & 0x20:
+        p = [s ^ 0x38 for s in p]
+    for s in p:
+        if off_diagonal(s) != 0:
+            if off_diagonal(s) > 0:
+                p = [flip_diagonal(s) for s in p]
+            break
+    s1 = int(p[1] > p[0])
+    s2 = int(p[2] > p[0]) + int(p[2] > p[1])
+    if off_diagonal(p[0]):
+        return TRIANGLE[p[0]] * 63 * 62 + (p[1] - s1) * 62 + (p[2] - s2)
+    if off_diagonal(p[1]):
+        return 6 * 63 * 62 + rank_of(p[0]) * 28 * 62 + LOWER[p[1]] * 62 + p[2] - s2
+    if off_diagonal(p[2]):
+        return (
+            6 * 63 * 62
+            + 4 * 28 * 62
+            + rank_of(p[0]) * 7 * 28
+            + (rank_of(p[1]) - s1) * 28
+            + LOWER[p[2]]
+        )
+    return (
+        6 * 63 * 62
+        + 4 * 28 * 62
+        + 4 * 7 * 28
+        + rank_of(p[0]) * 7 * 6
+        + (rank_of(p[1]) - s1) * 6
+        + (rank_of(p[2]) - s2)
+    )
+
+
+TABLE_SIZE = 6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + 4 * 7 * 6
+
+
+def fill(values):
+    """Give positions that can't occur the value before them, which compresses best."""
+    previous = next(v for v in values if v is not None)
+    filled = []
+    for value in values:
+        previous = previous if value is None else value
+        filled.append(previous)
+    return filled
+
+
+# Compression of a table of values.
+
+
+def pair(values, max_symbols=64):
+    """Recursively replace the most frequent pair of adjacent symbols by a new symbol."""
+    leaves = sorted(set(values))
+    symbols = [(value, None) for value in leaves]
+    lengths = [1] * len(leaves)
+    sequence = [leaves.index(value) for value in values]
+    while len(symbols) < max_symbols:
+        counts = Counter(
+            pair
+            for pair in zip(sequence, sequence[1:])
+            if lengths[pair[0]] + lengths[pair[1]] <= 256
+        )
+        if not counts:
+            break
+        (left, right), count = counts.most_common(1)[0]
+        if count < 8:
+            break
+        new = len(symbols)
+        symbols.append((left, right))
+        lengths.append(lengths[left] + lengths[right])
+        replaced, i = [], 0
+        while i < len(sequence):
+            if i + 1 < len(sequence) and (sequence[i], sequence[i + 1]) == (left, right):
+                replaced.append(new)
+                i += 2
+            else:
+                replaced.append(sequence[i])
+                i += 1
+        sequence = replaced
+    return symbols, lengths, sequence
+
+
+def code_lengths(frequencies):
+    if len(frequencies) == 1:
+        return {sym: 1 for sym in frequencies}
+    heap = [(freq, i, [sym]) for i, (sym, freq) in enumerate(sorted(frequencies.items()))]
+    heapq.heapify(heap)
+    depth = {sym: 0 for sym in frequencies}
+    counter = len(heap)
+    while len(heap) > 1:
+        f1, _, s1 = heapq.heappop(heap)
+        f2, _, s2 = heapq.heappop(heap)
+        for sym in s1 + s2:
+            depth[sym] += 1
+        heapq.heappush(heap, (f1 + f2, counter, s1 + s2))
+        counter += 1
+    return depth
+
+
+def compress(values, flags, block_size_log, span_log):
+    """Encode a subtable, returning its size header, sparse index, block lengths and data."""
+    if len(set(values)) == 1:
+        return bytes([flags | 0x80, values[0]]), b"", b"", b""
+
+    symbols, lengths, sequence = pair(values)
+    depth = code_lengths(Counter(sequence))
+    min_len, max_len = min(depth.values()), max(depth.values())
+    assert max_len <= 32
+
+    # number the coded symbols from the longest code, followed by the others
+    coded = sorted(depth, key=lambda sym: (-depth[sym], sym))
+    order = coded + [sym for sym in range(len(symbols)) if sym not in depth]
+    ids = {sym: i for i, sym in enumerate(order)}
+
+    # canonical codes: the lowest code of a length follows the codes of the next longer one
+    lowest, codes = {}, {}
+    base, first = 0, 0
+    for length in range(max_len, min_len - 1, -1):
+        syms = [sym for sym in coded if depth[sym] == length]
+        lowest[length] = first
+        first += len(syms)
+        for j, sym in enumerate(syms):
+            codes[sym] = (base + j, length)
+        if length > min_len:
+            assert (base + len(syms)) % 2 == 0
+            base = (base + len(syms)) // 2
+
+    # split the symbols into blocks
+    block_bits = 8 << block_size_log
+    blocks, block_values = [], []
+    bits, count = "", 0
+    for sym in sequence:
+        code, length = codes[sym]
+        if len(bits) + length > block_bits:
+            blocks.append(bits)
+            block_values.append(count)
+            bits, count = "", 0
+        bits += format(code, "0{}b".format(length))
+        count += lengths[sym]
+    blocks.append(bits)
+    block_values.append(count)
+    assert max(block_values) <= 65536
+
+    data = b"".join(
+        int(bits.ljust(block_bits, "0"), 2).to_bytes(block_bits // 8, "big") for bits in blocks
+    )
+    block_lengths = b"".join(struct.pack("<H", count - 1) for count in block_values)
+
+    starts = [0]
+    for count in block_values:
+        starts.append(starts[-1] + count)
+    span = 1 << span_log
+    sparse_index = b""
+    for k in range((len(values) + span - 1) // span):
+        position = k * span + span // 2
+        block = max(b for b in range(len(blocks)) if starts[b] <= position)
+        sparse_index += struct.pack("<IH", block, position - starts[block])
+
+    header = bytes([flags, block_size_log, span_log, 0])
+    header += struct.pack("<I", len(blocks))
+    header += bytes([max_len, min_len])
+    for length in range(min_len, max_len + 1):
+        header += struct.pack("<H", lowest[length])
+    header += struct.pack("<H", len(symbols))
+    for sym in order:
+        left, right = symbols[sym]
+        if right is None:
+            left, right = left, 0xFFF
+        else:
+            left, right = ids[left], ids[right]
+        header += bytes([left & 0xFF, (left >> 8) | ((right & 0xF) << 4), right >> 4])
+    if len(symbols) & 1:
+        header += b"\0"
+    return header, sparse_index, block_lengths, data
+
+
+def write_table(path, magic, asymmetric, pieces, subtables, dtz_map=b""):
+    header = bytearray(magic + bytes([1 if asymmetric else 0, 0]))
+    for piece in pieces:
+        header.append(piece | (piece << 4))
+    if len(header) & 1:
+        header.append(0)
+    parts = [compress(*subtable) for subtable in subtables]
+    for size_header, _, _, _ in parts:
+        header += size_header
+    header += dtz_map
+    if len(header) & 1:
+        header.append(0)
+    for _, sparse_index, _, _ in parts:
+        header += sparse_index
+    for _, _, block_lengths, _ in parts:
+        header += block_lengths
+    for _, _, _, data in parts:
+        if data:
+            header += bytes(-len(header) % 64)
+            header += data
+    with open(path, "wb") as f:
+        f.write(header)
+
+
+def generate(name, code, lines):
+    legal_w, legal_b, white, black, split = solve(lines)
+    assert legal_w <= set(white), "every position with white to move is won"
+
+    wdl = [[None] * TABLE_SIZE, [None] * TABLE_SIZE]
+    dtz = [None] * TABLE_SIZE
+    for side, legal in enumerate([legal_w, legal_b]):
+        for s in legal:
+            wk, p, bk = split(s)
+            idx = encode([p, wk, bk])
+            value = 4 if side == 0 else (0 if s in black else 2)
+            assert wdl[side][idx] in (None, value)
+            wdl[side][idx] = value
+            if side == 0:
+                moves = (white[s] - 1) // 2
+                assert dtz[idx] in (None, moves)
+                dtz[idx] = moves
+
+    # the DTZ table stores the distances through a map, the most frequent one first
+    by_frequency = [v for v, _ in Counter(v for v in dtz if v is not None).most_common()]
+    mapped = [None if v is None else by_frequency.index(v) for v in dtz]
+    dtz_map = bytes([len(by_frequency)] + by_frequency + [0, 0, 0])
+
+    pieces = [code, 6, 14]
+    write_table(
+        name + ".rtbw",
+        WDL_MAGIC,
+        True,
+        pieces,
+        [(fill(wdl[0]), 0, 5, 6), (fill(wdl[1]), 0, 5, 6)],
+    )
+    write_table(name + ".rtbz", DTZ_MAGIC, True, pieces, [(fill(mapped), 2, 5, 6)], dtz_map)
+    print(name, "longest win", max(white.values()), "longest loss", max(black.values()))
+
+
+if __name__ == "__main__":
+    generate("KQvK", 5, QUEEN_LINES)
+    generate("KRvK", 4, ROOK_LINES)